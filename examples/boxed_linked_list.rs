@@ -1,5 +1,6 @@
-use index_alloc::boxed::Box;
-use index_alloc::{IndexAllocator, IndexError};
+use core::mem;
+
+use index_alloc::prelude::*;
 
 pub struct ListIterator<'a, 'b, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
 where
@@ -18,7 +19,7 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         match self.list {
             List::Cons(val, next) => {
-                self.list = &*next;
+                self.list = next;
                 Some(val)
             }
             List::Nil(_) => None,
@@ -42,12 +43,31 @@ impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
     }
 
     pub fn push(&mut self, val: T) -> Result<(), IndexError> {
-        match self {
-            Self::Nil(allocator) => {
-                *self = Self::Cons(val, allocator.try_boxed(Self::Nil(allocator))?);
-                Ok(())
-            }
-            Self::Cons(_, list) => list.push(val),
+        // Walk to the end of the list with a loop rather than recursively, which could overflow the stack on long lists.
+        let mut last = self;
+        while let Self::Cons(_, next) = last {
+            last = next;
+        }
+        if let Self::Nil(allocator) = *last {
+            *last = Self::Cons(val, allocator.try_boxed(Self::Nil(allocator))?);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Drop
+    for List<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn drop(&mut self) {
+        // Unlink the boxes one by one rather than recursively, which could overflow the stack on long lists:
+        // each box is emptied before being dropped, so dropping it doesn't drop the rest of the list.
+        let Self::Cons(_, next) = self else {
+            return;
+        };
+        let allocator = next.allocator();
+        let mut current = mem::replace(&mut **next, Self::Nil(allocator));
+        while let Self::Cons(_, next) = &mut current {
+            current = mem::replace(&mut **next, Self::Nil(allocator));
         }
     }
 }
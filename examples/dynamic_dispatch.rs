@@ -1,12 +1,11 @@
-use index_alloc::boxed::Box;
-use index_alloc::IndexAllocator;
+use index_alloc::prelude::*;
 
 const MEMORY_SIZE: usize = 1024;
 const INDEX_SIZE: usize = 32;
 
-static ALLOCATOR: IndexAllocator<MEMORY_SIZE, INDEX_SIZE> = IndexAllocator::empty();
+static ALLOCATOR: SingleThreaded<MEMORY_SIZE, INDEX_SIZE> = unsafe { SingleThreaded::empty() };
 
-pub type BoxedListener<'a> = Box<'a, dyn Listener + 'static, MEMORY_SIZE, INDEX_SIZE>;
+pub type BoxedListener<'a> = Box<'a, dyn Listener + 'a, MEMORY_SIZE, INDEX_SIZE>;
 
 pub struct EventDispatcher<'a, const N: usize> {
     allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
@@ -16,18 +15,17 @@ pub struct EventDispatcher<'a, const N: usize> {
 
 impl<'a, const N: usize> EventDispatcher<'a, N> {
     pub fn empty(allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
-        const NONE: Option<BoxedListener> = None;
         Self {
             allocator,
-            listeners: [NONE; N],
+            listeners: [const { None }; N],
             counter: 0,
         }
     }
 
     pub fn register<T>(&mut self, listener: T)
     where
-        T: Listener + 'static,
-        &'a mut (dyn Listener + 'static): From<&'a mut T>,
+        T: Listener + 'a,
+        &'a mut (dyn Listener + 'a): From<&'a mut T>,
     {
         if self.counter >= N {
             panic!("Out of listeners");
@@ -35,17 +33,15 @@ impl<'a, const N: usize> EventDispatcher<'a, N> {
 
         self.listeners[self.counter] = Some(
             self.allocator
-                .try_boxed::<'a, dyn Listener + 'static, T>(listener)
+                .try_boxed::<'a, 'a, dyn Listener + 'a, T>(listener)
                 .unwrap(),
         );
         self.counter += 1;
     }
 
     pub fn send(&mut self, name: &str) {
-        for listener in self.listeners.iter_mut() {
-            if let Some(l) = listener {
-                l.on_event(name);
-            }
+        for listener in self.listeners.iter_mut().flatten() {
+            listener.on_event(name);
         }
     }
 }
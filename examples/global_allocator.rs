@@ -1,7 +1,7 @@
-use index_alloc::IndexAllocator;
+use index_alloc::sync::SingleThreaded;
 
 #[global_allocator]
-static ALLOCATOR: IndexAllocator<2048, 32> = IndexAllocator::empty();
+static ALLOCATOR: SingleThreaded<2048, 32> = unsafe { SingleThreaded::empty() };
 
 fn main() {
     let mut test_str = String::from("Hello World!\n");
@@ -9,10 +9,7 @@ fn main() {
 
     println!("{test_str}");
 
-    let test_vec: Vec<String> = (0..=10)
-        .into_iter()
-        .map(|i| format!("Number {i}"))
-        .collect();
+    let test_vec: Vec<String> = (0..=10).map(|i| format!("Number {i}")).collect();
 
     println!("{:?}", test_vec);
 }
@@ -0,0 +1,15 @@
+// Safety: the example never allocates from two threads.
+index_alloc::index_global_alloc!(
+    #[link_section = ".data.heap"]
+    unsafe static HEAP: 4096, 64
+);
+
+fn main() {
+    println!("Before allocating : {:?}", heap_stats());
+
+    let test_vec: Vec<u32> = (0..32).collect();
+    println!("With a Vec of {} u32 : {:?}", test_vec.len(), heap_stats());
+
+    drop(test_vec);
+    println!("After dropping it : {:?}", heap_stats());
+}
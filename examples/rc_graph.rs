@@ -1,5 +1,4 @@
-use index_alloc::rc::{Rc, Weak};
-use index_alloc::IndexAllocator;
+use index_alloc::prelude::*;
 
 const MEMORY_SIZE: usize = 1024;
 const INDEX_SIZE: usize = 64;
@@ -42,7 +41,7 @@ impl<'a, T> Node<'a, T> {
 
     fn add_link(&mut self, link: Link<'a, T>) {
         for l in self.links.iter_mut() {
-            if matches!(l, None) {
+            if l.is_none() {
                 *l = Some(link);
                 return;
             }
@@ -63,7 +62,7 @@ fn main() {
 
     let second_child_node = {
         let mut node = Node::new("Second child node");
-        node.add_link(Link::Weak(first_child_node.downgrade()));
+        node.add_link(Link::new_weak(first_child_node.downgrade()));
         Rc::try_new(node, &allocator).unwrap()
     };
 
@@ -0,0 +1,95 @@
+//! Link-time check that the core paths of `index_alloc` can't panic.
+//!
+//! Built with the `no-panic` profile (`cargo build -p index_alloc_no_panic --profile no-panic`),
+//! this is a `no_std` binary whose panic handler references a symbol that doesn't exist:
+//! if any panic remains reachable from the functions exercised below after optimization,
+//! linking fails.
+//!
+//! With any other profile it is a regular binary running the same functions,
+//! so that it builds along with the rest of the workspace.
+
+#![cfg_attr(not(debug_assertions), no_std, no_main)]
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::hint::black_box;
+
+use index_alloc::rc::Rc;
+use index_alloc::sync::SingleThreaded;
+
+static ALLOCATOR: SingleThreaded<1024, 16> = unsafe { SingleThreaded::empty() };
+
+/// Run every core path of the allocator.
+fn exercise() {
+    let allocator = black_box(&ALLOCATOR);
+
+    unsafe {
+        let layout = black_box(Layout::from_size_align_unchecked(16, 8));
+        let ptr = allocator.alloc(layout);
+        allocator.dealloc(ptr, layout);
+        allocator.dealloc(black_box(core::ptr::null_mut()), layout);
+
+        // Grown in place, shrunk, then moved past another allocation.
+        let ptr = allocator.alloc(layout);
+        let ptr = allocator.realloc(ptr, layout, black_box(48));
+        let layout = black_box(Layout::from_size_align_unchecked(48, 8));
+        let ptr = allocator.realloc(ptr, layout, black_box(24));
+        let layout = black_box(Layout::from_size_align_unchecked(24, 8));
+        let blocker = allocator.alloc(layout);
+        let ptr = allocator.realloc(ptr, layout, black_box(256));
+        allocator.dealloc(blocker, layout);
+        allocator.dealloc(ptr, Layout::from_size_align_unchecked(256, 8));
+
+        let zeroed = allocator.alloc_zeroed(layout);
+        black_box(zeroed);
+        allocator.dealloc(zeroed, layout);
+    }
+
+    allocator.set_free_scrub(black_box(Some(0xDD)));
+    let _ = allocator.clear_to_pattern(black_box(0xAA));
+
+    if let Ok(boxed) = allocator.try_boxed(black_box([1u8, 2, 3, 4])) {
+        black_box(&*boxed);
+    }
+    if let Ok(boxed) = allocator.try_boxed(black_box([1u32, 2, 3, 4])) {
+        let _ = black_box(boxed.try_free());
+    }
+    if let Ok(boxed) = allocator.try_boxed_no_panic(black_box([1u64, 2])) {
+        black_box(&*boxed);
+    }
+    black_box(allocator.has_failed_drop());
+
+    if let Ok(rc) = Rc::try_new(black_box([1u16, 2, 3, 4]), allocator) {
+        let clone = rc.clone();
+        let weak = rc.downgrade();
+        drop(rc);
+        black_box(weak.upgrade());
+        drop(clone);
+        black_box(weak.upgrade());
+    }
+}
+
+#[cfg(debug_assertions)]
+fn main() {
+    exercise();
+}
+
+#[cfg(not(debug_assertions))]
+#[link(name = "c")]
+extern "C" {}
+
+#[cfg(not(debug_assertions))]
+#[no_mangle]
+extern "C" fn main(_argc: core::ffi::c_int, _argv: *const *const u8) -> core::ffi::c_int {
+    exercise();
+    0
+}
+
+#[cfg(not(debug_assertions))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    extern "C" {
+        /// Doesn't exist: any reachable panic makes the link fail.
+        fn index_alloc_core_paths_must_not_panic() -> !;
+    }
+    unsafe { index_alloc_core_paths_must_not_panic() }
+}
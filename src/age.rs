@@ -0,0 +1,159 @@
+//! This module contains the allocation age tracking, to hunt slow leaks by finding the old live allocations.
+//!
+//! It is only available with the `age` feature. Once a clock is set with [`IndexAllocator::set_clock`],
+//! every allocation is timestamped, and the age of the live allocations is the time elapsed since then.
+//! The clock is a plain tick counter: its unit is up to the user, and it may wrap around.
+
+use crate::{IndexAllocator, IndexError};
+
+/// The live allocations matching an age filter, see [`IndexAllocator::live_older_than`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AgeStats {
+    /// The number of allocations.
+    pub count: usize,
+    /// The total size of the regions holding them.
+    pub bytes: usize,
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Set the clock timestamping the following allocations, replacing the previous one.
+    ///
+    /// The clock is called on every allocation, so it should be cheap, and it must not allocate through this allocator.
+    /// The allocations made before the clock is set have no age.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicU32, Ordering};
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// static TICKS: AtomicU32 = AtomicU32::new(0);
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// allocator.set_clock(|| TICKS.load(Ordering::Relaxed));
+    ///
+    /// let test_box = allocator.try_boxed([1u8; 4]).unwrap();
+    /// TICKS.store(10, Ordering::Relaxed);
+    /// assert_eq!(allocator.allocation_age(test_box.as_ptr().cast()), Ok(Some(10)));
+    /// ```
+    pub fn set_clock(&self, clock: fn() -> u32) {
+        self.clock.set(Some(clock));
+    }
+
+    /// Remove the clock set with [`IndexAllocator::set_clock`].
+    /// The following allocations have no age, the live ones keep their timestamp.
+    pub fn clear_clock(&self) {
+        self.clock.set(None);
+    }
+
+    /// Read the clock, if it is set.
+    pub(crate) fn now(&self) -> Option<u32> {
+        self.clock.get().map(|clock| clock())
+    }
+
+    /// Compute the age of the allocation holding `ptr`, or `None` if it was made without a clock.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::EmptyPtr`] if `ptr` is null,
+    /// an [`IndexError::OutOfMemory`] if it isn't in the memory pool,
+    /// an [`IndexError::NoSuchRegion`] if it isn't in a used region,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn allocation_age(&self, ptr: *const u8) -> Result<Option<u32>, IndexError> {
+        let offset = self.offset_of(ptr)?;
+        let now = self.now();
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region = index.get_region(index.find_region(offset)?)?;
+        if !region.used {
+            return Err(IndexError::NoSuchRegion);
+        }
+
+        Ok(age(now, region.allocated_at))
+    }
+
+    /// Count the live allocations older than `ticks`, and the bytes they hold, for periodic leak checks.
+    ///
+    /// Allocations made without a clock are never counted, nor is anything when the clock isn't set.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn live_older_than(&self, ticks: u32) -> Result<AgeStats, IndexError> {
+        let now = self.now();
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        Ok(index
+            .regions()
+            .filter(|region| region.used)
+            .filter(|region| age(now, region.allocated_at).is_some_and(|age| age > ticks))
+            .fold(AgeStats::default(), |stats, region| AgeStats {
+                count: stats.count + 1,
+                bytes: stats.bytes + region.size,
+            }))
+    }
+}
+
+/// Compute the age of an allocation made at `allocated_at`, the clock reading `now`.
+pub(crate) fn age(now: Option<u32>, allocated_at: Option<u32>) -> Option<u32> {
+    Some(now?.wrapping_sub(allocated_at?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    std::thread_local! {
+        /// The time of the fake clock, per thread so that the tests don't share it.
+        static TIME: Cell<u32> = const { Cell::new(0) };
+    }
+
+    fn clock() -> u32 {
+        TIME.with(Cell::get)
+    }
+
+    fn advance(ticks: u32) {
+        TIME.with(|time| time.set(time.get() + ticks));
+    }
+
+    #[test]
+    fn test_allocation_age() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let untimed = allocator.try_boxed([0u8; 4]).unwrap();
+        allocator.set_clock(clock);
+        let old = allocator.try_boxed([0u8; 16]).unwrap();
+        advance(100);
+        let young = allocator.try_boxed([0u8; 32]).unwrap();
+        advance(5);
+
+        assert_eq!(allocator.allocation_age(untimed.as_ptr().cast()), Ok(None));
+        assert_eq!(allocator.allocation_age(old.as_ptr().cast()), Ok(Some(105)));
+        assert_eq!(allocator.allocation_age(young.as_ptr().cast()), Ok(Some(5)));
+
+        let old_size = 16 + 2 * crate::REDZONE_SIZE;
+        assert_eq!(
+            allocator.live_older_than(10),
+            Ok(AgeStats {
+                count: 1,
+                bytes: old_size
+            })
+        );
+        assert_eq!(allocator.live_older_than(1).map(|stats| stats.count), Ok(2));
+        assert_eq!(allocator.live_older_than(105), Ok(AgeStats::default()));
+
+        drop(old);
+        assert_eq!(allocator.live_older_than(1).map(|stats| stats.count), Ok(1));
+
+        allocator.clear_clock();
+        assert_eq!(allocator.live_older_than(0), Ok(AgeStats::default()));
+        assert_eq!(allocator.allocation_age(young.as_ptr().cast()), Ok(None));
+    }
+}
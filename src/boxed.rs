@@ -1,9 +1,13 @@
 //! This module contains the [`Box`] smart pointer, capable of managing memory in a [`IndexAllocator`].
 
 use core::fmt::Debug;
+use core::mem::MaybeUninit;
 use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::{mem, ptr, slice};
 
-use crate::{IndexAllocator, IndexError};
+use crate::{IndexAllocator, IndexError, UnwindGuard};
 
 /// A smart pointer holding its value in an [`IndexAllocator`] and managing its memory.
 ///
@@ -32,14 +36,96 @@ use crate::{IndexAllocator, IndexError};
 /// For more information, see the [`Dynamic dispatch example`].
 ///
 /// [`Dynamic dispatch example`]: https://github.com/Adi-df/index_alloc/blob/master/examples/dynamic_dispatch_example.rs
+///
+/// # Thread safety
+///
+/// A [`Box`] holds a shared reference to its [`IndexAllocator`], which isn't [`Sync`] as its bookkeeping isn't thread-safe,
+/// so a [`Box`] is neither [`Send`] nor [`Sync`], whatever `T` is. It must be dropped by the thread which allocated it,
+/// even when the allocator is wrapped in a [`SingleThreaded`](crate::sync::SingleThreaded) static:
+///
+/// ```compile_fail
+/// use index_alloc::sync::SingleThreaded;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+///
+/// static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+///
+/// let test_box = ALLOCATOR.try_boxed([1, 2, 3, 4]).unwrap();
+/// assert_send(&test_box);
+/// ```
+///
+/// This also holds for a `Locked` allocator, even when `T` is [`Send`]: its [`Box`] borrows the [`IndexAllocator`]
+/// inside `Locked::with`, and another thread dropping it would free the memory outside of the critical section,
+/// racing the other users of the allocator. The value can still cross threads once moved out of the [`Box`].
+#[cfg_attr(
+    feature = "critical-section",
+    doc = r#"
+```compile_fail
+use index_alloc::sync::Locked;
+
+fn assert_send<T: Send>(_: &T) {}
+
+static ALLOCATOR: Locked<64, 8> = Locked::empty();
+
+ALLOCATOR.with(|allocator| {
+    let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    assert_send(&test_box);
+});
+```
+"#
+)]
+///
+/// # Lifetime
+///
+/// The lifetime `'a` of a [`Box`] is the lifetime of its value, which may be shorter than the lifetime of the [`IndexAllocator`].
+/// A value borrowing from shorter-lived data can thus be boxed, but the [`Box`] can't outlive the borrowed data:
+///
+/// ```compile_fail
+/// use index_alloc::boxed::Box;
+/// use index_alloc::sync::SingleThreaded;
+///
+/// static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+///
+/// struct Wrapper<'p>(&'p [u8]);
+///
+/// let escaped: Box<Wrapper, 64, 8> = {
+///     let packet = [1, 2, 3, 4];
+///     ALLOCATOR.try_boxed(Wrapper(&packet)).unwrap()
+/// };
+/// ```
+///
+/// Nor can it outlive the [`IndexAllocator`] itself:
+///
+/// ```compile_fail
+/// use index_alloc::boxed::Box;
+/// use index_alloc::IndexAllocator;
+///
+/// let escaped: Box<[u8; 4], 64, 8> = {
+///     let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+///     allocator.try_boxed([1, 2, 3, 4]).unwrap()
+/// };
+/// ```
 pub struct Box<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
 where
     T: ?Sized,
 {
     val: &'a mut T,
     allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    /// Whether a failure to free the value when dropped is only flagged in the allocator, see [`IndexAllocator::try_boxed_no_panic`].
+    pub(crate) no_panic: bool,
+    /// The epoch of the allocator when the value was allocated.
+    #[cfg(feature = "generations")]
+    epoch: usize,
 }
 
+/// A [`Box`] allocated in a `static` [`IndexAllocator`], which can outlive any scope.
+///
+/// With the `embedded-dma` feature, a [`StaticBox`] of words (or of an array or slice of words)
+/// implements the `embedded_dma::ReadBuffer` and `embedded_dma::WriteBuffer` traits,
+/// as DMA transfers require a `'static` buffer.
+pub type StaticBox<T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> =
+    Box<'static, T, MEMORY_SIZE, INDEX_SIZE>;
+
 impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Box<'a, T, MEMORY_SIZE, INDEX_SIZE>
 where
     T: ?Sized,
@@ -47,26 +133,95 @@ where
     /// Try to create a new [`Box`] containing a value of type `T` in an [`IndexAllocator`].
     /// See also [`IndexAllocator::try_boxed`] to create a [`Box`] directly by the allocator.
     ///
+    /// The allocator may outlive the [`Box`], which only needs to live as long as `val`.
+    ///
+    /// `val` is moved into the memory pool, which may copy it through the stack on the way, notably in debug builds:
+    /// a large value may overflow a small stack. Such values are better built in place,
+    /// in a [`Box`] from [`Box::try_new_uninit`].
+    ///
     /// # Errors
     /// The method return an [`IndexError`] if the allocation failed.
-    pub fn try_new<U>(
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_new<'b, U>(
         val: U,
-        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+        allocator: &'b IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
     ) -> Result<Self, IndexError>
     where
+        'b: 'a,
         U: 'a,
         &'a mut T: From<&'a mut U>,
     {
-        let inner_ref = unsafe { allocator.try_alloc_value(val)? };
+        let inner_ptr = unsafe { allocator.try_alloc_value(val)? }.as_ptr();
+        // The conversion is user code, which may panic.
+        let guard = UnwindGuard::initialized(allocator, inner_ptr);
+        let inner_ref: &'a mut T = unsafe { &mut *inner_ptr }.into();
+        guard.disarm();
 
-        Ok(unsafe { Self::from_raw_ref(inner_ref.into(), allocator) })
+        Ok(unsafe { Self::from_raw_ref(inner_ref, allocator) })
+    }
+
+    /// Try to create a new [`Box`] like [`Box::try_new`], allowed to use the bytes kept by [`IndexAllocator::set_reserve`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_new_priority<'b, U>(
+        val: U,
+        allocator: &'b IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError>
+    where
+        'b: 'a,
+        U: 'a,
+        &'a mut T: From<&'a mut U>,
+    {
+        let inner_ptr = unsafe { allocator.try_alloc_value_priority(val)? }.as_ptr();
+        let guard = UnwindGuard::initialized(allocator, inner_ptr);
+        let inner_ref: &'a mut T = unsafe { &mut *inner_ptr }.into();
+        guard.disarm();
+
+        Ok(unsafe { Self::from_raw_ref(inner_ref, allocator) })
+    }
+
+    /// Create a new [`Box`] containing a value of type `T` in an [`IndexAllocator`], see [`Box::try_new`].
+    /// See also [`IndexAllocator::boxed`] to create a [`Box`] directly by the allocator.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if the allocation failed, the panic location being the caller of [`Box::new`].
+    #[track_caller]
+    pub fn new<'b, U>(val: U, allocator: &'b IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self
+    where
+        'b: 'a,
+        U: 'a,
+        &'a mut T: From<&'a mut U>,
+    {
+        match Self::try_new(val, allocator) {
+            Ok(boxed) => boxed,
+            Err(err) => panic!(
+                "memory allocation of {} bytes failed: {err}",
+                mem::size_of::<U>()
+            ),
+        }
     }
 
+    /// Create a [`Box`] from a reference to a value living in the memory pool of `allocator`.
+    ///
+    /// # Safety
+    ///
+    /// `val` must point to a value allocated by `allocator`
+    /// and must not be owned by anything else, as the [`Box`] will free it when dropped.
     pub unsafe fn from_raw_ref(
         val: &'a mut T,
         allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
     ) -> Self {
-        Self { val, allocator }
+        Self {
+            val,
+            allocator,
+            no_panic: false,
+            #[cfg(feature = "generations")]
+            epoch: allocator.epoch(),
+        }
     }
 
     /// Try to free the memory the [`Box`] is managing, dropping its value.
@@ -75,7 +230,112 @@ where
     ///
     /// The method return a [`IndexError`] if the deallocation failed.
     pub fn try_free(self) -> Result<(), IndexError> {
-        unsafe { self.allocator.try_free_value(self.val) }
+        // The value is dropped here, so the `Drop` implementation of the `Box` must not run.
+        let mut this = mem::ManuallyDrop::new(self);
+        let val: *mut T = &mut *this.val;
+        unsafe {
+            ptr::drop_in_place(val);
+            this.allocator.try_free_value(val)
+        }
+    }
+
+    /// Get the number of bytes the region holding the value reserves beyond its size,
+    /// such as the padding needed to align the value in the memory pool.
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let _byte = allocator.try_boxed(1u8).unwrap();
+    /// let word = allocator.try_boxed(2u32).unwrap();
+    /// // Up to 3 bytes may be needed to align the `u32` after the `u8`.
+    /// assert!(word.slack().unwrap() < 4);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`IndexError`] if the index is currently in use.
+    pub fn slack(&self) -> Result<usize, IndexError> {
+        let size = mem::size_of_val::<T>(self.val);
+        if size == 0 {
+            return Ok(0);
+        }
+        self.allocator
+            .try_slack((self.val as *const T).cast::<u8>(), size)
+    }
+
+    /// Consume the [`Box`] without freeing its memory, returning a reference to its value living as long as the allocator borrow.
+    ///
+    /// The region stays reserved, so leaking is suited for values meant to live as long as the [`IndexAllocator`].
+    /// This is an associated function, like the core library `Box::leak`, so it doesn't shadow a method of `T`.
+    ///
+    /// The memory can be reclaimed by rebuilding a [`Box`] from the reference with [`Box::from_raw_ref`] and dropping it,
+    /// otherwise it is only given back by [`IndexAllocator::reset`].
+    /// Discarding the reference leaks the memory for good, so it is an error with `deny(unused_must_use)`:
+    ///
+    /// ```compile_fail
+    /// #![deny(unused_must_use)]
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed(1u32).unwrap();
+    /// Box::leak(test_box);
+    /// ```
+    #[must_use = "the memory can only be reclaimed through the returned reference"]
+    pub fn leak(this: Self) -> &'a mut T {
+        let this = mem::ManuallyDrop::new(this);
+        unsafe { ptr::read(&this.val) }
+    }
+
+    /// Pin the [`Box`], so that its value can no longer be moved, see [`IndexAllocator::try_pin_with`].
+    ///
+    /// The value lives in the memory pool, so moving the [`Box`] never moves it.
+    #[must_use]
+    pub fn into_pin(this: Self) -> Pin<Self> {
+        // Safety: the value is never moved out of the memory pool while the `Box` is alive.
+        unsafe { Pin::new_unchecked(this) }
+    }
+
+    /// Consume the [`Box`] without freeing its memory, returning a pointer to its value, its size in bytes and its allocator,
+    /// for instance to describe the buffer of a DMA transfer.
+    ///
+    /// The memory can be freed by rebuilding the [`Box`] with [`Box::from_raw_parts`],
+    /// otherwise it is only given back by [`IndexAllocator::reset`].
+    ///
+    /// ```
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let buffer = allocator.try_boxed([0u8; 16]).unwrap();
+    /// let (ptr, len, allocator) = Box::into_raw_parts(buffer);
+    /// assert_eq!(len, 16);
+    ///
+    /// let buffer: Box<[u8; 16], 64, 8> = unsafe { Box::from_raw_parts(ptr, len, allocator) }.unwrap();
+    /// drop(buffer);
+    /// assert_eq!(allocator.heap_stats().allocations, 0);
+    /// ```
+    #[must_use = "the memory can only be reclaimed through the returned pointer"]
+    pub fn into_raw_parts(
+        this: Self,
+    ) -> (*mut u8, usize, &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) {
+        let allocator = this.allocator;
+        let val = Self::leak(this);
+        let len = mem::size_of_val::<T>(val);
+        (ptr::from_mut(val).cast::<u8>(), len, allocator)
+    }
+
+    /// Test if the allocator wasn't reset since the [`Box`] was created, see [`generation`](crate::generation).
+    ///
+    /// A [`Box`] which isn't valid must not be accessed anymore.
+    #[cfg(feature = "generations")]
+    #[must_use]
+    pub fn is_valid(this: &Self) -> bool {
+        this.epoch == this.allocator.epoch()
     }
 
     /// Get a reference to the [`IndexAllocator`] used by the box.
@@ -83,6 +343,369 @@ where
     pub fn allocator(&self) -> &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
         self.allocator
     }
+
+    /// Convert the reference to the value of the [`Box`], keeping the same allocation.
+    ///
+    /// # Safety
+    ///
+    /// `convert` must return a reference to the same value, only changing its type or its pointer metadata.
+    unsafe fn map_ref<U: ?Sized>(
+        this: Self,
+        convert: impl FnOnce(&'a mut T) -> &'a mut U,
+    ) -> Box<'a, U, MEMORY_SIZE, INDEX_SIZE> {
+        let this = mem::ManuallyDrop::new(this);
+        Box {
+            val: convert(ptr::read(&this.val)),
+            allocator: this.allocator,
+            no_panic: this.no_panic,
+            #[cfg(feature = "generations")]
+            epoch: this.epoch,
+        }
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Box<'a, T, MEMORY_SIZE, INDEX_SIZE> {
+    /// Rebuild a [`Box`] from the parts returned by [`Box::into_raw_parts`], which frees the memory when dropped.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::EmptyPtr`] if `ptr` is null,
+    /// and an [`IndexError::OutOfMemory`] if it isn't in the memory pool or isn't aligned for `T`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr`, `len` and `allocator` must have been returned by [`Box::into_raw_parts`] for a [`Box`] of the same type,
+    /// and the [`Box`] must be rebuilt only once.
+    pub unsafe fn from_raw_parts(
+        ptr: *mut u8,
+        len: usize,
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError> {
+        debug_assert_eq!(len, mem::size_of::<T>());
+        if ptr.is_null() {
+            return Err(IndexError::EmptyPtr);
+        }
+        // Values taking no space were never reserved, their pointer is dangling.
+        let in_pool = mem::size_of::<T>() == 0 || allocator.offset_of(ptr).is_ok();
+        if !in_pool || ptr.align_offset(mem::align_of::<T>()) != 0 {
+            return Err(IndexError::OutOfMemory);
+        }
+
+        Ok(Self::from_raw_ref(&mut *ptr.cast::<T>(), allocator))
+    }
+
+    /// Try to create a new [`Box`] with room for a value of type `T` in an [`IndexAllocator`], left uninitialized.
+    ///
+    /// Unlike with [`Box::try_new`], the value never goes through the stack when it is written in place,
+    /// which suits the values too large for a small stack.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<2048, 8> = IndexAllocator::empty();
+    ///
+    /// let mut table = Box::<[u32; 256], 2048, 8>::try_new_uninit(&allocator).unwrap();
+    /// let entries = table.as_mut_ptr().cast::<u32>();
+    /// for i in 0..256 {
+    ///     unsafe { entries.add(i).write(i as u32 * 2) };
+    /// }
+    /// let table = unsafe { Box::assume_init(table) };
+    /// assert_eq!(table[255], 510);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_new_uninit(
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Box<'a, MaybeUninit<T>, MEMORY_SIZE, INDEX_SIZE>, IndexError> {
+        let inner_ptr = allocator.try_alloc_array::<MaybeUninit<T>>(1)?;
+        Ok(unsafe {
+            Box::from_raw_ref(&mut *inner_ptr.cast::<MaybeUninit<T>>().as_ptr(), allocator)
+        })
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    Box<'a, MaybeUninit<T>, MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Write `val` in the [`Box`] and convert it to a [`Box`] of the initialized value, keeping the same allocation.
+    ///
+    /// `val` is passed by value, so it may go through the stack like with [`Box::try_new`]:
+    /// a large value is better written field by field, through [`MaybeUninit::as_mut_ptr`], before [`Box::assume_init`].
+    pub fn write(this: Self, val: T) -> Box<'a, T, MEMORY_SIZE, INDEX_SIZE> {
+        unsafe {
+            Box::map_ref(this, |uninit| {
+                uninit.write(val);
+                uninit.assume_init_mut()
+            })
+        }
+    }
+
+    /// Convert the [`Box`] to a [`Box`] of the initialized value, keeping the same allocation.
+    ///
+    /// # Safety
+    ///
+    /// The value must have been initialized, see [`MaybeUninit::assume_init`].
+    pub unsafe fn assume_init(this: Self) -> Box<'a, T, MEMORY_SIZE, INDEX_SIZE> {
+        Box::map_ref(this, |uninit| uninit.assume_init_mut())
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    Box<'a, [T], MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Try to create a new [`Box`] holding a copy of `slice` in an [`IndexAllocator`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = Box::try_from_slice(&[1u8, 2, 3, 4], &allocator).unwrap();
+    /// assert_eq!(*test_box, [1, 2, 3, 4]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_from_slice(
+        slice: &[T],
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError>
+    where
+        T: Copy,
+    {
+        let inner_ptr = allocator.try_alloc_array::<T>(slice.len())?;
+        unsafe {
+            ptr::copy_nonoverlapping(slice.as_ptr(), inner_ptr.cast::<T>().as_ptr(), slice.len());
+            Ok(Self::from_raw_ref(&mut *inner_ptr.as_ptr(), allocator))
+        }
+    }
+
+    /// Convert the [`Box`] to a [`Box`] of an array of `N` values, or return it unchanged if its length isn't `N`.
+    /// The allocation is kept as is.
+    ///
+    /// ```
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let slice = Box::try_from_slice(&[1u8, 2, 3, 4], &allocator).unwrap();
+    /// let array: Box<[u8; 4], 64, 8> = slice.into_array().unwrap();
+    /// assert_eq!(*array, [1, 2, 3, 4]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return the [`Box`] unchanged if its length isn't `N`.
+    pub fn into_array<const N: usize>(
+        self,
+    ) -> Result<Box<'a, [T; N], MEMORY_SIZE, INDEX_SIZE>, Self> {
+        if self.val.len() != N {
+            return Err(self);
+        }
+
+        Ok(unsafe { Box::map_ref(self, |val| &mut *val.as_mut_ptr().cast::<[T; N]>()) })
+    }
+
+    /// Try to shrink the slice to its first `len` values, giving the memory of the others back to the [`IndexAllocator`].
+    /// Shrinking to 0 frees the allocation entirely, and `len` greater than the slice length does nothing.
+    ///
+    /// The values are [`Copy`], so the truncated ones don't need to be dropped.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the memory couldn't be given back,
+    /// in which case the [`Box`] is left unchanged.
+    pub fn try_shrink_to(&mut self, len: usize) -> Result<(), IndexError>
+    where
+        T: Copy,
+    {
+        if len >= self.val.len() {
+            return Ok(());
+        }
+
+        let inner_ptr = self.val.as_mut_ptr();
+        if mem::size_of::<T>() != 0 {
+            if len == 0 {
+                unsafe {
+                    self.allocator
+                        .try_free_array(NonNull::new_unchecked(inner_ptr), self.val.len())?;
+                }
+                self.val = &mut [];
+                return Ok(());
+            }
+            unsafe {
+                self.allocator
+                    .try_shrink(inner_ptr.cast::<u8>(), len * mem::size_of::<T>())?;
+            }
+        }
+        self.val = unsafe { slice::from_raw_parts_mut(inner_ptr, len) };
+
+        Ok(())
+    }
+
+    /// Split the slice at `mid` into two [`Box`] owning the values before and after it, without copying them.
+    /// Each one frees its part of the allocation on its own.
+    ///
+    /// Splitting at 0 or at the length of the slice gives an empty [`Box`] along the original one,
+    /// which takes no slot of the index. Otherwise, the region holding the slice is split,
+    /// which takes a slot of the index, see [`MemoryIndex::split_used_region`](crate::index::MemoryIndex::split_used_region).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// # if cfg!(feature = "redzone") { return; }
+    /// let frame = Box::try_from_slice(&[2u8, 0xAA, 0xBB, 0xCC], &allocator).unwrap();
+    /// let (header, payload) = frame.split_at_boxed(1).map_err(|(_, err)| err).unwrap();
+    /// assert_eq!(*header, [2]);
+    /// drop(header);
+    /// assert_eq!(*payload, [0xAA, 0xBB, 0xCC]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return the [`Box`] unchanged along an [`IndexError`] if the region couldn't be split:
+    /// an [`IndexError::RegionTooThin`] if `mid` is greater than the length of the slice, in buddy mode,
+    /// or with the `redzone` feature which would leave no gap between the two parts,
+    /// and an [`IndexError::NoIndexAvailable`] if the index is full.
+    pub fn split_at_boxed(self, mid: usize) -> Result<(Self, Self), (Self, IndexError)> {
+        let len = self.val.len();
+        if mid > len {
+            return Err((self, IndexError::RegionTooThin));
+        }
+
+        let inner_ptr = self.val.as_mut_ptr();
+        if mid != 0 && mid != len && mem::size_of::<T>() != 0 {
+            if let Err(err) = unsafe {
+                self.allocator
+                    .try_split(inner_ptr.cast::<u8>(), mid * mem::size_of::<T>())
+            } {
+                return Err((self, err));
+            }
+        }
+
+        let this = mem::ManuallyDrop::new(self);
+        let part = |val: &'a mut [T]| Self {
+            val,
+            allocator: this.allocator,
+            no_panic: this.no_panic,
+            #[cfg(feature = "generations")]
+            epoch: this.epoch,
+        };
+        unsafe {
+            Ok((
+                part(slice::from_raw_parts_mut(inner_ptr, mid)),
+                part(slice::from_raw_parts_mut(inner_ptr.add(mid), len - mid)),
+            ))
+        }
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Box<'a, [u8], MEMORY_SIZE, INDEX_SIZE> {
+    /// Try to create a new [`Box`] holding `len` zeroed bytes in an [`IndexAllocator`],
+    /// such as a receive buffer whose length is only known at runtime.
+    /// See also [`IndexAllocator::try_boxed_slice_zeroed`].
+    ///
+    /// The bytes are zeroed once reserved, as [`GlobalAlloc::alloc_zeroed`](core::alloc::GlobalAlloc::alloc_zeroed) does,
+    /// so a region reused after a free never leaks its previous content.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_new_slice_zeroed(
+        len: usize,
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError> {
+        let inner_ptr = allocator.try_alloc_array_zeroed::<u8>(len)?;
+        Ok(unsafe { Self::from_raw_ref(&mut *inner_ptr.as_ptr(), allocator) })
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Box<'a, str, MEMORY_SIZE, INDEX_SIZE> {
+    /// Try to create a new [`Box`] holding a copy of `string` in an [`IndexAllocator`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = Box::try_from_str("Hello", &allocator).unwrap();
+    /// assert_eq!(&*test_box, "Hello");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_from_str(
+        string: &str,
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError> {
+        let bytes = Box::try_from_slice(string.as_bytes(), allocator)?;
+        // The bytes are copied from a `str`, so they are valid UTF-8.
+        Ok(unsafe { Box::map_ref(bytes, |bytes| core::str::from_utf8_unchecked_mut(bytes)) })
+    }
+}
+
+/// Convert a [`Box`] of an array to a [`Box`] of a slice, keeping the same allocation.
+impl<'a, T, const N: usize, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    From<Box<'a, [T; N], MEMORY_SIZE, INDEX_SIZE>> for Box<'a, [T], MEMORY_SIZE, INDEX_SIZE>
+{
+    fn from(array: Box<'a, [T; N], MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        unsafe { Box::map_ref(array, |array| array as &mut [T]) }
+    }
+}
+
+/// Copy a slice in an [`IndexAllocator`], see [`Box::try_from_slice`].
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    TryFrom<(&[T], &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>)>
+    for Box<'a, [T], MEMORY_SIZE, INDEX_SIZE>
+where
+    T: Copy,
+{
+    type Error = IndexError;
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    fn try_from(
+        (slice, allocator): (&[T], &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>),
+    ) -> Result<Self, Self::Error> {
+        Self::try_from_slice(slice, allocator)
+    }
+}
+
+/// Copy a string in an [`IndexAllocator`], see [`Box::try_from_str`].
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    TryFrom<(&str, &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>)>
+    for Box<'a, str, MEMORY_SIZE, INDEX_SIZE>
+{
+    type Error = IndexError;
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    fn try_from(
+        (string, allocator): (&str, &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>),
+    ) -> Result<Self, Self::Error> {
+        Self::try_from_str(string, allocator)
+    }
 }
 
 impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Drop
@@ -91,8 +714,19 @@ where
     T: ?Sized,
 {
     fn drop(&mut self) {
-        unsafe {
-            self.allocator.try_free_value(self.val).unwrap();
+        #[cfg(feature = "generations")]
+        if !self.allocator.check_drop_epoch(self.epoch, "a Box") {
+            return;
+        }
+
+        // The value is dropped in place, so a trait object runs the destructor of its concrete type through its vtable.
+        let val: *mut T = &mut *self.val;
+        unsafe { ptr::drop_in_place(val) };
+        if self.no_panic {
+            let result = unsafe { self.allocator.try_free_value(val) };
+            self.allocator.flag_drop_error("a Box", result);
+        } else {
+            unsafe { self.allocator.drop_free("a Box", val) };
         }
     }
 }
@@ -128,13 +762,38 @@ where
     }
 }
 
+/// Format the address of the value held by the [`Box`], to tell allocations apart in logs.
+///
+/// ```
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+///
+/// let test_box = allocator.try_boxed(42u32).unwrap();
+/// assert_eq!(format!("{test_box:p}"), format!("{:p}", &*test_box));
+/// ```
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> core::fmt::Pointer
+    for Box<'a, T, MEMORY_SIZE, INDEX_SIZE>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Pointer::fmt(&ptr::addr_of!(*self.val), f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sync::SingleThreaded;
 
     #[test]
     // Ignore MIRI because the allocator inner memory is directly read, wich MIRI don't like.
     #[cfg_attr(miri, ignore)]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
     fn test_box_allocation() {
         let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
 
@@ -146,4 +805,497 @@ mod tests {
         assert_eq!(unsafe { (*allocator.memory.get())[2] }, 3);
         assert_eq!(unsafe { (*allocator.memory.get())[3] }, 4);
     }
+
+    struct Wrapper<'p>(&'p [u8]);
+
+    fn box_wrapper<'p>(
+        allocator: &'static IndexAllocator<64, 8>,
+        packet: &'p [u8],
+    ) -> Box<'p, Wrapper<'p>, 64, 8> {
+        allocator.try_boxed(Wrapper(packet)).unwrap()
+    }
+
+    #[test]
+    fn test_box_shorter_lived_value() {
+        static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+
+        let packet = [1u8, 2, 3, 4];
+        let from_allocator = box_wrapper(&ALLOCATOR, &packet[..2]);
+        let from_new: Box<Wrapper, 64, 8> =
+            Box::try_new(Wrapper(&packet[2..]), &ALLOCATOR).unwrap();
+
+        assert_eq!(from_allocator.0, &[1, 2]);
+        assert_eq!(from_new.0, &[3, 4]);
+        assert!(core::ptr::eq(from_new.allocator(), &*ALLOCATOR));
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
+    fn test_box_slack() {
+        #[repr(align(16))]
+        struct Aligned(u8);
+
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let byte = allocator.try_boxed(1u8).unwrap();
+        assert_eq!(byte.slack(), Ok(0));
+
+        let aligned = allocator.try_boxed(Aligned(2)).unwrap();
+        let misalignment = allocator.memory.get() as usize % 16;
+        let padding = (16 - (misalignment + 1) % 16) % 16;
+        assert_eq!(aligned.slack(), Ok(padding));
+        assert_eq!(aligned.0, 2);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
+    fn test_box_shrink_to() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let mut test_box = Box::try_from_slice(&[1u16, 2, 3, 4, 5, 6, 7, 8], &allocator).unwrap();
+        assert_eq!(allocator.heap_stats().free_bytes, 48);
+
+        test_box.try_shrink_to(3).unwrap();
+        assert_eq!(*test_box, [1, 2, 3]);
+        assert_eq!(allocator.heap_stats().free_bytes, 58);
+
+        test_box.try_shrink_to(0).unwrap();
+        assert!(test_box.is_empty());
+        assert_eq!(allocator.heap_stats().free_bytes, 64);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+
+        drop(test_box);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_box_slice_zeroed() {
+        let allocator: IndexAllocator<512, 8> = IndexAllocator::empty();
+
+        let dirty = allocator.try_boxed([0xAAu8; 256]).unwrap();
+        let dirty_ptr = dirty.as_ptr();
+        drop(dirty);
+
+        let zeroed = allocator.try_boxed_slice_zeroed(256).unwrap();
+        assert_eq!(zeroed.as_ptr(), dirty_ptr);
+        assert_eq!(zeroed.len(), 256);
+        assert!(zeroed.iter().all(|byte| *byte == 0));
+
+        let empty = Box::try_new_slice_zeroed(0, &allocator).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(allocator.heap_stats().allocations, 1);
+    }
+
+    #[test]
+    fn test_box_zero_sized() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let unit = allocator.try_boxed(()).unwrap();
+        let empty = Box::<[u8], 64, 8>::try_from_slice(&[], &allocator).unwrap();
+        assert_eq!(unit.slack(), Ok(0));
+        assert!(empty.is_empty());
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    #[cfg(all(debug_assertions, not(feature = "lenient-drop")))]
+    #[should_panic(expected = "isn't in the memory pool of its allocator")]
+    fn test_box_wrong_allocator_drop() {
+        let first: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let second: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let leaked = first.try_leak([1u8, 2, 3, 4]).unwrap();
+        drop(unsafe { Box::from_raw_ref(leaked, &second) });
+    }
+
+    struct SelfRef {
+        data: [u8; 4],
+        first: *const u8,
+        _pinned: core::marker::PhantomPinned,
+    }
+
+    #[test]
+    fn test_box_pin_with() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let pinned = unsafe {
+            allocator.try_pin_with::<SelfRef, _>(|slot| {
+                let this = slot.get_unchecked_mut().as_mut_ptr();
+                ptr::addr_of_mut!((*this).data).write([1, 2, 3, 4]);
+                ptr::addr_of_mut!((*this).first).write(ptr::addr_of!((*this).data).cast());
+                ptr::addr_of_mut!((*this)._pinned).write(core::marker::PhantomPinned);
+            })
+        }
+        .unwrap();
+        assert_eq!(pinned.first, pinned.data.as_ptr());
+
+        // Moving the pinned box doesn't move the value it points to.
+        let moved = std::vec![pinned];
+        assert_eq!(moved[0].first, moved[0].data.as_ptr());
+        assert_eq!(unsafe { *moved[0].first.add(3) }, 4);
+
+        drop(moved);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_box_pin_with_panic() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+            allocator.try_pin_with::<SelfRef, _>(|slot| {
+                let this = slot.get_unchecked_mut().as_mut_ptr();
+                ptr::addr_of_mut!((*this).data).write([1, 2, 3, 4]);
+                panic!("initialization failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.largest_free_block(), Ok(64));
+    }
+
+    /// A value whose conversion to [`Converted`] panics, holding a reference count to check it is dropped.
+    struct Unconvertible {
+        id: u32,
+        _owner: std::rc::Rc<()>,
+    }
+    struct Converted;
+
+    impl<'a> From<&'a mut Unconvertible> for &'a mut Converted {
+        fn from(value: &'a mut Unconvertible) -> Self {
+            panic!("can't convert {}", value.id)
+        }
+    }
+
+    #[test]
+    fn test_box_conversion_panic() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let owned = std::rc::Rc::new(());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            Box::<Converted, 64, 8>::try_new(
+                Unconvertible {
+                    id: 42,
+                    _owner: owned.clone(),
+                },
+                &allocator,
+            )
+            .map(|_| ())
+        }));
+        assert!(result.is_err());
+        // The value was moved in before the conversion, so it is dropped rather than leaked.
+        assert_eq!(std::rc::Rc::strong_count(&owned), 1);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.largest_free_block(), Ok(64));
+    }
+
+    #[test]
+    fn test_box_raw_parts() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let buffer = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+        let (ptr, len, parts_allocator) = Box::into_raw_parts(buffer);
+        assert_eq!(len, 4);
+        assert!(core::ptr::eq(parts_allocator, &allocator));
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        assert_eq!(unsafe { *ptr.add(2) }, 3);
+
+        let buffer: Box<[u8; 4], 64, 8> =
+            unsafe { Box::from_raw_parts(ptr, len, &allocator) }.unwrap();
+        assert_eq!(*buffer, [1, 2, 3, 4]);
+        buffer.try_free().unwrap();
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(
+            unsafe { allocator.try_free(ptr) },
+            Err(IndexError::DoubleFree)
+        );
+        assert!(!allocator.has_failed_drop());
+    }
+
+    #[test]
+    fn test_box_no_panic_drop_error() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let test_box = allocator.try_boxed_no_panic([1u8, 2, 3, 4]).unwrap();
+        {
+            // Hold the index so that freeing the value fails.
+            let _index = allocator.index.borrow_mut();
+            drop(test_box);
+        }
+
+        assert!(allocator.has_failed_drop());
+        assert_eq!(allocator.heap_stats().allocations, 1);
+
+        allocator.clear_failed_drop();
+        assert!(!allocator.has_failed_drop());
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the regions can't be split with gaps around allocations"
+    )]
+    fn test_box_split_at() {
+        let values: [u32; 64] = core::array::from_fn(|i| i as u32);
+
+        for drop_head_first in [true, false] {
+            let allocator: IndexAllocator<512, 8> = IndexAllocator::empty();
+            let buffer = Box::try_from_slice(&values, &allocator).unwrap();
+
+            let (head, tail) = buffer.split_at_boxed(24).map_err(|(_, err)| err).unwrap();
+            assert_eq!(*head, values[..24]);
+            assert_eq!(*tail, values[24..]);
+            assert_eq!(allocator.heap_stats().allocations, 2);
+            assert_eq!(allocator.index.borrow().len(), 3);
+
+            if drop_head_first {
+                drop(head);
+                assert_eq!(*tail, values[24..]);
+                drop(tail);
+            } else {
+                drop(tail);
+                assert_eq!(*head, values[..24]);
+                drop(head);
+            }
+            assert_eq!(allocator.heap_stats().allocations, 0);
+            assert_eq!(allocator.index.borrow().len(), 1);
+            assert_eq!(allocator.largest_free_block(), Ok(512));
+        }
+    }
+
+    #[test]
+    fn test_box_split_at_ends() {
+        let allocator: IndexAllocator<128, 4> = IndexAllocator::empty();
+        let buffer = Box::try_from_slice(&[1u8, 2, 3, 4], &allocator).unwrap();
+
+        let (empty, buffer) = buffer.split_at_boxed(0).map_err(|(_, err)| err).unwrap();
+        assert!(empty.is_empty());
+        let (buffer, empty) = buffer.split_at_boxed(4).map_err(|(_, err)| err).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(*buffer, [1, 2, 3, 4]);
+        assert_eq!(allocator.index.borrow().len(), 2);
+
+        let (buffer, err) = buffer.split_at_boxed(5).unwrap_err();
+        assert_eq!(err, IndexError::RegionTooThin);
+        drop((buffer, empty));
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the regions can't be split with gaps around allocations"
+    )]
+    fn test_box_split_at_index_full() {
+        let allocator: IndexAllocator<128, 2> = IndexAllocator::empty();
+        let buffer = Box::try_from_slice(&[1u8, 2, 3, 4], &allocator).unwrap();
+
+        let (buffer, err) = buffer.split_at_boxed(2).unwrap_err();
+        assert_eq!(err, IndexError::NoIndexAvailable);
+        assert_eq!(*buffer, [1, 2, 3, 4]);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        assert_eq!(allocator.index.borrow().len(), 2);
+
+        drop(buffer);
+        assert_eq!(allocator.largest_free_block(), Ok(128));
+    }
+
+    trait Greeter {
+        fn greet(&self) -> &str;
+    }
+
+    struct Named<'n>(&'n str);
+
+    impl Greeter for Named<'_> {
+        fn greet(&self) -> &str {
+            self.0
+        }
+    }
+
+    impl<'g, T: Greeter> From<&'g mut T> for &'g mut dyn Greeter {
+        fn from(value: &'g mut T) -> Self {
+            value as _
+        }
+    }
+
+    struct Noisy<'d>(&'d core::cell::Cell<usize>);
+
+    impl Greeter for Noisy<'_> {
+        fn greet(&self) -> &str {
+            "noisy"
+        }
+    }
+
+    impl Drop for Noisy<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_box_trait_object_drop() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let drops = core::cell::Cell::new(0);
+
+        let greeter = allocator
+            .try_boxed::<dyn Greeter + '_, _>(Noisy(&drops))
+            .unwrap();
+        assert_eq!(greeter.greet(), "noisy");
+        drop(greeter);
+        assert_eq!(drops.get(), 1);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+
+        let greeter = allocator
+            .try_boxed::<dyn Greeter + '_, _>(Noisy(&drops))
+            .unwrap();
+        greeter.try_free().unwrap();
+        assert_eq!(drops.get(), 2);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    /// A guard flipping a flag when dropped, owning another [`Box`].
+    struct Guard<'a> {
+        dropped: &'a core::cell::Cell<bool>,
+        _inner: Box<'a, [u8; 8], 128, 8>,
+    }
+
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.dropped.set(true);
+        }
+    }
+
+    #[test]
+    fn test_box_drops_inner_value_once() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        for try_free in [false, true] {
+            let dropped = core::cell::Cell::new(false);
+            let guard = allocator
+                .try_boxed(Guard {
+                    dropped: &dropped,
+                    _inner: allocator.try_boxed([0u8; 8]).unwrap(),
+                })
+                .unwrap();
+            assert_eq!(allocator.heap_stats().allocations, 2);
+
+            if try_free {
+                guard.try_free().unwrap();
+            } else {
+                drop(guard);
+            }
+            // The inner box is freed by the destructor of the guard.
+            assert!(dropped.get());
+            assert_eq!(allocator.heap_stats().allocations, 0);
+        }
+    }
+
+    #[test]
+    fn test_box_borrowing_trait_object() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let name = std::string::String::from("local");
+
+        let greeter = allocator
+            .try_boxed::<dyn Greeter + '_, _>(Named(&name))
+            .unwrap();
+        assert_eq!(greeter.greet(), "local");
+
+        let counter = core::cell::Cell::new(0);
+        let increment = allocator
+            .try_boxed_as(|| counter.set(counter.get() + 1), |f| f as &mut dyn Fn())
+            .unwrap();
+        increment();
+        increment();
+        assert_eq!(counter.get(), 2);
+    }
+
+    #[test]
+    fn test_box_as_other_reference() {
+        static mut OTHER: u32 = 0;
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let result =
+            allocator.try_boxed_as(1u32, |_| unsafe { &mut *core::ptr::addr_of_mut!(OTHER) });
+        assert_eq!(result.map(|_| ()), Err(IndexError::OutOfMemory));
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_box_array_slice_conversions() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let regions = || allocator.index.borrow().regions().count();
+
+        let array = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+        let before = regions();
+        let slice: Box<[u8], 64, 8> = array.into();
+        assert_eq!(*slice, [1, 2, 3, 4]);
+
+        let slice = slice.into_array::<3>().unwrap_err();
+        let array = slice.into_array::<4>().unwrap();
+        assert_eq!(*array, [1, 2, 3, 4]);
+        assert_eq!(regions(), before);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+
+        drop(array);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_box_try_from() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let slice = Box::try_from((&[1u16, 2, 3][..], &allocator)).unwrap();
+        assert_eq!(*slice, [1, 2, 3]);
+        let string = Box::try_from(("Hello", &allocator)).unwrap();
+        assert_eq!(&*string, "Hello");
+        assert_eq!(allocator.heap_stats().allocations, 2);
+
+        let too_long = Box::<[u8], 64, 8>::try_from((&[0u8; 128][..], &allocator));
+        assert_eq!(too_long.map(|_| ()), Err(IndexError::NoFittingRegion));
+    }
+
+    #[test]
+    fn test_box_uninit_large_value_small_stack() {
+        /// A value of 4 KiB, built in place.
+        #[repr(C)]
+        struct Large {
+            header: u32,
+            table: [u32; 1023],
+        }
+
+        static ALLOCATOR: SingleThreaded<8192, 8> = unsafe { SingleThreaded::empty() };
+
+        // The smallest stack the platform allows, which no more than a few copies of the value would overflow.
+        let thread = std::thread::Builder::new().stack_size(16 * 1024);
+        let handle = thread
+            .spawn(|| {
+                let mut large = Box::<Large, 8192, 8>::try_new_uninit(&ALLOCATOR).unwrap();
+                let large_ptr = large.as_mut_ptr();
+                unsafe {
+                    ptr::addr_of_mut!((*large_ptr).header).write(0xCAFE);
+                    let table = ptr::addr_of_mut!((*large_ptr).table).cast::<u32>();
+                    for i in 0..1023 {
+                        table.add(i).write(i as u32);
+                    }
+                }
+                let large = unsafe { Box::assume_init(large) };
+                assert_eq!(mem::size_of_val(&*large), 4096);
+                assert_eq!(large.header, 0xCAFE);
+                assert_eq!(large.table[1022], 1022);
+
+                drop(large);
+                let written =
+                    Box::write(Box::<u64, 8192, 8>::try_new_uninit(&ALLOCATOR).unwrap(), 7);
+                assert_eq!(*written, 7);
+            })
+            .unwrap();
+        handle.join().unwrap();
+        assert_eq!(ALLOCATOR.heap_stats().allocations, 0);
+    }
 }
@@ -0,0 +1,147 @@
+//! This module contains the buddy mode, trading memory for fragmentation by rounding allocations up to power of two blocks.
+//!
+//! It is only available with the `buddy` feature. An allocator created with [`IndexAllocator::empty_buddy`]
+//! cuts its memory pool in blocks whose size is a power of two, aligned on their size from the start of the memory pool.
+//! An allocation takes the smallest block of its size class, the power of two its size is rounded up to,
+//! splitting larger blocks in halves as needed. A freed block only merges with its buddy,
+//! the other half of the block it was split from, so the blocks keep their invariants.
+//!
+//! The rounding wastes up to half of each block, but a freed block always merges back,
+//! so the memory pool doesn't fragment in small free regions between allocations.
+//! Shrinking an allocation keeps its block, and [`IndexAllocator::compact`] does nothing.
+//! Buffers attached with [`IndexAllocator::add_region`] aren't cut in blocks, so they only serve allocations
+//! if their size is a power of two and their offset from the memory pool a multiple of it.
+
+use crate::index::MemoryIndex;
+use crate::IndexAllocator;
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Create an empty [`IndexAllocator`] in buddy mode, see [`buddy`](crate::buddy).
+    ///
+    /// A memory pool whose size is a power of two is a single block. Otherwise, it is cut in blocks of decreasing sizes,
+    /// each taking a slot of the index.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<256, 16> = IndexAllocator::empty_buddy();
+    ///
+    /// // 17 bytes are rounded up to a 32 bytes block, or 64 bytes with the gaps of the `redzone` feature.
+    /// let test_box = allocator.try_boxed([0u8; 17]).unwrap();
+    /// assert!(allocator.heap_stats().used_bytes.is_power_of_two());
+    /// ```
+    #[must_use]
+    pub const fn empty_buddy() -> Self {
+        let mut allocator = Self::new([0; MEMORY_SIZE], MemoryIndex::buddy(MEMORY_SIZE));
+        allocator.buddy = true;
+        allocator
+    }
+
+    /// Test if the allocator is in buddy mode, see [`IndexAllocator::empty_buddy`].
+    #[must_use]
+    pub fn is_buddy(&self) -> bool {
+        self.buddy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// List the regions of the index as `(from, size, used)`.
+    fn blocks<const MEMORY_SIZE: usize, const INDEX_SIZE: usize>(
+        allocator: &IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Vec<(usize, usize, bool)> {
+        allocator
+            .index
+            .borrow()
+            .regions()
+            .map(|region| (region.from, region.size, region.used))
+            .collect()
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the size classes"
+    )]
+    fn test_buddy_size_class() {
+        let allocator: IndexAllocator<256, 16> = IndexAllocator::empty_buddy();
+        assert!(allocator.is_buddy());
+
+        let test_box = allocator.try_boxed([0u8; 17]).unwrap();
+        assert_eq!(allocator.heap_stats().used_bytes, 32);
+        assert_eq!(
+            blocks(&allocator),
+            [
+                (0, 32, true),
+                (32, 32, false),
+                (64, 64, false),
+                (128, 128, false)
+            ]
+        );
+
+        drop(test_box);
+        assert_eq!(blocks(&allocator), [(0, 256, false)]);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the size classes"
+    )]
+    fn test_buddy_merges() {
+        let allocator: IndexAllocator<256, 16> = IndexAllocator::empty_buddy();
+
+        let [first, second, third, fourth] =
+            core::array::from_fn(|_| allocator.try_boxed([0u8; 64]).unwrap());
+
+        // The second and third blocks are adjacent, but they aren't buddies.
+        drop(second);
+        drop(third);
+        assert_eq!(
+            blocks(&allocator),
+            [
+                (0, 64, true),
+                (64, 64, false),
+                (128, 64, false),
+                (192, 64, true)
+            ]
+        );
+        assert!(allocator.try_boxed([0u8; 128]).is_err());
+
+        // Freeing a block merges it with its buddy, and the merged block with its own buddy.
+        drop(first);
+        assert_eq!(
+            blocks(&allocator),
+            [(0, 128, false), (128, 64, false), (192, 64, true)]
+        );
+        drop(fourth);
+        assert_eq!(blocks(&allocator), [(0, 256, false)]);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the size classes"
+    )]
+    fn test_buddy_smallest_block() {
+        let allocator: IndexAllocator<96, 8> = IndexAllocator::empty_buddy();
+        assert_eq!(blocks(&allocator), [(0, 64, false), (64, 32, false)]);
+
+        // The 32 bytes block is taken as is rather than splitting the larger one.
+        let small = allocator.try_boxed([0u8; 20]).unwrap();
+        assert_eq!(blocks(&allocator), [(0, 64, false), (64, 32, true)]);
+        let large = allocator.try_boxed([0u8; 40]).unwrap();
+        assert!(allocator.try_boxed(0u8).is_err());
+
+        core::mem::forget(small);
+        core::mem::forget(large);
+        unsafe { allocator.reset().unwrap() };
+        assert_eq!(blocks(&allocator), [(0, 64, false), (64, 32, false)]);
+    }
+}
@@ -0,0 +1,228 @@
+//! This module contains the bump mode, serving bursts of allocations which are never freed individually from a single region.
+
+use core::alloc::Layout;
+
+use crate::{IndexAllocator, IndexError};
+
+/// The region reserved by [`IndexAllocator::enter_bump_mode`], with the addresses relative to the memory pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BumpArena {
+    from: usize,
+    end: usize,
+    /// The first byte which wasn't handed out yet.
+    next: usize,
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Reserve the largest free region as an arena, serving the following allocations by bumping a pointer inside it,
+    /// until [`IndexAllocator::exit_bump_mode`] is called.
+    ///
+    /// The allocations served in bump mode don't take a slot of the index each,
+    /// which suits bursts of allocations never freed individually, such as the startup of a program.
+    /// Once the arena is full, allocations are served as usual.
+    ///
+    /// The arena is a single used region, counted as one allocation, and it is never freed:
+    /// freeing or shrinking an allocation inside it does nothing, even after leaving bump mode,
+    /// and its memory is only given back by [`IndexAllocator::reset`].
+    /// With the `redzone` feature, the allocations inside it have no gaps around them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<1024, 4> = IndexAllocator::empty();
+    ///
+    /// allocator.enter_bump_mode().unwrap();
+    /// let values: [&mut u32; 16] = core::array::from_fn(|i| allocator.try_leak(i as u32).unwrap());
+    /// allocator.exit_bump_mode().unwrap();
+    ///
+    /// assert_eq!(*values[15], 15);
+    /// assert_eq!(allocator.heap_stats().allocations, 1);
+    /// assert_eq!(allocator.heap_stats().used_bytes, 64);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::NoFittingRegion`] if there is no free region,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    /// It does nothing if the allocator is already in bump mode.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn enter_bump_mode(&self) -> Result<(), IndexError> {
+        if self.bump.get().is_some() {
+            return Ok(());
+        }
+
+        #[cfg(feature = "age")]
+        let now = self.now();
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let largest = index
+            .regions()
+            .filter(|region| !region.used && region.size > 0)
+            .reduce(|largest, region| {
+                if region.size > largest.size {
+                    region
+                } else {
+                    largest
+                }
+            })
+            .ok_or(IndexError::NoFittingRegion)?;
+        let (from, end) = (largest.from, largest.end());
+
+        let region_index = index.find_region(from)?;
+        let mut region = index.get_region_mut(region_index)?;
+        region.reserve();
+        region.arena = true;
+        #[cfg(feature = "call-site")]
+        {
+            region.location = Some(core::panic::Location::caller());
+        }
+        #[cfg(feature = "age")]
+        {
+            region.allocated_at = now;
+        }
+
+        self.allocations.set(self.allocations.get() + 1);
+        self.touch(region.pool, end);
+        self.bump.set(Some(BumpArena {
+            from,
+            end,
+            next: from,
+        }));
+
+        Ok(())
+    }
+
+    /// Leave bump mode, giving the unused end of the arena back to the allocator,
+    /// or the whole arena if nothing was allocated in it, see [`IndexAllocator::enter_bump_mode`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the unused end couldn't be given back,
+    /// for instance when the index is full and the end needs a new region.
+    /// The allocator then stays in bump mode. It does nothing if the allocator isn't in bump mode.
+    pub fn exit_bump_mode(&self) -> Result<(), IndexError> {
+        let Some(arena) = self.bump.get() else {
+            return Ok(());
+        };
+
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region_index = index.find_region(arena.from)?;
+        let consumed = arena.next - arena.from;
+        if consumed == 0 {
+            self.free_region(&mut index, region_index)?;
+            self.allocations
+                .set(self.allocations.get().saturating_sub(1));
+        } else {
+            self.shrink_region(&mut index, region_index, consumed)?;
+            #[cfg(feature = "stats")]
+            {
+                index.get_region_mut(region_index)?.requested_size = consumed;
+                self.size_histogram.record_alloc(consumed);
+            }
+        }
+        self.bump.set(None);
+        drop(index);
+
+        #[cfg(feature = "async")]
+        self.wake_pending();
+
+        Ok(())
+    }
+
+    /// Test if the allocator is in bump mode, see [`IndexAllocator::enter_bump_mode`].
+    #[must_use]
+    pub fn is_bump_mode(&self) -> bool {
+        self.bump.get().is_some()
+    }
+
+    /// Serve `layout` from the arena in bump mode, returning its address relative to the memory pool,
+    /// or `None` if the allocator isn't in bump mode, the arena is full or the allocation takes more than `budget` bytes.
+    pub(crate) fn try_bump(&self, layout: Layout, budget: usize) -> Option<usize> {
+        let mut arena = self.bump.get()?;
+
+        let start = (self.memory.get() as usize).wrapping_add(arena.next);
+        let mask = layout.align() - 1;
+        let offset = (start.checked_add(mask)? & !mask) - start;
+        let addr = arena.next.checked_add(offset)?;
+        let next = addr.checked_add(layout.size())?;
+        if next > arena.end || next - arena.next > budget {
+            return None;
+        }
+
+        self.used_bytes
+            .set(self.used_bytes.get() + (next - arena.next));
+        arena.next = next;
+        self.bump.set(Some(arena));
+
+        Some(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bump_mode_burst() {
+        let allocator: IndexAllocator<4096, 8> = IndexAllocator::empty();
+
+        allocator.enter_bump_mode().unwrap();
+        assert!(allocator.is_bump_mode());
+        for i in 0..500u32 {
+            let value = allocator.try_leak(i).unwrap();
+            assert_eq!(*value, i);
+        }
+        allocator.exit_bump_mode().unwrap();
+        assert!(!allocator.is_bump_mode());
+
+        let index = allocator.index.borrow();
+        let used: std::vec::Vec<usize> = index
+            .regions()
+            .filter(|region| region.used)
+            .map(|region| region.size)
+            .collect();
+        assert_eq!(used, [2000]);
+        drop(index);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        assert_eq!(allocator.heap_stats().used_bytes, 2000);
+    }
+
+    #[test]
+    fn test_bump_mode_free_ignored() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        allocator.enter_bump_mode().unwrap();
+        let first = allocator.try_boxed([1u8; 16]).unwrap();
+        let second = allocator.try_boxed([2u8; 16]).unwrap();
+        drop(first);
+        allocator.exit_bump_mode().unwrap();
+        drop(second);
+
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        assert_eq!(allocator.heap_stats().used_bytes, 32);
+
+        unsafe { allocator.reset().unwrap() };
+        assert_eq!(allocator.heap_stats().free_bytes, 256);
+    }
+
+    #[test]
+    fn test_bump_mode_unused() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let _before = allocator.try_boxed(0u64).unwrap();
+        let snapshot = allocator.index.borrow().snapshot();
+        allocator.enter_bump_mode().unwrap();
+        assert_eq!(allocator.largest_free_block(), Ok(0));
+        allocator.exit_bump_mode().unwrap();
+
+        assert_eq!(allocator.index.borrow().snapshot(), snapshot);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+    }
+}
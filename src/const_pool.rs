@@ -0,0 +1,201 @@
+//! This module contains the [`ConstPoolBuilder`], placing data known at compile time in the memory pool of an [`IndexAllocator`].
+//!
+//! Lookup tables placed this way live in the memory pool with no initialization at runtime: the memory pool
+//! and its index are computed at compile time, the placed bytes being held by used regions,
+//! so that the [`IndexPtr`](crate::index_ptr::IndexPtr) offsets work on them and they can be freed like any allocation.
+//!
+//! The address of the memory pool is only known at runtime, so the placed bytes have no alignment.
+//! They aren't counted by the size histogram of the `stats` feature.
+
+use crate::index::{MemoryIndex, MemoryRegion};
+#[cfg(feature = "redzone")]
+use crate::redzone::REDZONE_BYTE;
+use crate::{IndexAllocator, REDZONE_SIZE};
+
+/// A builder placing byte blobs in a memory pool at compile time, see [`const_pool`](self).
+///
+/// The blobs are placed one after the other from the start of the memory pool,
+/// the rest of the memory pool being free once the allocator is built.
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::const_pool::ConstPoolBuilder;
+/// use index_alloc::sync::SingleThreaded;
+///
+/// const SQUARES: [u8; 8] = [0, 1, 4, 9, 16, 25, 36, 49];
+///
+/// const POOL: (ConstPoolBuilder<256, 8>, usize) = {
+///     let mut pool = ConstPoolBuilder::new();
+///     let squares = pool.place(&SQUARES);
+///     (pool, squares)
+/// };
+///
+/// static ALLOCATOR: SingleThreaded<256, 8> = unsafe { SingleThreaded::new(POOL.0.build()) };
+///
+/// let squares = ALLOCATOR.ptr_at_offset(POOL.1).unwrap();
+/// assert_eq!(unsafe { core::slice::from_raw_parts(squares.as_ptr(), 8) }, SQUARES);
+/// assert_eq!(ALLOCATOR.heap_stats().allocations, 1);
+///
+/// // The table is a used region like any other, it can be freed once no longer needed.
+/// unsafe { ALLOCATOR.try_free_array(squares, SQUARES.len()).unwrap() };
+/// assert_eq!(ALLOCATOR.heap_stats().allocations, 0);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ConstPoolBuilder<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    memory: [u8; MEMORY_SIZE],
+    regions: [Option<MemoryRegion>; INDEX_SIZE],
+    /// The number of blobs placed.
+    placed: usize,
+    /// The first byte after the last blob.
+    next: usize,
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> ConstPoolBuilder<MEMORY_SIZE, INDEX_SIZE> {
+    /// Create a builder of a zeroed memory pool.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            memory: [0; MEMORY_SIZE],
+            regions: [None; INDEX_SIZE],
+            placed: 0,
+            next: 0,
+        }
+    }
+
+    /// Copy `bytes` after the blobs already placed, in a used region of their own, and return their offset
+    /// from the start of the memory pool.
+    ///
+    /// # Panics
+    ///
+    /// The method panics, which fails to compile in a const context, if `bytes` is empty, if it doesn't fit in
+    /// the rest of the memory pool, or if the index has no slot left for it and the free region after it.
+    #[must_use]
+    pub const fn place(&mut self, bytes: &[u8]) -> usize {
+        assert!(!bytes.is_empty(), "an empty blob can't be placed");
+        assert!(
+            self.placed + 1 < INDEX_SIZE,
+            "the index is too small for the blobs placed"
+        );
+        let from = self.next;
+        let size = bytes.len() + 2 * REDZONE_SIZE;
+        assert!(
+            size <= MEMORY_SIZE - from,
+            "the memory pool is too small for the blobs placed"
+        );
+
+        let data = from + REDZONE_SIZE;
+        let mut i = 0;
+        while i < bytes.len() {
+            self.memory[data + i] = bytes[i];
+            i += 1;
+        }
+        #[allow(unused_mut)]
+        let mut region = MemoryRegion::new(from, size, true);
+        #[cfg(feature = "redzone")]
+        {
+            region.data_offset = REDZONE_SIZE;
+            let mut i = 0;
+            while i < REDZONE_SIZE {
+                self.memory[from + i] = REDZONE_BYTE;
+                self.memory[data + bytes.len() + i] = REDZONE_BYTE;
+                i += 1;
+            }
+        }
+        #[cfg(feature = "stats")]
+        {
+            region.requested_size = bytes.len();
+        }
+
+        self.regions[self.placed] = Some(region);
+        self.placed += 1;
+        self.next = from + size;
+        data
+    }
+
+    /// Build the [`IndexAllocator`] holding the blobs placed.
+    #[must_use]
+    pub const fn build(self) -> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+        let Self {
+            memory,
+            mut regions,
+            placed,
+            next,
+        } = self;
+        if next < MEMORY_SIZE && placed < INDEX_SIZE {
+            regions[placed] = Some(MemoryRegion::new(next, MEMORY_SIZE - next, false));
+        }
+
+        let mut allocator = IndexAllocator::new(memory, MemoryIndex::new(regions));
+        allocator.used_bytes = core::cell::Cell::new(next);
+        allocator.allocations = core::cell::Cell::new(placed);
+        allocator.initial_free_bytes = MEMORY_SIZE - next;
+        allocator.untouched = core::cell::Cell::new(next);
+        allocator
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Default
+    for ConstPoolBuilder<MEMORY_SIZE, INDEX_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::NonNull;
+    use std::slice;
+
+    use super::*;
+
+    const FIRST: [u8; 4] = [1, 2, 3, 4];
+    const SECOND: [u8; 8] = [8, 7, 6, 5, 4, 3, 2, 1];
+
+    const fn build_pool() -> (IndexAllocator<256, 16>, usize, usize) {
+        let mut pool = ConstPoolBuilder::new();
+        let first = pool.place(&FIRST);
+        let second = pool.place(&SECOND);
+        (pool.build(), first, second)
+    }
+
+    #[test]
+    fn test_const_pool() {
+        let (allocator, first, second) = const { build_pool() };
+        assert_eq!(first, REDZONE_SIZE);
+        assert_eq!(second, FIRST.len() + 3 * REDZONE_SIZE);
+
+        let read = |offset: usize, len: usize| unsafe {
+            slice::from_raw_parts(allocator.ptr_at(offset).cast_const(), len)
+        };
+        assert_eq!(read(first, FIRST.len()), FIRST);
+        assert_eq!(read(second, SECOND.len()), SECOND);
+        assert_eq!(allocator.heap_stats().allocations, 2);
+        let placed_bytes = FIRST.len() + SECOND.len() + 4 * REDZONE_SIZE;
+        assert_eq!(allocator.initial_free_bytes(), 256 - placed_bytes);
+        #[cfg(feature = "redzone")]
+        assert_eq!(allocator.check_integrity(), Ok(()));
+
+        let after = allocator.try_boxed([9u8; 16]).unwrap();
+        assert!(allocator.ptr_at(second + SECOND.len()) <= after.as_ptr().cast_mut().cast());
+
+        // Freeing the first blob makes room for an allocation before the second one.
+        unsafe {
+            allocator
+                .try_free_array(NonNull::new(allocator.ptr_at(first)).unwrap(), FIRST.len())
+                .unwrap();
+        }
+        let before = allocator.try_boxed([0u8; 4]).unwrap();
+        assert_eq!(
+            before.as_ptr().cast::<u8>().cast_mut(),
+            allocator.ptr_at(first)
+        );
+        assert_eq!(read(second, SECOND.len()), SECOND);
+
+        drop((before, after));
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        // The initial free bytes don't follow the blobs freed since.
+        assert_eq!(allocator.initial_free_bytes(), 256 - placed_bytes);
+    }
+}
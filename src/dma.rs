@@ -0,0 +1,66 @@
+//! This module makes [`Box`] usable as an `embedded-dma` buffer.
+//!
+//! `embedded_dma` implements [`ReadBuffer`](embedded_dma::ReadBuffer) and [`WriteBuffer`](embedded_dma::WriteBuffer)
+//! for every `'static` [`StableDeref`] pointer to words, arrays or slices of words,
+//! so implementing [`StableDeref`] for [`Box`] makes any [`StaticBox`](crate::boxed::StaticBox) usable for DMA transfers.
+//!
+//! # Example
+//!
+//! ```
+//! use embedded_dma::WriteBuffer;
+//! use index_alloc::boxed::StaticBox;
+//! use index_alloc::sync::SingleThreaded;
+//!
+//! static ALLOCATOR: SingleThreaded<256, 8> = unsafe { SingleThreaded::empty() };
+//!
+//! /// A mock transfer, which would usually hand the buffer to a DMA peripheral and return a transfer handle.
+//! fn start_transfer<B: WriteBuffer<Word = u8>>(mut buffer: B) -> B {
+//!     let (ptr, len) = unsafe { buffer.write_buffer() };
+//!     unsafe { core::ptr::write_bytes(ptr, 0xFF, len) };
+//!     buffer
+//! }
+//!
+//! let buffer: StaticBox<[u8; 64], 256, 8> = ALLOCATOR.try_boxed([0; 64]).unwrap();
+//! let buffer = start_transfer(buffer);
+//! assert_eq!(*buffer, [0xFF; 64]);
+//! ```
+
+use stable_deref_trait::StableDeref;
+
+use crate::boxed::Box;
+
+/// The value of a [`Box`] never moves: the allocator never moves live allocations,
+/// so the address is stable until the [`Box`] is dropped.
+///
+/// Note that this only holds as long as the memory pool itself isn't reinitialized,
+/// which must not happen while a DMA transfer is in flight.
+unsafe impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> StableDeref
+    for Box<'a, T, MEMORY_SIZE, INDEX_SIZE>
+where
+    T: ?Sized,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_dma::{ReadBuffer, WriteBuffer};
+
+    use crate::boxed::StaticBox;
+    use crate::sync::SingleThreaded;
+
+    static ALLOCATOR: SingleThreaded<256, 8> = unsafe { SingleThreaded::empty() };
+
+    #[test]
+    fn test_dma_buffer() {
+        let mut buffer: StaticBox<[u16; 16], 256, 8> = ALLOCATOR.try_boxed([0; 16]).unwrap();
+        let expected_ptr = buffer.as_ptr();
+
+        let (read_ptr, read_len) = unsafe { buffer.read_buffer() };
+        assert_eq!(read_ptr, expected_ptr);
+        assert_eq!(read_len, 16);
+
+        let (write_ptr, write_len) = unsafe { buffer.write_buffer() };
+        assert_eq!(write_ptr.cast_const(), expected_ptr);
+        assert_eq!(write_len, 16);
+    }
+}
@@ -0,0 +1,261 @@
+//! This module contains the [`AllocFuture`], waiting for memory to be freed when the memory pool is momentarily full.
+//!
+//! It is only available with the `async` feature, which keeps a few [`Waker`]s in every [`IndexAllocator`].
+//! A task polling an allocation which doesn't fit registers its [`Waker`],
+//! and every [`Waker`] is woken as soon as memory is given back to the allocator, by a free, a shrink or a reset.
+//! The woken tasks then race for the memory: the first one polled gets it, the others register again.
+
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::task::{Context, Poll, Waker};
+
+use crate::boxed::Box;
+use crate::{IndexAllocator, IndexError, REDZONE_SIZE};
+
+/// The number of tasks which can wait for memory at the same time.
+///
+/// A task polled while every slot is taken is woken right away, so it is polled again instead of waiting.
+pub const WAKER_SLOTS: usize = 4;
+
+/// The [`Waker`]s of the tasks waiting for memory.
+pub(crate) struct WakerSlots {
+    wakers: RefCell<[Option<Waker>; WAKER_SLOTS]>,
+}
+
+impl WakerSlots {
+    pub const fn new() -> Self {
+        Self {
+            wakers: RefCell::new([const { None }; WAKER_SLOTS]),
+        }
+    }
+
+    /// Register `waker` to be woken when memory is freed.
+    fn register(&self, waker: &Waker) {
+        let Ok(mut wakers) = self.wakers.try_borrow_mut() else {
+            waker.wake_by_ref();
+            return;
+        };
+
+        if wakers.iter().flatten().any(|slot| slot.will_wake(waker)) {
+            return;
+        }
+        match wakers.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(waker.clone()),
+            None => {
+                drop(wakers);
+                waker.wake_by_ref();
+            }
+        }
+    }
+
+    /// Wake every registered [`Waker`], emptying the slots.
+    ///
+    /// The slots are released before waking, so an executor allocating in its [`Waker`] can register again.
+    fn wake_all(&self) {
+        let Ok(mut wakers) = self.wakers.try_borrow_mut() else {
+            return;
+        };
+        let woken = mem::replace(&mut *wakers, [const { None }; WAKER_SLOTS]);
+        drop(wakers);
+
+        for waker in woken.into_iter().flatten() {
+            waker.wake();
+        }
+    }
+}
+
+/// A [`Future`] resolving to an allocation once it fits in the memory pool, obtained with [`IndexAllocator::alloc_pending`].
+///
+/// Polling it attempts the allocation. When the memory pool or the index is full,
+/// the task is woken again once memory is freed in the [`IndexAllocator`].
+/// A layout which can never fit in the memory pool resolves to an [`IndexError::NoFittingRegion`] right away.
+#[must_use = "futures do nothing unless polled"]
+pub struct AllocFuture<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    layout: Layout,
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Future
+    for AllocFuture<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+    type Output = Result<NonNull<u8>, IndexError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match unsafe { self.allocator.try_alloc(self.layout) } {
+            Ok(ptr) => Poll::Ready(NonNull::new(ptr).ok_or(IndexError::EmptyPtr)),
+            Err(IndexError::NoFittingRegion | IndexError::NoIndexAvailable)
+                if self.allocator.may_fit(self.layout) =>
+            {
+                self.allocator.wakers.register(cx.waker());
+                Poll::Pending
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Allocate memory for `layout`, waiting for memory to be freed when the memory pool is momentarily full,
+    /// see [`AllocFuture`].
+    ///
+    /// The memory must be freed with [`GlobalAlloc::dealloc`](core::alloc::GlobalAlloc::dealloc),
+    /// [`IndexAllocator::try_boxed_async`] manages it in a [`Box`] instead.
+    pub fn alloc_pending(&self, layout: Layout) -> AllocFuture<'_, MEMORY_SIZE, INDEX_SIZE> {
+        AllocFuture {
+            allocator: self,
+            layout,
+        }
+    }
+
+    /// Allocate the value in the memory pool, waiting for memory to be freed when the memory pool is momentarily full,
+    /// and then return a [`Box`] smart pointer which manage the memory, see [`IndexAllocator::try_boxed`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the value can never fit in the memory pool or the allocation failed otherwise.
+    pub async fn try_boxed_async<'a, T: 'a>(
+        &'a self,
+        val: T,
+    ) -> Result<Box<'a, T, MEMORY_SIZE, INDEX_SIZE>, IndexError> {
+        Box::try_new_async(val, self).await
+    }
+
+    /// Wake the tasks waiting for memory, once memory was given back.
+    pub(crate) fn wake_pending(&self) {
+        self.wakers.wake_all();
+    }
+
+    /// Test if `layout` fits in the memory pool when it's empty, aligned after the gap at the start of the pool.
+    fn may_fit(&self, layout: Layout) -> bool {
+        let start = self.memory.get() as usize + REDZONE_SIZE;
+        let offset = start.wrapping_neg() & (layout.align() - 1);
+        layout.align() <= MEMORY_SIZE
+            && offset
+                .checked_add(layout.size())
+                .and_then(|size| size.checked_add(2 * REDZONE_SIZE))
+                .is_some_and(|size| size <= MEMORY_SIZE)
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Box<'a, T, MEMORY_SIZE, INDEX_SIZE> {
+    /// Allocate the value in the memory pool of `allocator`, waiting for memory to be freed when it is momentarily full,
+    /// see [`IndexAllocator::try_boxed_async`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the value can never fit in the memory pool or the allocation failed otherwise.
+    pub async fn try_new_async(
+        val: T,
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError> {
+        let layout = Layout::new::<T>();
+        let inner_ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            allocator.alloc_pending(layout).await?.cast::<T>()
+        };
+        unsafe { ptr::write(inner_ptr.as_ptr(), val) };
+
+        Ok(unsafe { Self::from_raw_ref(&mut *inner_ptr.as_ptr(), allocator) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::vec::Vec;
+
+    use super::*;
+
+    struct Flag(AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Yield to the executor once, waking the task right away.
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    /// Poll the tasks in order whenever they are woken, until they all complete.
+    fn run(tasks: &mut [Pin<&mut dyn Future<Output = ()>>]) {
+        let flags: Vec<Arc<Flag>> = tasks
+            .iter()
+            .map(|_| Arc::new(Flag(AtomicBool::new(true))))
+            .collect();
+        let mut done = std::vec![false; tasks.len()];
+
+        while done.iter().any(|done| !done) {
+            let mut polled = false;
+            for (i, task) in tasks.iter_mut().enumerate() {
+                if done[i] || !flags[i].0.swap(false, Ordering::SeqCst) {
+                    continue;
+                }
+                polled = true;
+                let waker = Waker::from(flags[i].clone());
+                done[i] = task
+                    .as_mut()
+                    .poll(&mut Context::from_waker(&waker))
+                    .is_ready();
+            }
+            assert!(polled, "every pending task is waiting for a wake up");
+        }
+    }
+
+    #[test]
+    fn test_alloc_pending_waits_for_free() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        let events = RefCell::new(Vec::new());
+
+        let mut holder = core::pin::pin!(async {
+            let buffer = allocator.try_boxed([1u8; 160]).unwrap();
+            events.borrow_mut().push("allocated");
+            YieldNow(false).await;
+            drop(buffer);
+            events.borrow_mut().push("freed");
+        });
+        let mut waiter = core::pin::pin!(async {
+            let buffer = allocator.try_boxed_async([2u8; 160]).await.unwrap();
+            events.borrow_mut().push("awaited");
+            assert_eq!(*buffer, [2; 160]);
+        });
+
+        run(&mut [holder.as_mut(), waiter.as_mut()]);
+
+        assert_eq!(*events.borrow(), ["allocated", "freed", "awaited"]);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_alloc_pending_never_fits() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let mut future = core::pin::pin!(allocator.alloc_pending(Layout::new::<[u8; 128]>()));
+        let waker = Waker::from(Arc::new(Flag(AtomicBool::new(false))));
+        assert_eq!(
+            future.as_mut().poll(&mut Context::from_waker(&waker)),
+            Poll::Ready(Err(IndexError::NoFittingRegion))
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! This module contains the generation checks, making the smart pointers outstanding across [`IndexAllocator::reset`] harmless to drop.
+//!
+//! It is only available with the `generations` feature, which stamps every [`Box`](crate::boxed::Box),
+//! [`Rc`](crate::rc::Rc) and [`Weak`](crate::rc::Weak) with the epoch of the allocator when it is created.
+//! A smart pointer whose epoch is stale is no longer valid: dropping it doesn't free anything,
+//! the memory it pointed to belonging to the allocations made after the reset.
+//! Such drops are counted, see [`IndexAllocator::stale_drops`].
+//!
+//! The stamp costs one more `usize` in a [`Box`](crate::boxed::Box), and a reference to the allocator as well
+//! in an [`Rc`](crate::rc::Rc) or a [`Weak`](crate::rc::Weak), as their own reference is kept in the memory pool.
+
+use crate::IndexAllocator;
+
+/// The epoch of an allocator when a smart pointer was created, with the allocator itself.
+pub(crate) struct Generation<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    epoch: usize,
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    Generation<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Stamp a smart pointer created in `allocator` now.
+    pub fn new(allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        Self {
+            allocator,
+            epoch: allocator.epoch(),
+        }
+    }
+
+    /// Test if the allocator wasn't reset since the stamp was made.
+    pub fn is_current(&self) -> bool {
+        self.allocator.epoch() == self.epoch
+    }
+
+    /// Test if the allocator wasn't reset since the stamp was made, recording the drop of `what` otherwise.
+    pub fn check_drop(&self, what: &str) -> bool {
+        self.allocator.check_drop_epoch(self.epoch, what)
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Clone
+    for Generation<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Copy
+    for Generation<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Return the number of smart pointers dropped after the allocator was reset, which were ignored.
+    #[must_use]
+    pub fn stale_drops(&self) -> usize {
+        self.stale_drops.get()
+    }
+
+    /// Test if `epoch` is the current epoch, recording the drop of `what` otherwise.
+    pub(crate) fn check_drop_epoch(&self, epoch: usize, what: &str) -> bool {
+        if epoch == self.epoch() {
+            return true;
+        }
+
+        self.stale_drops.set(self.stale_drops.get() + 1);
+        log_record!(warn, "Ignored the drop of {what} allocated before a reset");
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boxed::Box;
+    use crate::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn test_stale_box_drop() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let stale = allocator.try_boxed([1u8; 16]).unwrap();
+        assert!(Box::is_valid(&stale));
+        unsafe { allocator.reset().unwrap() };
+        assert!(!Box::is_valid(&stale));
+
+        // The new allocation takes the same bytes.
+        let fresh = allocator.try_boxed([2u8; 16]).unwrap();
+        assert_eq!(stale.as_ptr(), fresh.as_ptr());
+        drop(stale);
+
+        let index = allocator.index.borrow();
+        assert!(index.get_region(0).unwrap().used);
+        drop(index);
+        assert_eq!(allocator.stale_drops(), 1);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        assert_eq!(*fresh, [2; 16]);
+    }
+
+    #[test]
+    fn test_stale_rc_drop() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let stale = Rc::try_new(1u32, &allocator).unwrap();
+        let stale_weak = stale.downgrade();
+        unsafe { allocator.reset().unwrap() };
+        assert!(!Rc::is_valid(&stale));
+        assert!(stale_weak.upgrade().is_none());
+
+        let fresh = Rc::try_new(2u32, &allocator).unwrap();
+        drop(stale);
+        drop(stale_weak);
+
+        assert_eq!(allocator.stale_drops(), 2);
+        assert_eq!(allocator.heap_stats().allocations, 2);
+        assert_eq!(*fresh, 2);
+    }
+}
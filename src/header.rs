@@ -0,0 +1,451 @@
+//! This module contains the [`HeaderIndexAllocator`], which keeps its index inside the memory pool
+//! rather than in a fixed-size array, so that the number of live allocations is only limited by the memory.
+//!
+//! Every block of the memory pool starts with a header holding its size and whether it is used,
+//! the next block starting right after it: the headers form the index.
+//! This costs a header per allocation instead of a slot per allocation, and walking the blocks
+//! is linear in the number of blocks, as looking up the index of an [`IndexAllocator`](crate::IndexAllocator) is.
+//!
+//! The [`HeaderIndexAllocator`] serves raw allocations only, through [`HeaderIndexAllocator::try_alloc`],
+//! [`HeaderIndexAllocator::try_free`] and [`GlobalAlloc`]. The smart pointers of the crate
+//! ([`Box`](crate::boxed::Box), [`Rc`](crate::rc::Rc), [`IndexVec`](crate::vec::IndexVec), ...) don't accept it:
+//! they rely on features of the [`IndexAllocator`](crate::IndexAllocator) beyond allocating and freeing,
+//! such as its epochs, poisoning and priority reserve, which a header per block has no room for.
+//!
+//! As the [`IndexAllocator`](crate::IndexAllocator), it isn't [`Sync`]: the [`sync`](crate::sync) module
+//! has the wrappers declaring it as the global allocator, [`HeaderSingleThreaded`](crate::sync::HeaderSingleThreaded)
+//! and, with the `critical-section` feature, `HeaderLocked`.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::{Cell, UnsafeCell};
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use crate::stats::HeapStats;
+use crate::IndexError;
+
+/// The header at the start of every block: its size in bytes, header included, with [`USED`] set if it is used.
+type Header = usize;
+
+/// The size of a header, which every block starts with.
+const HEADER_SIZE: usize = mem::size_of::<Header>();
+
+/// The alignment of every block, so that their headers are aligned. Block sizes are multiples of it.
+const BLOCK_ALIGN: usize = mem::align_of::<Header>();
+
+/// The smallest block, a header and a word of data.
+const MIN_BLOCK: usize = HEADER_SIZE + BLOCK_ALIGN;
+
+/// The bit of the header set for used blocks, free since block sizes are multiples of [`BLOCK_ALIGN`].
+const USED: usize = 1;
+
+/// A block of the memory pool, with its offset in the memory pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Block {
+    from: usize,
+    size: usize,
+    used: bool,
+}
+
+impl Block {
+    fn end(&self) -> usize {
+        self.from + self.size
+    }
+}
+
+/// An allocator keeping a header before each block of its memory pool, see [`header`](crate::header).
+///
+/// # Example
+///
+/// ```
+/// use core::alloc::Layout;
+/// use index_alloc::header::HeaderIndexAllocator;
+///
+/// let allocator: HeaderIndexAllocator<4096> = HeaderIndexAllocator::empty();
+///
+/// // Far more live allocations than a reasonable index would hold.
+/// let layout = Layout::new::<u32>();
+/// let ptrs: Vec<_> = (0..200).map(|_| allocator.try_alloc(layout).unwrap()).collect();
+/// assert_eq!(allocator.heap_stats().allocations, 200);
+///
+/// for ptr in ptrs {
+///     unsafe { allocator.try_free(ptr.as_ptr()).unwrap() };
+/// }
+/// assert_eq!(allocator.heap_stats().allocations, 0);
+/// ```
+pub struct HeaderIndexAllocator<const MEMORY_SIZE: usize> {
+    memory: UnsafeCell<[u8; MEMORY_SIZE]>,
+    /// Whether the first header was written, which needs the address of the memory pool.
+    initialized: Cell<bool>,
+    used_bytes: Cell<usize>,
+    allocations: Cell<usize>,
+}
+
+impl<const MEMORY_SIZE: usize> HeaderIndexAllocator<MEMORY_SIZE> {
+    /// Create an empty [`HeaderIndexAllocator`], its memory pool being a single free block.
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            memory: UnsafeCell::new([0; MEMORY_SIZE]),
+            initialized: Cell::new(false),
+            used_bytes: Cell::new(0),
+            allocations: Cell::new(0),
+        }
+    }
+
+    /// Get the offsets of the first block and of the end of the blocks, both aligned for the headers.
+    fn bounds(&self) -> (usize, usize) {
+        let start = (self.memory.get() as *const u8).align_offset(BLOCK_ALIGN);
+        let end = start + MEMORY_SIZE.saturating_sub(start) / BLOCK_ALIGN * BLOCK_ALIGN;
+        (start.min(end), end)
+    }
+
+    /// Write the header of the single free block covering the memory pool, once.
+    fn init(&self) {
+        if self.initialized.replace(true) {
+            return;
+        }
+
+        let (start, end) = self.bounds();
+        if end - start >= MIN_BLOCK {
+            unsafe { self.write_block(start, end - start, false) };
+        }
+    }
+
+    /// Read the block starting at `from`.
+    ///
+    /// # Safety
+    ///
+    /// A header must start at `from`.
+    unsafe fn block(&self, from: usize) -> Block {
+        let header = ptr::read(self.memory.get().cast::<u8>().add(from).cast::<Header>());
+        Block {
+            from,
+            size: header & !USED,
+            used: header & USED != 0,
+        }
+    }
+
+    /// Write the header of a block starting at `from`.
+    ///
+    /// # Safety
+    ///
+    /// `from` must be aligned for the headers, and `size` a multiple of [`BLOCK_ALIGN`] keeping the block in the memory pool.
+    unsafe fn write_block(&self, from: usize, size: usize, used: bool) {
+        ptr::write(
+            self.memory.get().cast::<u8>().add(from).cast::<Header>(),
+            size | if used { USED } else { 0 },
+        );
+    }
+
+    /// Iterate over the blocks of the memory pool, in address order.
+    fn blocks(&self) -> impl Iterator<Item = Block> + '_ {
+        self.init();
+        let (start, end) = self.bounds();
+        let mut next = if end - start >= MIN_BLOCK { start } else { end };
+        core::iter::from_fn(move || {
+            if next >= end {
+                return None;
+            }
+            let block = unsafe { self.block(next) };
+            next = block.end();
+            Some(block)
+        })
+    }
+
+    /// Compute where `layout` fits in `block`: the offset of the block of the allocation,
+    /// after a free block filling the gap needed to align the data, and the size of the allocation block.
+    fn fit(&self, block: Block, layout: Layout) -> Option<(usize, usize)> {
+        let memory_start = self.memory.get() as usize;
+        let align = layout.align().max(BLOCK_ALIGN);
+        let aligned_data = |from: usize| {
+            let data = memory_start.checked_add(from)?.checked_add(align - 1)? & !(align - 1);
+            Some(data - memory_start)
+        };
+
+        let mut data = aligned_data(block.from + HEADER_SIZE)?;
+        // A gap before the allocation must hold a free block of its own.
+        if data - HEADER_SIZE != block.from && data - HEADER_SIZE - block.from < MIN_BLOCK {
+            data = aligned_data(block.from + HEADER_SIZE + MIN_BLOCK)?;
+        }
+        let from = data - HEADER_SIZE;
+        let size = HEADER_SIZE
+            .checked_add(layout.size())?
+            .checked_add(BLOCK_ALIGN - 1)?
+            & !(BLOCK_ALIGN - 1);
+        let size = size.max(MIN_BLOCK);
+
+        (from.checked_add(size)? <= block.end()).then_some((from, size))
+    }
+
+    /// Try to allocate memory for `layout` in the first free block it fits in.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::NoFittingRegion`] if no free block can hold the layout.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_alloc(&self, layout: Layout) -> Result<NonNull<u8>, IndexError> {
+        if layout.align() > MEMORY_SIZE {
+            return Err(IndexError::NoFittingRegion);
+        }
+
+        let (block, (from, size)) = self
+            .blocks()
+            .filter(|block| !block.used)
+            .find_map(|block| Some((block, self.fit(block, layout)?)))
+            .ok_or(IndexError::NoFittingRegion)?;
+
+        unsafe {
+            if from != block.from {
+                self.write_block(block.from, from - block.from, false);
+            }
+            let tail = block.end() - (from + size);
+            if tail >= MIN_BLOCK {
+                self.write_block(from, size, true);
+                self.write_block(from + size, tail, false);
+            } else {
+                self.write_block(from, size + tail, true);
+            }
+            self.used_bytes
+                .set(self.used_bytes.get() + self.block(from).size);
+        }
+        self.allocations.set(self.allocations.get() + 1);
+
+        NonNull::new(unsafe { self.memory.get().cast::<u8>().add(from + HEADER_SIZE) })
+            .ok_or(IndexError::EmptyPtr)
+    }
+
+    /// Try to free the allocation at `ptr`, merging its block with the free blocks around it.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::EmptyPtr`] if `ptr` is null,
+    /// an [`IndexError::OutOfMemory`] if it isn't in the memory pool,
+    /// an [`IndexError::NoSuchRegion`] if it isn't the start of an allocation,
+    /// or an [`IndexError::DoubleFree`] if the allocation was already freed.
+    ///
+    /// # Safety
+    ///
+    /// The allocation must not be used anymore.
+    pub unsafe fn try_free(&self, ptr: *mut u8) -> Result<(), IndexError> {
+        if ptr.is_null() {
+            return Err(IndexError::EmptyPtr);
+        }
+        let offset = (ptr as usize).wrapping_sub(self.memory.get() as usize);
+        if offset >= MEMORY_SIZE {
+            return Err(IndexError::OutOfMemory);
+        }
+
+        // The header is only trusted once found by walking the blocks, so a wrong pointer can't corrupt the pool.
+        let mut previous = None;
+        let mut blocks = self.blocks();
+        let block = loop {
+            match blocks.next() {
+                Some(block) if block.from + HEADER_SIZE == offset => break block,
+                Some(block) if block.from < offset => previous = Some(block),
+                _ => return Err(IndexError::NoSuchRegion),
+            }
+        };
+        if !block.used {
+            return Err(IndexError::DoubleFree);
+        }
+
+        self.used_bytes
+            .set(self.used_bytes.get().saturating_sub(block.size));
+        self.allocations
+            .set(self.allocations.get().saturating_sub(1));
+
+        let mut merged = Block {
+            used: false,
+            ..block
+        };
+        if let Some(next) = blocks.next().filter(|next| !next.used) {
+            merged.size += next.size;
+        }
+        if let Some(previous) = previous.filter(|previous| !previous.used) {
+            merged.from = previous.from;
+            merged.size += previous.size;
+        }
+        self.write_block(merged.from, merged.size, false);
+
+        Ok(())
+    }
+
+    /// Get the current [`HeapStats`] of the allocator, see [`IndexAllocator::heap_stats`](crate::IndexAllocator::heap_stats).
+    ///
+    /// The used bytes include the headers of the used blocks.
+    #[must_use]
+    pub fn heap_stats(&self) -> HeapStats {
+        let used_bytes = self.used_bytes.get();
+
+        HeapStats {
+            used_bytes,
+            free_bytes: MEMORY_SIZE.saturating_sub(used_bytes),
+            allocations: self.allocations.get(),
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize> Default for HeaderIndexAllocator<MEMORY_SIZE> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+unsafe impl<const MEMORY_SIZE: usize> GlobalAlloc for HeaderIndexAllocator<MEMORY_SIZE> {
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.try_alloc(layout)
+            .map_or(ptr::null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        // Freeing a null pointer does nothing, as with the C `free`.
+        if ptr.is_null() {
+            return;
+        }
+        // A global allocator must not unwind, and there is no way to report the error.
+        let _ = self.try_free(ptr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Check the blocks cover the memory pool without two adjacent free blocks.
+    fn check_blocks<const MEMORY_SIZE: usize>(allocator: &HeaderIndexAllocator<MEMORY_SIZE>) {
+        let (start, end) = allocator.bounds();
+        let blocks: Vec<Block> = allocator.blocks().collect();
+        assert_eq!(blocks.first().map(|block| block.from), Some(start));
+        assert_eq!(blocks.last().map(Block::end), Some(end));
+        for pair in blocks.windows(2) {
+            assert_eq!(pair[0].end(), pair[1].from);
+            assert!(pair[0].used || pair[1].used, "adjacent free blocks");
+        }
+    }
+
+    #[test]
+    fn test_header_alloc_free() {
+        let allocator: HeaderIndexAllocator<256> = HeaderIndexAllocator::empty();
+
+        let ptrs: Vec<*mut u8> = (0..3)
+            .map(|i| {
+                let ptr = allocator
+                    .try_alloc(Layout::new::<[u8; 16]>())
+                    .unwrap()
+                    .as_ptr();
+                unsafe { ptr.write_bytes(i, 16) };
+                ptr
+            })
+            .collect();
+        check_blocks(&allocator);
+        for (i, ptr) in ptrs.iter().enumerate() {
+            let bytes = unsafe { core::slice::from_raw_parts(*ptr, 16) };
+            assert!(bytes.iter().all(|&byte| usize::from(byte) == i));
+        }
+
+        // Free the middle block, then the ones around it, which merge back into a single block.
+        for i in [1, 0, 2] {
+            unsafe { allocator.try_free(ptrs[i]).unwrap() };
+            check_blocks(&allocator);
+        }
+        assert_eq!(allocator.blocks().count(), 1);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.heap_stats().used_bytes, 0);
+    }
+
+    #[test]
+    fn test_header_free_errors() {
+        let allocator: HeaderIndexAllocator<256> = HeaderIndexAllocator::empty();
+
+        let ptr = allocator
+            .try_alloc(Layout::new::<[u8; 16]>())
+            .unwrap()
+            .as_ptr();
+        let outside = 0u8;
+        unsafe {
+            assert_eq!(
+                allocator.try_free(ptr::null_mut()),
+                Err(IndexError::EmptyPtr)
+            );
+            assert_eq!(
+                allocator.try_free(ptr::addr_of!(outside).cast_mut()),
+                Err(IndexError::OutOfMemory)
+            );
+            assert_eq!(
+                allocator.try_free(ptr.add(4)),
+                Err(IndexError::NoSuchRegion)
+            );
+            allocator.try_free(ptr).unwrap();
+            assert_eq!(allocator.try_free(ptr), Err(IndexError::DoubleFree));
+        }
+        check_blocks(&allocator);
+    }
+
+    #[test]
+    fn test_header_alignments() {
+        for align in (0..=7).map(|shift| 1 << shift) {
+            for size in [1, 4, 16, 33] {
+                let layout = Layout::from_size_align(size, align).unwrap();
+                let allocator: HeaderIndexAllocator<512> = HeaderIndexAllocator::empty();
+
+                // Fill the pool, free every other allocation and fill the holes left.
+                let mut ptrs = Vec::new();
+                for round in 0..2 {
+                    while let Ok(ptr) = allocator.try_alloc(layout) {
+                        assert_eq!(ptr.as_ptr().align_offset(align), 0, "{layout:?} misaligned");
+                        ptrs.push(ptr.as_ptr());
+                    }
+                    check_blocks(&allocator);
+                    if round == 0 {
+                        for ptr in ptrs.iter().step_by(2) {
+                            unsafe { allocator.try_free(*ptr).unwrap() };
+                        }
+                        ptrs = ptrs.into_iter().skip(1).step_by(2).collect();
+                    }
+                }
+
+                for ptr in ptrs {
+                    unsafe { allocator.try_free(ptr).unwrap() };
+                }
+                assert_eq!(allocator.blocks().count(), 1, "{layout:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_header_many_allocations() {
+        let allocator: HeaderIndexAllocator<65536> = HeaderIndexAllocator::empty();
+
+        let layout = Layout::new::<u64>();
+        let ptrs: Vec<*mut u8> = (0..2000)
+            .map(|_| allocator.try_alloc(layout).unwrap().as_ptr())
+            .collect();
+        assert_eq!(allocator.heap_stats().allocations, 2000);
+        check_blocks(&allocator);
+
+        for ptr in ptrs.into_iter().rev() {
+            unsafe { allocator.dealloc(ptr, layout) };
+        }
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.blocks().count(), 1);
+    }
+
+    #[test]
+    fn test_header_too_large() {
+        let allocator: HeaderIndexAllocator<64> = HeaderIndexAllocator::empty();
+
+        assert_eq!(
+            allocator.try_alloc(Layout::new::<[u8; 64]>()),
+            Err(IndexError::NoFittingRegion)
+        );
+        assert_eq!(
+            allocator.try_alloc(Layout::from_size_align(1, 128).unwrap()),
+            Err(IndexError::NoFittingRegion)
+        );
+        assert!(unsafe { allocator.alloc(Layout::new::<[u8; 64]>()) }.is_null());
+    }
+}
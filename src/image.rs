@@ -0,0 +1,367 @@
+//! This module contains the images of an [`IndexAllocator`], to build a graph of values in its memory pool
+//! on one machine, store it, and attach it on another one, see [`IndexAllocator::export_image`].
+//!
+//! An image holds the used regions of the memory pool and their bytes, addressed by their offset from the start of the pool,
+//! so that it can be imported in an allocator living at another address. The values it holds must only link to each other
+//! with [`IndexPtr`](crate::index_ptr::IndexPtr), which are offsets as well. Anything holding an absolute address,
+//! such as a [`Box`](crate::boxed::Box), an [`Rc`](crate::rc::Rc), a reference or a raw pointer, is unsupported:
+//! once imported, it would still point in the memory pool of the exporting allocator.
+//!
+//! The values keep their offset, so they stay aligned only if both memory pools have the same address modulo
+//! their alignment: the import checks it for alignments up to [`IMAGE_ALIGN`]. The bytes of the values are copied as is,
+//! so both machines need the same endianness and pointer width, and both allocators the same `redzone` feature.
+//!
+//! The image starts with a header of 4 little-endian `u32`: the magic bytes `IXIM`, the size of the memory pool,
+//! its address modulo [`IMAGE_ALIGN`] and the number of used regions. A record of 5 `u32` follows for every region:
+//! its offset, its size, the offset of the value in it, the size requested by the allocation and its flags.
+//! The bytes of the regions come last, in the order of the records.
+
+use core::ptr;
+
+use crate::index::{MemoryIndex, MemoryRegion};
+use crate::{IndexAllocator, IndexError};
+
+/// The largest alignment of the values an image keeps, see [`IndexAllocator::import_image`].
+pub const IMAGE_ALIGN: usize = 16;
+
+const MAGIC: [u8; 4] = *b"IXIM";
+const HEADER_SIZE: usize = 16;
+const RECORD_SIZE: usize = 20;
+/// The flag of a region holding the arena of the bump mode.
+const ARENA_FLAG: usize = 1;
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Compute the size of the image [`IndexAllocator::export_image`] would write.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn image_size(&self) -> Result<usize, IndexError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        Ok(index
+            .regions()
+            .filter(|region| region.used)
+            .fold(HEADER_SIZE, |size, region| size + RECORD_SIZE + region.size))
+    }
+
+    /// Write an image of the used regions of the memory pool to `out`, see [`image`](crate::image),
+    /// and return the number of bytes written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::index_ptr::IndexPtr;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed(42u16).unwrap();
+    /// let ptr: IndexPtr<u16> = allocator.ptr_to(&*test_box).unwrap();
+    ///
+    /// let mut image = [0; 128];
+    /// let len = allocator.export_image(&mut image).unwrap();
+    /// assert_eq!(len, allocator.image_size().unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::RegionTooThin`] if `out` is smaller than [`IndexAllocator::image_size`],
+    /// an [`IndexError::OutOfMemory`] if a buffer attached with [`IndexAllocator::add_region`] holds an allocation,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn export_image(&self, out: &mut [u8]) -> Result<usize, IndexError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let used = || index.regions().filter(|region| region.used);
+        if used().any(|region| region.pool != 0) {
+            return Err(IndexError::OutOfMemory);
+        }
+
+        let count = used().count();
+        out.get_mut(..4)
+            .ok_or(IndexError::RegionTooThin)?
+            .copy_from_slice(&MAGIC);
+        write_u32(out, 4, MEMORY_SIZE)?;
+        write_u32(out, 8, self.memory.get() as usize % IMAGE_ALIGN)?;
+        write_u32(out, 12, count)?;
+
+        let mut data = HEADER_SIZE + count * RECORD_SIZE;
+        for (i, region) in used().enumerate() {
+            #[cfg(feature = "redzone")]
+            let data_offset = region.data_offset;
+            #[cfg(not(feature = "redzone"))]
+            let data_offset = 0;
+            #[cfg(feature = "stats")]
+            let requested_size = region.requested_size;
+            #[cfg(not(feature = "stats"))]
+            let requested_size = 0;
+            let flags = if region.arena { ARENA_FLAG } else { 0 };
+
+            let record = HEADER_SIZE + i * RECORD_SIZE;
+            for (at, field) in [region.from, region.size, data_offset, requested_size, flags]
+                .into_iter()
+                .enumerate()
+            {
+                write_u32(out, record + 4 * at, field)?;
+            }
+
+            let bytes = out
+                .get_mut(data..data + region.size)
+                .ok_or(IndexError::RegionTooThin)?;
+            unsafe {
+                ptr::copy_nonoverlapping(self.ptr_at(region.from), bytes.as_mut_ptr(), region.size);
+            }
+            data += region.size;
+        }
+
+        Ok(data)
+    }
+
+    /// Replace every allocation with the ones of an image written by [`IndexAllocator::export_image`],
+    /// as if they had been made at the same offsets in this memory pool, see [`image`](crate::image).
+    ///
+    /// The memory pool of the exporting allocator may be smaller than this one, the bytes past it are left free.
+    /// Like [`IndexAllocator::reset`], the import starts a new epoch of the allocator, and it leaves the bump mode.
+    /// The image is checked before anything is replaced: on failure, the allocator is left unchanged.
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated before the import may be accessed or dropped afterwards, as for [`IndexAllocator::reset`].
+    /// The image must have been written by [`IndexAllocator::export_image`], on a machine and with features
+    /// matching this one as described in [`image`](crate::image), and the values in it must hold no absolute address.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::InvalidImage`] if the image is malformed, bigger than the memory pool,
+    /// or exported from a memory pool whose address doesn't match this one modulo [`IMAGE_ALIGN`],
+    /// an [`IndexError::NoIndexAvailable`] if the index can't hold the regions of the image,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    /// An allocator in buddy mode can't import an image either, it raises an [`IndexError::InvalidImage`].
+    pub unsafe fn import_image(&self, image: &[u8]) -> Result<(), IndexError> {
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            return Err(IndexError::InvalidImage);
+        }
+        if image.get(..4) != Some(&MAGIC[..])
+            || read_u32(image, 4)? > MEMORY_SIZE
+            || read_u32(image, 8)? != self.memory.get() as usize % IMAGE_ALIGN
+        {
+            return Err(IndexError::InvalidImage);
+        }
+        let memory_size = read_u32(image, 4)?;
+        let count = read_u32(image, 12)?;
+
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        // The used regions are carved out of an empty index, one after the other.
+        let mut imported = MemoryIndex::empty(MEMORY_SIZE);
+        self.insert_extra_pools(&mut imported)?;
+        let mut data = count
+            .checked_mul(RECORD_SIZE)
+            .and_then(|records| records.checked_add(HEADER_SIZE))
+            .ok_or(IndexError::InvalidImage)?;
+        for i in 0..count {
+            let region = read_region(image, HEADER_SIZE + i * RECORD_SIZE)?;
+            if region.size == 0 || region.from.saturating_add(region.size) > memory_size {
+                return Err(IndexError::InvalidImage);
+            }
+
+            // Regions overlapping the ones carved before aren't in a free region.
+            let mut slot = imported.find_region(region.from)?;
+            let free = imported.get_region(slot)?;
+            if free.used || region.end() > free.end() {
+                return Err(IndexError::InvalidImage);
+            }
+            if free.from < region.from {
+                slot = imported.split_region(slot, region.from - free.from)?.1;
+            }
+            if imported.get_region(slot)?.size > region.size {
+                imported.split_region(slot, region.size)?;
+            }
+            *imported.get_region_mut(slot)? = region;
+            data = data
+                .checked_add(imported.get_region(slot)?.size)
+                .ok_or(IndexError::InvalidImage)?;
+        }
+        if data != image.len() {
+            return Err(IndexError::InvalidImage);
+        }
+
+        if let Some(byte) = self.free_scrub.get() {
+            self.fill(0, MEMORY_SIZE, byte);
+            self.fill_extra_pools(byte);
+        }
+        // The imported bytes land wherever the image put them.
+        self.touch(0, MEMORY_SIZE);
+        let mut data = HEADER_SIZE + count * RECORD_SIZE;
+        let (mut used_bytes, mut allocations) = (0, 0);
+        #[cfg(feature = "stats")]
+        self.size_histogram.clear_live();
+        for region in imported.regions().filter(|region| region.used) {
+            let bytes = image
+                .get(data..data + region.size)
+                .ok_or(IndexError::InvalidImage)?;
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr_at(region.from), region.size);
+            data += region.size;
+            used_bytes += region.size;
+            allocations += 1;
+            #[cfg(feature = "stats")]
+            self.size_histogram.record_alloc(region.requested_size);
+        }
+        *index = imported;
+        self.used_bytes.set(used_bytes);
+        self.allocations.set(allocations);
+        self.epoch.set(self.epoch.get().wrapping_add(1));
+        self.bump.set(None);
+        drop(index);
+
+        self.check_watermark();
+        #[cfg(feature = "async")]
+        self.wake_pending();
+
+        Ok(())
+    }
+}
+
+/// Write `val` at `at` as a little-endian `u32`.
+fn write_u32(out: &mut [u8], at: usize, val: usize) -> Result<(), IndexError> {
+    let val = u32::try_from(val).map_err(|_| IndexError::OutOfMemory)?;
+    out.get_mut(at..at + 4)
+        .ok_or(IndexError::RegionTooThin)?
+        .copy_from_slice(&val.to_le_bytes());
+    Ok(())
+}
+
+/// Read the little-endian `u32` at `at`.
+fn read_u32(image: &[u8], at: usize) -> Result<usize, IndexError> {
+    let bytes = image
+        .get(at..at.checked_add(4).ok_or(IndexError::InvalidImage)?)
+        .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+        .ok_or(IndexError::InvalidImage)?;
+    Ok(u32::from_le_bytes(bytes) as usize)
+}
+
+/// Read the record of a used region at `at`.
+fn read_region(image: &[u8], at: usize) -> Result<MemoryRegion, IndexError> {
+    let mut region = MemoryRegion::new(read_u32(image, at)?, read_u32(image, at + 4)?, true);
+    #[cfg(feature = "redzone")]
+    {
+        region.data_offset = read_u32(image, at + 8)?;
+    }
+    #[cfg(feature = "stats")]
+    {
+        region.requested_size = read_u32(image, at + 12)?;
+    }
+    region.arena = read_u32(image, at + 16)? & ARENA_FLAG != 0;
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box as StdBox;
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::index_ptr::IndexPtr;
+
+    struct Node {
+        val: u16,
+        next: IndexPtr<Node>,
+    }
+
+    /// An allocator whose memory pool has the same address modulo [`IMAGE_ALIGN`] as any other one.
+    #[repr(align(64))]
+    struct Aligned(IndexAllocator<256, 16>);
+
+    #[test]
+    // Ignore MIRI because the allocator inner memory is directly written, wich MIRI don't like.
+    #[cfg_attr(miri, ignore)]
+    fn test_image_linked_list() {
+        let host = StdBox::new(Aligned(IndexAllocator::empty()));
+        let device = StdBox::new(Aligned(IndexAllocator::empty()));
+        let (host, device) = (&host.0, &device.0);
+
+        // The freed padding leaves a hole at the start of the pool, kept free by the import.
+        let padding = host.try_boxed([0u8; 8]).unwrap();
+        let mut head = IndexPtr::null();
+        for val in 1..=3 {
+            let node = unsafe { host.try_alloc_value(Node { val, next: head }) }.unwrap();
+            head = host.ptr_to(unsafe { node.as_ref() }).unwrap();
+        }
+        drop(padding);
+
+        let mut image = vec![0; host.image_size().unwrap()];
+        assert_eq!(host.export_image(&mut image), Ok(image.len()));
+        assert_eq!(
+            host.export_image(&mut image[..HEADER_SIZE]),
+            Err(IndexError::RegionTooThin)
+        );
+
+        unsafe { device.import_image(&image).unwrap() };
+        assert_ne!(host.memory.get().cast::<u8>(), device.memory.get().cast());
+        assert_eq!(device.heap_stats(), host.heap_stats());
+        assert_eq!(device.epoch(), 1);
+
+        let mut values = Vec::new();
+        let mut current = head;
+        while !current.is_null() {
+            let node = unsafe { device.resolve(current) }.unwrap();
+            values.push(node.val);
+            current = node.next;
+        }
+        assert_eq!(values, [3, 2, 1]);
+
+        // The imported allocations are freed and the free memory allocated as usual.
+        let head_offset = head.offset().unwrap();
+        device.try_free_addr(head_offset).unwrap();
+        assert_eq!(device.heap_stats().allocations, 2);
+        assert!(device.try_boxed([0u8; 8]).is_ok());
+    }
+
+    #[test]
+    fn test_image_invalid() {
+        let host: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let device: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let small: IndexAllocator<32, 8> = IndexAllocator::empty();
+
+        let _test_box = host.try_boxed([1u8; 40]).unwrap();
+        let mut image = vec![0; host.image_size().unwrap()];
+        host.export_image(&mut image).unwrap();
+        // Both pools are made to have the same address modulo IMAGE_ALIGN.
+        write_u32(&mut image, 8, device.memory.get() as usize % IMAGE_ALIGN).unwrap();
+
+        let mut bad_magic = image.clone();
+        bad_magic[0] = b'X';
+        let mut overlapping = image.clone();
+        write_u32(&mut overlapping, 12, 2).unwrap();
+        overlapping.splice(
+            HEADER_SIZE..HEADER_SIZE,
+            image[HEADER_SIZE..][..RECORD_SIZE].to_vec(),
+        );
+        overlapping.extend_from_slice(&image[HEADER_SIZE + RECORD_SIZE..]);
+        let invalid = [bad_magic, image[..image.len() - 1].to_vec(), overlapping];
+        for image in invalid {
+            assert_eq!(
+                unsafe { device.import_image(&image) },
+                Err(IndexError::InvalidImage)
+            );
+        }
+        assert_eq!(
+            unsafe { small.import_image(&image) },
+            Err(IndexError::InvalidImage)
+        );
+        assert_eq!(device.heap_stats().allocations, 0);
+
+        unsafe { device.import_image(&image).unwrap() };
+        assert_eq!(device.heap_stats().allocations, 1);
+    }
+}
@@ -1,21 +1,66 @@
+//! This module contains the [`MemoryIndex`], keeping track of the regions of the memory pool.
+//!
+//! It is only public with the `index-fixtures` feature, to build arbitrary indices in integration tests.
+//!
+//! With the `compact-index` feature, every slot of the index packs its region in a single `u32`,
+//! which limits the memory pool to [`COMPACT_MEMORY_SIZE`] bytes. The regions are unpacked on access,
+//! so the index keeps working with [`MemoryRegion`] values.
+
 use core::alloc::Layout;
-use core::cmp::Ordering;
+#[cfg(test)]
+use core::cell::Cell;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "call-site")]
+use core::panic::Location;
 
 use crate::IndexError;
 
 /// The representation of a region of the memory pool in the index.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MemoryRegion {
     pub from: usize,
     pub size: usize,
     pub used: bool,
+    /// The pool holding the region: 0 for the memory pool of the allocator, then the buffers attached
+    /// with [`IndexAllocator::add_region`](crate::IndexAllocator::add_region) in order.
+    /// Regions of different pools are never merged, even if the pools happen to be adjacent.
+    pub pool: usize,
+    /// Whether the region is the arena of the bump mode, whose allocations are never freed individually.
+    pub arena: bool,
+    /// The call site which reserved the region, if it is used.
+    #[cfg(feature = "call-site")]
+    pub location: Option<&'static Location<'static>>,
+    /// The time the region was reserved at, if it is used and a clock is set.
+    #[cfg(feature = "age")]
+    pub allocated_at: Option<u32>,
+    /// The offset of the value from the start of the region, after the alignment padding and the gap before it.
+    #[cfg(feature = "redzone")]
+    pub data_offset: usize,
+    /// The size requested by the allocation holding the region, before any padding.
+    #[cfg(feature = "stats")]
+    pub requested_size: usize,
 }
 
 impl MemoryRegion {
     /// Create a new [`MemoryRegion`].
     #[must_use]
     pub const fn new(from: usize, size: usize, used: bool) -> Self {
-        Self { from, size, used }
+        Self {
+            from,
+            size,
+            used,
+            pool: 0,
+            arena: false,
+            #[cfg(feature = "call-site")]
+            location: None,
+            #[cfg(feature = "age")]
+            allocated_at: None,
+            #[cfg(feature = "redzone")]
+            data_offset: 0,
+            #[cfg(feature = "stats")]
+            requested_size: 0,
+        }
     }
 
     /// Mark the region as used.
@@ -26,18 +71,35 @@ impl MemoryRegion {
     /// Mark the region as available for use.
     pub fn free(&mut self) {
         self.used = false;
+        self.arena = false;
+        #[cfg(feature = "call-site")]
+        {
+            self.location = None;
+        }
+        #[cfg(feature = "age")]
+        {
+            self.allocated_at = None;
+        }
+        #[cfg(feature = "redzone")]
+        {
+            self.data_offset = 0;
+        }
+        #[cfg(feature = "stats")]
+        {
+            self.requested_size = 0;
+        }
     }
 
-    /// Compute the end address of the region.
+    /// Compute the end address of the region, saturating at [`usize::MAX`] rather than overflowing.
     #[must_use]
     pub fn end(&self) -> usize {
-        self.from + self.size
+        self.from.saturating_add(self.size)
     }
 
     /// Test if the region contains the specified address.
     #[must_use]
     pub fn contains(&self, addr: usize) -> bool {
-        self.from <= addr && addr < self.from + self.size
+        self.from <= addr && addr < self.end()
     }
 }
 
@@ -50,49 +112,302 @@ pub struct AllocationBaker {
     pub offset: usize,
 }
 
+/// The largest memory pool the `compact-index` feature can index, 16 KiB.
+#[cfg(feature = "compact-index")]
+pub const COMPACT_MEMORY_SIZE: usize = 1 << 14;
+
+/// A slot of the index, holding a region or nothing.
+#[cfg(not(feature = "compact-index"))]
+#[derive(Debug, Clone, Copy)]
+struct Slot(Option<MemoryRegion>);
+
+#[cfg(not(feature = "compact-index"))]
+impl Slot {
+    const EMPTY: Self = Self(None);
+
+    const fn new(region: Option<MemoryRegion>) -> Self {
+        Self(region)
+    }
+
+    fn get(&self) -> Option<MemoryRegion> {
+        self.0
+    }
+}
+
+/// A slot of the index, packing its region in a `u32` whose bits are the offset (15 bits), the size (15 bits),
+/// the used bit and the arena bit, all inverted so that the empty slot is 0.
+///
+/// The offsets and sizes up to [`COMPACT_MEMORY_SIZE`] fit, but a region of an attached buffer doesn't.
+#[cfg(feature = "compact-index")]
+#[derive(Debug, Clone, Copy)]
+struct Slot(u32);
+
+#[cfg(feature = "compact-index")]
+impl Slot {
+    const EMPTY: Self = Self(0);
+    const FIELD_BITS: u32 = 15;
+    const FIELD_MASK: u32 = (1 << Self::FIELD_BITS) - 1;
+    const USED: u32 = 1 << (2 * Self::FIELD_BITS);
+    const ARENA: u32 = Self::USED << 1;
+
+    const fn new(region: Option<MemoryRegion>) -> Self {
+        let Some(region) = region else {
+            return Self::EMPTY;
+        };
+        debug_assert!(
+            region.from.saturating_add(region.size) <= COMPACT_MEMORY_SIZE && region.pool == 0
+        );
+
+        let mut word = (region.from as u32 & Self::FIELD_MASK)
+            | (region.size as u32 & Self::FIELD_MASK) << Self::FIELD_BITS;
+        if region.used {
+            word |= Self::USED;
+        }
+        if region.arena {
+            word |= Self::ARENA;
+        }
+        // The offset of a region is at most 2^14, so the inverted word is never 0.
+        Self(!word)
+    }
+
+    fn get(&self) -> Option<MemoryRegion> {
+        if self.0 == 0 {
+            return None;
+        }
+
+        let word = !self.0;
+        let mut region = MemoryRegion::new(
+            (word & Self::FIELD_MASK) as usize,
+            (word >> Self::FIELD_BITS & Self::FIELD_MASK) as usize,
+            word & Self::USED != 0,
+        );
+        region.arena = word & Self::ARENA != 0;
+        Some(region)
+    }
+}
+
+impl Slot {
+    fn is_none(&self) -> bool {
+        self.get().is_none()
+    }
+}
+
+/// Mutable access to a region of the index, see [`MemoryIndex::get_region_mut`].
+///
+/// The region is a copy, written back to its slot when the access is dropped.
+pub struct RegionMut<'a> {
+    slot: &'a mut Slot,
+    region: MemoryRegion,
+}
+
+impl Deref for RegionMut<'_> {
+    type Target = MemoryRegion;
+
+    fn deref(&self) -> &MemoryRegion {
+        &self.region
+    }
+}
+
+impl DerefMut for RegionMut<'_> {
+    fn deref_mut(&mut self) -> &mut MemoryRegion {
+        &mut self.region
+    }
+}
+
+impl Drop for RegionMut<'_> {
+    fn drop(&mut self) {
+        *self.slot = Slot::new(Some(self.region));
+    }
+}
+
 /// The type storing the memroy regions informations and so keeping the abstract representation of the memory pool.
+///
+/// Once sorted, the index stays sorted: splitting a region inserts the right part just after it,
+/// and freeing a region only merges it with the slots next to it,
+/// so that no operation needs more than a few passes over the slots.
 #[derive(Debug, Clone)]
 pub struct MemoryIndex<const INDEX_SIZE: usize> {
-    regions: [Option<MemoryRegion>; INDEX_SIZE],
+    regions: [Slot; INDEX_SIZE],
+    /// Whether the regions are known to be in ascending order, followed by the empty slots.
+    sorted: bool,
+    /// The number of slots visited, to check the bounds of the operations in tests.
+    #[cfg(test)]
+    visits: Cell<usize>,
 }
 
 impl<const INDEX_SIZE: usize> MemoryIndex<INDEX_SIZE> {
     /// Create the [`MemoryIndex`] based on preexisting partition.
+    /// The partition doesn't need to be sorted.
     pub const fn new(regions: [Option<MemoryRegion>; INDEX_SIZE]) -> Self {
-        Self { regions }
+        let mut slots = [Slot::EMPTY; INDEX_SIZE];
+        let mut i = 0;
+        while i < INDEX_SIZE {
+            slots[i] = Slot::new(regions[i]);
+            i += 1;
+        }
+
+        Self {
+            regions: slots,
+            sorted: false,
+            #[cfg(test)]
+            visits: Cell::new(0),
+        }
+    }
+
+    /// Create the [`MemoryIndex`] from a partition of the memory pool, which doesn't need to be sorted.
+    ///
+    /// Unlike [`MemoryIndex::new`], the regions are validated: they must cover the memory pool from its start
+    /// without any gap or overlap, otherwise `None` is returned.
+    #[cfg(feature = "index-fixtures")]
+    #[must_use]
+    pub fn from_regions(regions: [Option<MemoryRegion>; INDEX_SIZE]) -> Option<Self> {
+        let partition = || regions.iter().flatten();
+        let size: usize = partition().map(|region| region.size).sum();
+        let overlaps = |(i, region): (usize, &MemoryRegion)| {
+            partition()
+                .skip(i + 1)
+                .any(|other| region.from < other.end() && other.from < region.end())
+        };
+
+        // Without overlap, regions summing to the size of the pool they fit in leave no gap.
+        let valid = partition().any(|region| region.from == 0)
+            && partition().all(|region| region.end() <= size)
+            && !partition().enumerate().any(overlaps);
+
+        valid.then(|| Self::new(regions))
     }
 
     /// Create the [`MemoryIndex`] as a single region containing the whole memory pool.
     pub const fn empty(memory_size: usize) -> Self {
         const NONE: Option<MemoryRegion> = None;
         let mut regions = [NONE; INDEX_SIZE];
-        regions[0] = Some(MemoryRegion::new(0, memory_size, false));
-        Self::new(regions)
+        if INDEX_SIZE > 0 {
+            regions[0] = Some(MemoryRegion::new(0, memory_size, false));
+        }
+        let mut index = Self::new(regions);
+        index.sorted = true;
+        index
+    }
+
+    /// Create the [`MemoryIndex`] of a memory pool in buddy mode, cut in free blocks of decreasing powers of two,
+    /// each aligned on its size from the start of the memory pool.
+    ///
+    /// A memory pool whose size is a power of two is a single block. If the index is too small to hold every block,
+    /// the smallest ones at the end of the memory pool are left out.
+    #[cfg(feature = "buddy")]
+    pub const fn buddy(memory_size: usize) -> Self {
+        const NONE: Option<MemoryRegion> = None;
+        let mut regions = [NONE; INDEX_SIZE];
+        let (mut from, mut slot) = (0, 0);
+        while slot < INDEX_SIZE && from < memory_size {
+            let size = 1 << (usize::BITS - 1 - (memory_size - from).leading_zeros());
+            regions[slot] = Some(MemoryRegion::new(from, size, false));
+            from += size;
+            slot += 1;
+        }
+        let mut index = Self::new(regions);
+        index.sorted = true;
+        index
     }
 
     /// Get the region at the specified index.
     /// Raise an [`IndexError::NoSuchRegion`] if the index is not a region.
-    pub fn get_region(&self, region: usize) -> Result<&MemoryRegion, IndexError> {
-        self.regions[region]
-            .as_ref()
+    pub fn get_region(&self, region: usize) -> Result<MemoryRegion, IndexError> {
+        self.regions
+            .get(region)
+            .and_then(Slot::get)
             .ok_or(IndexError::NoSuchRegion)
     }
 
-    /// Get mutable access the region at the specified index.
+    /// Get mutable access the region at the specified index, the changes being written back once it is dropped.
     /// Raise an [`IndexError::NoSuchRegion`] if the index is not a region.
-    pub fn get_region_mut(&mut self, region: usize) -> Result<&mut MemoryRegion, IndexError> {
-        self.regions[region]
-            .as_mut()
-            .ok_or(IndexError::NoSuchRegion)
+    pub fn get_region_mut(&mut self, region: usize) -> Result<RegionMut<'_>, IndexError> {
+        let slot = self
+            .regions
+            .get_mut(region)
+            .ok_or(IndexError::NoSuchRegion)?;
+        let region = slot.get().ok_or(IndexError::NoSuchRegion)?;
+
+        Ok(RegionMut { slot, region })
+    }
+
+    /// Get a copy of the region at the specified index, which doesn't borrow the index.
+    /// Raise an [`IndexError::NoSuchRegion`] if the index is not a region.
+    pub fn region_at(&self, region: usize) -> Result<MemoryRegion, IndexError> {
+        self.get_region(region)
+    }
+
+    /// Put `region` in a slot, or empty it.
+    fn set_slot(&mut self, slot: usize, region: Option<MemoryRegion>) {
+        if let Some(slot) = self.regions.get_mut(slot) {
+            *slot = Slot::new(region);
+        }
+    }
+
+    /// Count a visit of a slot, see [`MemoryIndex::take_visits`].
+    #[inline(always)]
+    fn visit(&self) {
+        #[cfg(test)]
+        self.visits.set(self.visits.get() + 1);
+    }
+
+    /// Copy the slots and the sorting state of the index, to check it is left unchanged.
+    #[cfg(test)]
+    pub fn snapshot(&self) -> ([Option<MemoryRegion>; INDEX_SIZE], bool) {
+        (self.regions.map(|slot| slot.get()), self.sorted)
+    }
+
+    /// Return the number of slots visited since the last call.
+    #[cfg(test)]
+    pub fn take_visits(&self) -> usize {
+        self.visits.take()
+    }
+
+    /// Iterate over the regions of the index.
+    pub fn regions(&self) -> impl Iterator<Item = MemoryRegion> + '_ {
+        self.regions.iter().filter_map(Slot::get)
+    }
+
+    /// Count the regions of the index, the slots holding one.
+    #[cfg(any(test, feature = "index-fixtures"))]
+    pub fn len(&self) -> usize {
+        self.regions().count()
+    }
+
+    /// Test if the index holds no region, which never happens for an index covering a memory pool.
+    #[cfg(any(test, feature = "index-fixtures"))]
+    pub fn is_empty(&self) -> bool {
+        self.regions.iter().all(Slot::is_none)
+    }
+
+    /// Compute the total size of the free regions.
+    pub fn free_bytes(&self) -> usize {
+        self.regions()
+            .filter(|region| !region.used)
+            .map(|region| region.size)
+            .sum()
+    }
+
+    /// Compute the size of the largest free region.
+    pub fn largest_free_block(&self) -> usize {
+        self.regions()
+            .filter(|region| !region.used)
+            .map(|region| region.size)
+            .max()
+            .unwrap_or(0)
     }
 
     /// Get an index corresponding to an empty index.
     /// Raise an [`IndexError::NoIndexAvailable`] if the index is full.
+    ///
+    /// The lowest empty index is always chosen, so the layout of the index only depends on the regions it holds,
+    /// not on the order they were freed in.
     pub fn available_index(&self) -> Result<usize, IndexError> {
         self.regions
             .iter()
             .enumerate()
             .find_map(|(i, maybe_region)| {
+                self.visit();
                 if maybe_region.is_none() {
                     Some(i)
                 } else {
@@ -107,7 +422,8 @@ impl<const INDEX_SIZE: usize> MemoryIndex<INDEX_SIZE> {
         self.regions
             .iter()
             .enumerate()
-            .find_map(|(i, maybe_region)| match maybe_region {
+            .inspect(|_| self.visit())
+            .find_map(|(i, slot)| match slot.get() {
                 Some(region) if region.contains(addr) => Some(i),
                 _ => None,
             })
@@ -116,6 +432,9 @@ impl<const INDEX_SIZE: usize> MemoryIndex<INDEX_SIZE> {
 
     /// Look for a memory region ready to store data corresponding to a certain [Layout].
     /// Raise an [`Index::NoFittingRegion`] if no region satisfy the [Layout] needs.
+    ///
+    /// The policy is first-fit: the first free region (in index order) able to hold the aligned [Layout] is chosen,
+    /// even if a later region would need a smaller alignment offset.
     pub fn size_region_available(
         &self,
         memory_start: usize,
@@ -124,117 +443,468 @@ impl<const INDEX_SIZE: usize> MemoryIndex<INDEX_SIZE> {
         self.regions
             .iter()
             .enumerate()
-            .find_map(|(i, maybe_region)| match maybe_region {
+            .inspect(|_| self.visit())
+            .find_map(|(i, slot)| match slot.get() {
                 Some(region) if !region.used => {
-                    let offset = (memory_start + region.from).next_multiple_of(layout.align())
-                        - memory_start
-                        - region.from;
-                    if region.from + offset + layout.size() <= region.end() {
-                        Some(AllocationBaker { region: i, offset })
-                    } else {
-                        None
-                    }
+                    // The alignment is a power of two, so the aligned address can be computed with a mask.
+                    // With an alignment of 1 the mask is 0 and the offset is always 0, whatever the address.
+                    // A `Layout` can't have an alignment of 0, not even an unchecked one, so the mask never underflows.
+                    let start = memory_start.wrapping_add(region.from);
+                    let mask = layout.align() - 1;
+                    let offset = (start.checked_add(mask)? & !mask) - start;
+                    let end = region
+                        .from
+                        .checked_add(offset)?
+                        .checked_add(layout.size())?;
+                    (end <= region.end()).then_some(AllocationBaker { region: i, offset })
+                }
+                _ => None,
+            })
+            .ok_or(IndexError::NoFittingRegion)
+    }
+
+    /// Look for the free region with the highest address able to hold the [Layout] at its end.
+    /// Raise an [`IndexError::NoFittingRegion`] if no region satisfy the [Layout] needs.
+    ///
+    /// The offset is the one of the highest aligned address leaving room for the [Layout] before the end of the region,
+    /// so that the tail of the region from that offset holds the [Layout].
+    pub fn size_region_available_high(
+        &self,
+        memory_start: usize,
+        layout: Layout,
+    ) -> Result<AllocationBaker, IndexError> {
+        self.regions
+            .iter()
+            .enumerate()
+            .inspect(|_| self.visit())
+            .filter_map(|(i, slot)| match slot.get() {
+                Some(region) if !region.used => {
+                    // The aligned address is rounded down from the last address leaving room for the layout.
+                    let start = memory_start.wrapping_add(region.from);
+                    let last = start.checked_add(region.size)?.checked_sub(layout.size())?;
+                    let aligned = last & !(layout.align() - 1);
+                    let offset = aligned.checked_sub(start)?;
+                    Some((region.from, AllocationBaker { region: i, offset }))
                 }
                 _ => None,
             })
+            .max_by_key(|(from, _)| *from)
+            .map(|(_, allocation_baker)| allocation_baker)
             .ok_or(IndexError::NoFittingRegion)
     }
 
     /// Split a region in two based on size to prepare for allocation.
     /// Return a couple of region index corresponding to the left and right parts of the cut.
     /// The left region is set to have the desired size.
+    ///
+    /// In a sorted index, the right region is inserted just after the left one, shifting the following regions.
+    /// Otherwise, it takes the lowest empty slot.
     pub fn split_region(
         &mut self,
         region: usize,
         size: usize,
     ) -> Result<(usize, usize), IndexError> {
-        if self.get_region(region)?.size < size {
+        let left_region = self.get_region(region)?;
+        if left_region.size < size {
             return Err(IndexError::RegionTooThin);
         }
 
-        let right_index = self.available_index()?;
-        let left_region = self.get_region_mut(region)?;
+        let mut right_region = MemoryRegion::new(
+            left_region.from + size,
+            left_region.size - size,
+            left_region.used,
+        );
+        right_region.pool = left_region.pool;
+        // Finding an available index is the only step which can fail, so it comes before any mutation.
+        let mut right_index = self.available_index()?;
 
-        let left_size = size;
-        let right_size = left_region.size - size;
+        if self.sorted {
+            // The empty slots follow the regions, so shifting the following regions moves the first one just after the left region.
+            if let Some(shifted) = self.regions.get_mut(region + 1..=right_index) {
+                let mut moved = Slot::EMPTY;
+                for slot in shifted.iter_mut() {
+                    moved = mem::replace(slot, moved);
+                }
+                for _ in 0..shifted.len() {
+                    self.visit();
+                }
+                right_index = region + 1;
+            }
+        } else if right_index != region + 1 {
+            // The right region only keeps the index sorted if it directly follows the left one.
+            self.sorted = false;
+        }
 
-        left_region.size = left_size;
-        self.regions[right_index] = Some(MemoryRegion::new(
-            left_region.end(),
-            right_size,
-            left_region.used,
-        ));
+        self.set_slot(right_index, Some(right_region));
+        self.get_region_mut(region)?.size = size;
 
         Ok((region, right_index))
     }
 
+    /// Insert a new region, such as a free region covering a buffer attached to the allocator.
+    /// Raise an [`IndexError::NoIndexAvailable`] if the index is full, leaving the index unchanged.
+    ///
+    /// The index is sorted again afterwards.
+    pub fn insert_region(&mut self, region: MemoryRegion) -> Result<(), IndexError> {
+        let slot = self.available_index()?;
+        self.set_slot(slot, Some(region));
+        self.sorted = false;
+        self.sort();
+
+        Ok(())
+    }
+
+    /// Look for the smallest free block able to hold the [Layout] once cut down to its size class,
+    /// the power of two the [Layout] is rounded up to, and split it in halves down to that size.
+    /// Return the region reserved and the offset needed for the pointer to be correctly aligned in it.
+    /// Raise an [`IndexError::NoFittingRegion`] if no block fits, or an [`IndexError::NoIndexAvailable`]
+    /// if the index can't hold the halves, in which case the index is left unchanged.
+    ///
+    /// A block is a free region whose size is a power of two, aligned on its size from the start of the memory pool.
+    /// If the lowest block of the size class isn't aligned enough for the [Layout], the size class is doubled.
+    #[cfg(feature = "buddy")]
+    pub fn buddy_reserve(
+        &mut self,
+        memory_start: usize,
+        layout: Layout,
+    ) -> Result<AllocationBaker, IndexError> {
+        self.sort();
+        let mut class = layout
+            .size()
+            .max(1)
+            .checked_next_power_of_two()
+            .ok_or(IndexError::NoFittingRegion)?;
+
+        let (region, offset) = loop {
+            let fitting = self
+                .regions
+                .iter()
+                .enumerate()
+                .inspect(|_| self.visit())
+                .filter_map(|(i, slot)| match slot.get() {
+                    Some(region)
+                        if !region.used
+                            && region.size >= class
+                            && region.size.is_power_of_two()
+                            && region.from.is_multiple_of(region.size) =>
+                    {
+                        let start = memory_start.wrapping_add(region.from);
+                        let mask = layout.align() - 1;
+                        let offset = (start.checked_add(mask)? & !mask) - start;
+                        let end = offset.checked_add(layout.size())?;
+                        (end <= class).then_some((i, region.size, offset))
+                    }
+                    _ => None,
+                })
+                .min_by_key(|(_, size, _)| *size);
+            if let Some((region, size, offset)) = fitting {
+                // Each split takes an empty slot, they are all checked to be available before splitting.
+                let splits = (size / class).trailing_zeros() as usize;
+                if self.regions.iter().filter(|slot| slot.is_none()).count() < splits {
+                    return Err(IndexError::NoIndexAvailable);
+                }
+                break (region, offset);
+            }
+
+            class = class
+                .checked_mul(2)
+                .filter(|class| *class <= self.largest_free_block())
+                .ok_or(IndexError::NoFittingRegion)?;
+        };
+
+        // The lower half is kept at each split, so the region keeps its slot.
+        while self.get_region(region)?.size > class {
+            let half = self.get_region(region)?.size / 2;
+            self.split_region(region, half)?;
+        }
+
+        Ok(AllocationBaker { region, offset })
+    }
+
+    /// Free a block reserved with [`MemoryIndex::buddy_reserve`], merging it with its buddy as long as it's free:
+    /// the block of the same size it was split from, whose offset only differs by the size bit.
+    #[cfg(feature = "buddy")]
+    pub fn buddy_free(&mut self, region: usize) -> Result<(), IndexError> {
+        let from = {
+            let mut block = self.get_region_mut(region)?;
+            block.free();
+            block.from
+        };
+        let mut region = region;
+        if !self.sorted {
+            self.sort();
+            region = self.find_region(from)?;
+        }
+
+        loop {
+            let block = self.region_at(region)?;
+            let buddy_from = block.from ^ block.size;
+            // In a sorted index, the buddy is in the slot just before or after the block.
+            let buddy = [region.checked_sub(1), region.checked_add(1)]
+                .into_iter()
+                .flatten()
+                .find(|slot| {
+                    self.visit();
+                    self.get_region(*slot).is_ok_and(|other| {
+                        !other.used
+                            && other.from == buddy_from
+                            && other.size == block.size
+                            && other.pool == block.pool
+                    })
+                });
+            let Some(buddy) = buddy else {
+                return Ok(());
+            };
+
+            let (lower, upper) = (region.min(buddy), region.max(buddy));
+            self.get_region_mut(lower)?.size *= 2;
+            if let Some(following) = self.regions.get_mut(upper..) {
+                Self::shift_left(following, 1);
+            }
+            region = lower;
+        }
+    }
+
+    /// Shrink a used region to `size`, giving its tail back as free memory.
+    /// The tail is merged in the following region if it's free, otherwise it needs an available index.
+    /// On failure, the index is left unchanged.
+    pub fn shrink_region(&mut self, region: usize, size: usize) -> Result<(), IndexError> {
+        let current = self.get_region(region)?;
+        if current.size < size {
+            return Err(IndexError::RegionTooThin);
+        }
+
+        let (tail_from, end, pool) = (current.from + size, current.end(), current.pool);
+        if tail_from == end {
+            return Ok(());
+        }
+
+        let next = if self.sorted {
+            self.visit();
+            Some(region + 1)
+        } else {
+            self.regions
+                .iter()
+                .position(|slot| slot.get().is_some_and(|next| next.from == end))
+        };
+        let next = next.filter(|next| {
+            self.get_region(*next)
+                .is_ok_and(|next| !next.used && next.pool == pool && next.from == end)
+        });
+        if let Some(next) = next {
+            {
+                let mut next = self.get_region_mut(next)?;
+                next.from = tail_from;
+                next.size += end - tail_from;
+            }
+            self.get_region_mut(region)?.size = size;
+        } else {
+            let (_, tail) = self.split_region(region, size)?;
+            self.get_region_mut(tail)?.free();
+        }
+
+        Ok(())
+    }
+
+    /// Grow a used region to `size`, taking the missing bytes from the start of the following region,
+    /// which must be free and in the same pool. A following region taken whole leaves the index.
+    /// On failure, the index is left unchanged.
+    pub fn grow_region(&mut self, region: usize, size: usize) -> Result<(), IndexError> {
+        let current = self.get_region(region)?;
+        let Some(extra) = size.checked_sub(current.size) else {
+            return Err(IndexError::RegionTooThin);
+        };
+        if extra == 0 {
+            return Ok(());
+        }
+
+        let end = current.end();
+        let next = if self.sorted {
+            self.visit();
+            Some(region + 1)
+        } else {
+            self.regions
+                .iter()
+                .position(|slot| slot.get().is_some_and(|next| next.from == end))
+        };
+        let (next, following) = next
+            .and_then(|next| Some((next, self.get_region(next).ok()?)))
+            .filter(|(_, following)| {
+                !following.used && following.pool == current.pool && following.from == end
+            })
+            .ok_or(IndexError::NoFittingRegion)?;
+        if following.size < extra {
+            return Err(IndexError::NoFittingRegion);
+        }
+
+        if following.size == extra {
+            if let Some(following) = self.regions.get_mut(next..) {
+                Self::shift_left(following, 1);
+                for _ in 0..following.len() {
+                    self.visit();
+                }
+            }
+        } else {
+            let mut following = self.get_region_mut(next)?;
+            following.from += extra;
+            following.size -= extra;
+        }
+        self.get_region_mut(region)?.size = size;
+
+        Ok(())
+    }
+
+    /// Split a used region in two used regions, the left one keeping the first `size` bytes,
+    /// so that both parts can be freed on their own. Return the index of the right region.
+    ///
+    /// The right region copies the metadata of the left one, such as its call site.
+    /// The caller is responsible for `size` to fall on a boundary between two values.
+    /// On failure, the index is left unchanged.
+    pub fn split_used_region(&mut self, region: usize, size: usize) -> Result<usize, IndexError> {
+        let left = self.get_region(region)?;
+        if !left.used {
+            return Err(IndexError::DoubleFree);
+        }
+        if size == 0 || size >= left.size {
+            return Err(IndexError::RegionTooThin);
+        }
+
+        let (_, right) = self.split_region(region, size)?;
+        *self.get_region_mut(right)? = MemoryRegion {
+            from: left.from + size,
+            size: left.size - size,
+            ..left
+        };
+        Ok(right)
+    }
+
+    /// Free a region and merge it with the free regions around it.
+    ///
+    /// In a sorted index, only the slots just before and after the region are merged, the index being already merged.
+    /// Otherwise, the whole index is sorted and merged.
+    pub fn free_region(&mut self, region: usize) -> Result<(), IndexError> {
+        self.get_region_mut(region)?.free();
+        if !self.sorted {
+            self.sort_merge();
+            return Ok(());
+        }
+
+        // Regions of different pools are never merged, even if they happen to be adjacent.
+        let pool = self.get_region(region)?.pool;
+        let is_free = |index: &Self, slot: usize| {
+            index.visit();
+            matches!(index.regions.get(slot).and_then(Slot::get), Some(region) if !region.used && region.pool == pool)
+        };
+        let first = match region.checked_sub(1) {
+            Some(prev) if is_free(self, prev) => prev,
+            _ => region,
+        };
+        let last = if is_free(self, region + 1) {
+            region + 1
+        } else {
+            region
+        };
+        if first == last {
+            return Ok(());
+        }
+
+        let size = (first..=last)
+            .filter_map(|slot| self.get_region(slot).ok())
+            .map(|region| region.size)
+            .sum();
+        self.get_region_mut(first)?.size = size;
+
+        // Move the merged slots at the end of the index and empty them.
+        let merged = last - first;
+        if let Some(following) = self.regions.get_mut(first + 1..) {
+            Self::shift_left(following, merged);
+            for _ in 0..following.len() {
+                self.visit();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the regions are known to be sorted, in which case [`MemoryIndex::sort`] does nothing.
+    pub fn is_sorted(&self) -> bool {
+        self.sorted
+    }
+
+    /// Sort region index in ascending order, unless it is already known to be sorted.
+    pub fn sort(&mut self) {
+        if self.is_sorted() {
+            return;
+        }
+
+        // The index is almost always sorted already, which makes an insertion sort the fitting choice.
+        // Unlike the core sorts, it also can't panic.
+        for i in 1..INDEX_SIZE {
+            let mut j = i;
+            while j > 0 && Self::goes_before(&self.regions[j], &self.regions[j - 1]) {
+                self.regions.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+
+        self.sorted = true;
+    }
+
     /// Sort region index in ascending order and then merge continuous, non-allocated regions.
     pub fn sort_merge(&mut self) {
-        self.regions
-            .sort_unstable_by(|region1, region2| match (region1, region2) {
-                (Some(r1), Some(r2)) => r1.from.cmp(&r2.from),
-                (None, Some(_)) => Ordering::Greater,
-                (Some(_), None) => Ordering::Less,
-                (None, None) => Ordering::Equal,
-            });
+        self.sort();
+        self.merge_only();
+    }
 
+    /// Merge continuous, non-allocated regions, without sorting the index first.
+    /// The index must already be sorted, see [`MemoryIndex::sort`].
+    pub fn merge_only(&mut self) {
         // The merging process look for non-allocated continuous ranges and group them in single [MemoryRegion].
 
-        // [new_counter] and [counter] are like to pointers to elements of the region index.
-        // [new_counter] overwrite the index whereas [counter] reads it.
-        // The new position of the region.
-        let mut new_counter = 0;
-        // The current region being processed.
-        let mut counter = 0;
-
-        // Loop through the index while it represents regions.
-        'merge_loop: while let Some(region) = &self.regions[counter] {
-            if region.used {
-                // If the region is used, let in place.
-                self.regions[new_counter] = Some(region.clone());
-                new_counter += 1;
-                counter += 1;
-            } else {
-                // Keep in track where the new merged region start and it's new size.
-                let from = region.from;
-                let mut size = 0;
-
-                // Walkthrough the rest of the index until:
-                // - It's the end of the index (or it's full), in that case, stop the whole process.
-                // - The next region is used, in that case merge the current regions and continue to the next one.
-                for i in counter..INDEX_SIZE {
-                    if let Some(r) = &self.regions[i] {
-                        if r.used {
-                            // If it's use, merge everything and go on.
-                            self.regions[new_counter] = Some(MemoryRegion::new(from, size, false));
-                            new_counter += 1;
-                            counter = i;
-                            break;
-                        } else {
-                            // If it's not, add the size of the current region to the size counter.
-                            size += r.size;
-
-                            if i + 1 == INDEX_SIZE {
-                                // If the index is full stop the whole process.
-                                self.regions[new_counter] =
-                                    Some(MemoryRegion::new(from, size, false));
-                                break 'merge_loop;
-                            }
-                        }
-                    } else {
-                        // If it's not a region, stop the merging process.
-                        self.regions[new_counter] = Some(MemoryRegion::new(from, size, false));
-                        new_counter += 1;
-                        counter = i;
-                        break;
-                    }
+        // [write] and [read] are like to pointers to elements of the region index.
+        // [write] overwrite the index whereas [read] reads it.
+        // As [write] never gets ahead of [read], every region not written back is erased.
+        let mut write: usize = 0;
+        for read in 0..INDEX_SIZE {
+            // Regions are sorted, so the first empty slot ends the index.
+            let Some(region) = mem::replace(&mut self.regions[read], Slot::EMPTY).get() else {
+                break;
+            };
+
+            let last = write.checked_sub(1);
+            match last.and_then(|last| self.get_region(last).ok()) {
+                // If both the last region written and the current one are free, in the same pool, merge them.
+                Some(mut merged) if !merged.used && !region.used && merged.pool == region.pool => {
+                    merged.size += region.size;
+                    self.set_slot(write - 1, Some(merged));
+                }
+                // Otherwise, let the region in place.
+                _ => {
+                    self.set_slot(write, Some(region));
+                    write += 1;
                 }
             }
         }
+    }
 
-        // After all the part that could merged was, earase the remaining free indexes.
-        for i in new_counter..INDEX_SIZE {
-            self.regions[i] = None;
+    /// Move the slots `count` places to the left, dropping the first ones and emptying the last ones.
+    ///
+    /// Unlike [`slice::rotate_left`], this has no bound to check, so it can't panic.
+    fn shift_left(slots: &mut [Slot], count: usize) {
+        for i in 0..slots.len() {
+            let next = slots.get(i + count).copied().unwrap_or(Slot::EMPTY);
+            if let Some(slot) = slots.get_mut(i) {
+                *slot = next;
+            }
+        }
+    }
+
+    /// Compare two slots of the index, regions going in ascending order before empty slots.
+    fn goes_before(slot: &Slot, other: &Slot) -> bool {
+        match (slot.get(), other.get()) {
+            (Some(r1), Some(r2)) => r1.from < r2.from,
+            (Some(_), None) => true,
+            (None, _) => false,
         }
     }
 }
@@ -249,11 +919,44 @@ mod tests {
     ) -> MemoryIndex<INDEX_SIZE> {
         let mut index = MemoryIndex::empty(size);
         for (i, region) in from.iter().enumerate() {
-            index.regions[i] = region.clone();
+            index.set_slot(i, *region);
         }
+        index.sorted = false;
         index
     }
 
+    #[test]
+    fn test_region_end_saturates() {
+        let region = MemoryRegion::new(usize::MAX - 8, 16, true);
+
+        assert_eq!(region.end(), usize::MAX);
+        assert!(region.contains(usize::MAX - 1));
+        assert!(!region.contains(usize::MAX - 9));
+    }
+
+    #[test]
+    fn test_region_at() {
+        let mut index: MemoryIndex<4> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 16, true)),
+                None,
+                Some(MemoryRegion::new(16, 48, false)),
+            ],
+        );
+
+        for slot in 0..4 {
+            assert_eq!(index.region_at(slot), index.get_region(slot));
+        }
+        assert_eq!(index.region_at(1), Err(IndexError::NoSuchRegion));
+        assert_eq!(index.region_at(4), Err(IndexError::NoSuchRegion));
+
+        // The copy is left untouched by later changes to the index.
+        let region = index.region_at(0).unwrap();
+        index.free_region(0).unwrap();
+        assert!(region.used);
+    }
+
     #[test]
     fn test_available_index() {
         let index: MemoryIndex<8> = create_index(
@@ -281,6 +984,51 @@ mod tests {
         assert_eq!(index.available_index(), Err(IndexError::NoIndexAvailable));
     }
 
+    #[test]
+    fn test_index_len() {
+        let index: MemoryIndex<8> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 16, false)),
+                None,
+                Some(MemoryRegion::new(16, 16, true)),
+                None,
+                Some(MemoryRegion::new(32, 32, false)),
+            ],
+        );
+        assert_eq!(index.len(), 3);
+        assert!(!index.is_empty());
+
+        let index: MemoryIndex<4> = MemoryIndex::empty(64);
+        assert_eq!(index.len(), 1);
+        assert!(MemoryIndex::<4>::new([const { None }; 4]).is_empty());
+    }
+
+    #[test]
+    fn test_split_region_lowest_slot() {
+        let holes: [&[usize]; 4] = [&[1, 2, 3], &[3, 1], &[2, 3], &[3]];
+
+        for holes in holes {
+            let mut regions = [
+                Some(MemoryRegion::new(0, 32, false)),
+                Some(MemoryRegion::new(32, 8, true)),
+                Some(MemoryRegion::new(40, 8, true)),
+                Some(MemoryRegion::new(48, 16, false)),
+            ];
+            for hole in holes {
+                regions[*hole] = None;
+            }
+            let mut index: MemoryIndex<4> = create_index(64, &regions);
+
+            let lowest = regions.iter().position(Option::is_none).unwrap();
+            assert_eq!(index.split_region(0, 8), Ok((0, lowest)));
+            assert_eq!(
+                index.get_region(lowest),
+                Ok(MemoryRegion::new(8, 24, false))
+            );
+        }
+    }
+
     #[test]
     fn test_index_size_region_available() {
         let index: MemoryIndex<8> = create_index(
@@ -315,6 +1063,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_index_size_region_available_first_fit() {
+        let index: MemoryIndex<8> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 4, true)),
+                Some(MemoryRegion::new(4, 16, false)),
+                Some(MemoryRegion::new(20, 12, true)),
+                Some(MemoryRegion::new(32, 32, false)),
+            ],
+        );
+
+        // The second region needs a 4 bytes offset but still fits, so it's chosen over the aligned last one.
+        assert_eq!(
+            index.size_region_available(0, Layout::from_size_align(8, 8).unwrap()),
+            Ok(AllocationBaker {
+                region: 1,
+                offset: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_index_size_region_available_overflow() {
+        let index: MemoryIndex<8> = MemoryIndex::empty(64);
+
+        // Aligning a region at the very end of the address space would overflow.
+        assert_eq!(
+            index.size_region_available(usize::MAX - 8, Layout::from_size_align(1, 16).unwrap()),
+            Err(IndexError::NoFittingRegion)
+        );
+    }
+
+    #[test]
+    fn test_index_size_region_available_degenerate_layouts() {
+        let index: MemoryIndex<8> = MemoryIndex::empty(64);
+
+        assert_eq!(
+            index.size_region_available(8, Layout::from_size_align(0, 1).unwrap()),
+            Ok(AllocationBaker {
+                region: 0,
+                offset: 0
+            })
+        );
+        // The largest alignment of a value pushes it far past the end of the region, without overflowing.
+        let largest_align = 1 << (usize::BITS - 2);
+        assert_eq!(
+            index.size_region_available(8, Layout::from_size_align(0, largest_align).unwrap()),
+            Err(IndexError::NoFittingRegion)
+        );
+        assert_eq!(
+            index.size_region_available(8, Layout::from_size_align(1, largest_align).unwrap()),
+            Err(IndexError::NoFittingRegion)
+        );
+    }
+
+    #[test]
+    fn test_index_size_region_available_high() {
+        let index: MemoryIndex<8> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 16, false)),
+                Some(MemoryRegion::new(16, 16, true)),
+                Some(MemoryRegion::new(32, 20, false)),
+                Some(MemoryRegion::new(52, 12, true)),
+            ],
+        );
+
+        // The highest region fits, its last aligned address leaving room for the layout is chosen.
+        assert_eq!(
+            index.size_region_available_high(0, Layout::from_size_align(8, 8).unwrap()),
+            Ok(AllocationBaker {
+                region: 2,
+                offset: 8
+            })
+        );
+        // The highest region has no aligned address leaving room for the layout, the lower one is chosen.
+        assert_eq!(
+            index.size_region_available_high(0, Layout::from_size_align(16, 64).unwrap()),
+            Ok(AllocationBaker {
+                region: 0,
+                offset: 0
+            })
+        );
+        assert_eq!(
+            index.size_region_available_high(0, Layout::from_size_align(24, 1).unwrap()),
+            Err(IndexError::NoFittingRegion)
+        );
+    }
+
     #[test]
     fn test_split_region() {
         let mut index: MemoryIndex<8> = create_index(
@@ -330,17 +1168,102 @@ mod tests {
         assert_eq!(index.split_region(2, 8), Ok((2, 4)));
 
         assert_eq!(
-            *index.get_region(2).unwrap(),
+            index.get_region(2).unwrap(),
             MemoryRegion::new(40, 8, false)
         );
         assert_eq!(
-            *index.get_region(4).unwrap(),
+            index.get_region(4).unwrap(),
             MemoryRegion::new(48, 8, false)
         );
 
         assert_eq!(index.split_region(0, 16), Err(IndexError::RegionTooThin));
     }
 
+    #[test]
+    fn test_index_shrink_region() {
+        let mut index: MemoryIndex<4> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 16, true)),
+                Some(MemoryRegion::new(16, 16, false)),
+                Some(MemoryRegion::new(32, 32, true)),
+            ],
+        );
+
+        // The tail of the first region is merged in the following free region.
+        index.shrink_region(0, 4).unwrap();
+        assert_eq!(index.get_region(0), Ok(MemoryRegion::new(0, 4, true)));
+        assert_eq!(index.get_region(1), Ok(MemoryRegion::new(4, 28, false)));
+
+        // The last region has no free neighbour, so its tail takes a new index.
+        index.shrink_region(2, 8).unwrap();
+        assert_eq!(index.get_region(2), Ok(MemoryRegion::new(32, 8, true)));
+        assert_eq!(index.get_region(3), Ok(MemoryRegion::new(40, 24, false)));
+
+        assert_eq!(index.shrink_region(0, 8), Err(IndexError::RegionTooThin));
+
+        // Without any index left, the region can't be shrunk and is left unchanged.
+        let mut full_index: MemoryIndex<2> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 32, true)),
+                Some(MemoryRegion::new(32, 32, true)),
+            ],
+        );
+        assert_eq!(
+            full_index.shrink_region(0, 8),
+            Err(IndexError::NoIndexAvailable)
+        );
+        assert_eq!(full_index.get_region(0), Ok(MemoryRegion::new(0, 32, true)));
+    }
+
+    #[test]
+    fn test_index_grow_region() {
+        let mut index: MemoryIndex<4> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 16, true)),
+                Some(MemoryRegion::new(16, 16, false)),
+                Some(MemoryRegion::new(32, 32, true)),
+            ],
+        );
+
+        // The first region takes the start of the following free region.
+        index.grow_region(0, 24).unwrap();
+        assert_eq!(index.get_region(0), Ok(MemoryRegion::new(0, 24, true)));
+        assert_eq!(index.get_region(1), Ok(MemoryRegion::new(24, 8, false)));
+
+        // Taken whole, the free region leaves the index.
+        index.grow_region(0, 32).unwrap();
+        assert_eq!(index.get_region(0), Ok(MemoryRegion::new(0, 32, true)));
+        assert_eq!(index.get_region(1), Ok(MemoryRegion::new(32, 32, true)));
+        assert_eq!(index.len(), 2);
+
+        // Without a free region after it, the region is left unchanged.
+        assert_eq!(index.grow_region(0, 40), Err(IndexError::NoFittingRegion));
+        assert_eq!(index.grow_region(1, 40), Err(IndexError::NoFittingRegion));
+        assert_eq!(index.grow_region(0, 8), Err(IndexError::RegionTooThin));
+        assert_eq!(index.get_region(0), Ok(MemoryRegion::new(0, 32, true)));
+
+        // A free region too small to grow into is left unchanged as well.
+        let mut small_index: MemoryIndex<4> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 32, true)),
+                Some(MemoryRegion::new(32, 8, false)),
+                Some(MemoryRegion::new(40, 24, true)),
+            ],
+        );
+        assert_eq!(
+            small_index.grow_region(0, 48),
+            Err(IndexError::NoFittingRegion)
+        );
+        assert_eq!(
+            small_index.get_region(1),
+            Ok(MemoryRegion::new(32, 8, false))
+        );
+    }
+
     #[test]
     fn test_index_sort() {
         let index_blueprint = [
@@ -355,22 +1278,10 @@ mod tests {
 
         index.sort_merge();
 
-        assert_eq!(
-            index.get_region(0).unwrap(),
-            index_blueprint[0].as_ref().unwrap()
-        );
-        assert_eq!(
-            index.get_region(1).unwrap(),
-            index_blueprint[5].as_ref().unwrap()
-        );
-        assert_eq!(
-            index.get_region(2).unwrap(),
-            index_blueprint[2].as_ref().unwrap()
-        );
-        assert_eq!(
-            index.get_region(3).unwrap(),
-            index_blueprint[3].as_ref().unwrap()
-        );
+        assert_eq!(index.get_region(0).unwrap(), index_blueprint[0].unwrap());
+        assert_eq!(index.get_region(1).unwrap(), index_blueprint[5].unwrap());
+        assert_eq!(index.get_region(2).unwrap(), index_blueprint[2].unwrap());
+        assert_eq!(index.get_region(3).unwrap(), index_blueprint[3].unwrap());
     }
 
     #[test]
@@ -388,16 +1299,215 @@ mod tests {
         index.sort_merge();
 
         assert_eq!(
-            *index.get_region(0).unwrap(),
+            index.get_region(0).unwrap(),
             MemoryRegion::new(0, 32, false)
         );
+        assert_eq!(index.get_region(1).unwrap(), index_blueprint[2].unwrap());
+        assert_eq!(index.get_region(2).unwrap(), index_blueprint[3].unwrap());
+    }
+
+    /// Check that merging pseudo random indices keeps every used region as it was, over many shuffled layouts.
+    #[test]
+    fn test_index_merge_preserves_used_regions() {
+        const INDEX_SIZE: usize = 16;
+        const MEMORY_SIZE: usize = 256;
+        let mut seed: u32 = 0x9E37_79B9;
+        let mut next = |bound: usize| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as usize % bound
+        };
+
+        for _ in 0..1000 {
+            // Cut the memory in regions, the ones after `pool_split` being in another pool,
+            // which the compact index can't hold.
+            let pool_split = next(MEMORY_SIZE);
+            let pool_split = if cfg!(feature = "compact-index") {
+                MEMORY_SIZE
+            } else {
+                pool_split
+            };
+            let mut slots = [None; INDEX_SIZE];
+            let (mut from, mut count) = (0, 0);
+            while from < MEMORY_SIZE && count < INDEX_SIZE {
+                let size = if count == INDEX_SIZE - 1 {
+                    MEMORY_SIZE - from
+                } else {
+                    (1 + next(48)).min(MEMORY_SIZE - from)
+                };
+                let mut region = MemoryRegion::new(from, size, next(2) == 0);
+                region.pool = usize::from(from >= pool_split);
+                slots[count] = Some(region);
+                from += size;
+                count += 1;
+            }
+            // Shuffle the regions among the slots, leaving holes.
+            for i in (1..INDEX_SIZE).rev() {
+                slots.swap(i, next(i + 1));
+            }
+
+            let mut index: MemoryIndex<INDEX_SIZE> = create_index(MEMORY_SIZE, &slots);
+            let used_before: std::vec::Vec<MemoryRegion> = {
+                let mut used: std::vec::Vec<_> =
+                    index.regions().filter(|region| region.used).collect();
+                used.sort_by_key(|region| region.from);
+                used
+            };
+            index.sort_merge();
+
+            let regions: std::vec::Vec<MemoryRegion> = index.regions().collect();
+            let used_after: std::vec::Vec<MemoryRegion> = regions
+                .iter()
+                .copied()
+                .filter(|region| region.used)
+                .collect();
+            assert_eq!(used_after, used_before);
+            assert_eq!(
+                used_after.iter().map(|region| region.size).sum::<usize>(),
+                used_before.iter().map(|region| region.size).sum::<usize>()
+            );
+
+            // The regions still cover the memory pool, and no two free regions of a pool are left side by side.
+            let mut end = 0;
+            for region in &regions {
+                assert_eq!(region.from, end, "{regions:?}");
+                end = region.end();
+            }
+            assert_eq!(end, from);
+            for pair in regions.windows(2) {
+                assert!(
+                    pair[0].used || pair[1].used || pair[0].pool != pair[1].pool,
+                    "{regions:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_merge_full() {
+        let mut index: MemoryIndex<4> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 16, false)),
+                Some(MemoryRegion::new(16, 16, true)),
+                Some(MemoryRegion::new(32, 16, false)),
+                Some(MemoryRegion::new(48, 16, false)),
+            ],
+        );
+
+        index.sort_merge();
+
+        assert_eq!(
+            index.get_region(2).unwrap(),
+            MemoryRegion::new(32, 32, false)
+        );
+        assert_eq!(index.get_region(3), Err(IndexError::NoSuchRegion));
+
+        let mut index: MemoryIndex<4> = create_index(
+            64,
+            &[
+                Some(MemoryRegion::new(0, 16, false)),
+                Some(MemoryRegion::new(16, 16, false)),
+                Some(MemoryRegion::new(32, 16, false)),
+                Some(MemoryRegion::new(48, 16, true)),
+            ],
+        );
+
+        index.sort_merge();
+
+        assert_eq!(
+            index.get_region(0).unwrap(),
+            MemoryRegion::new(0, 48, false)
+        );
         assert_eq!(
             index.get_region(1).unwrap(),
-            index_blueprint[2].as_ref().unwrap()
+            MemoryRegion::new(48, 16, true)
         );
+        assert_eq!(index.get_region(2), Err(IndexError::NoSuchRegion));
+    }
+
+    #[test]
+    fn test_index_merge_only() {
+        let index_blueprint = [
+            Some(MemoryRegion::new(0, 16, false)),
+            Some(MemoryRegion::new(16, 16, false)),
+            Some(MemoryRegion::new(32, 16, true)),
+            Some(MemoryRegion::new(48, 8, false)),
+            Some(MemoryRegion::new(56, 8, false)),
+        ];
+        let mut merged_index: MemoryIndex<8> = create_index(64, &index_blueprint);
+        merged_index.sort();
+        merged_index.merge_only();
+
+        let mut sort_merged_index: MemoryIndex<8> = create_index(64, &index_blueprint);
+        sort_merged_index.sort_merge();
+
+        assert_eq!(merged_index.snapshot().0, sort_merged_index.snapshot().0);
         assert_eq!(
-            index.get_region(2).unwrap(),
-            index_blueprint[3].as_ref().unwrap()
+            merged_index.get_region(2).unwrap(),
+            MemoryRegion::new(48, 16, false)
+        );
+    }
+
+    #[test]
+    fn test_index_sort_skipped_when_clean() {
+        let mut index: MemoryIndex<8> = MemoryIndex::empty(64);
+        assert!(index.is_sorted());
+
+        // Splitting the last region keeps the index sorted.
+        index.split_region(0, 16).unwrap();
+        index.split_region(1, 16).unwrap();
+        assert!(index.is_sorted());
+
+        // Splitting a region before the last one shifts the following regions.
+        index.split_region(0, 8).unwrap();
+        assert!(index.is_sorted());
+        assert_eq!(index.get_region(1).unwrap(), MemoryRegion::new(8, 8, false));
+        assert_eq!(
+            index.get_region(3).unwrap(),
+            MemoryRegion::new(32, 32, false)
+        );
+
+        // A clean index isn't sorted again.
+        index.regions.swap(0, 1);
+        index.sort();
+        assert_eq!(index.get_region(0).unwrap(), MemoryRegion::new(8, 8, false));
+    }
+
+    #[test]
+    fn test_index_free_region() {
+        let mut index: MemoryIndex<8> = MemoryIndex::empty(64);
+        for _ in 0..3 {
+            let last = index.regions().count() - 1;
+            index.split_region(last, 16).unwrap();
+        }
+        for slot in 0..4 {
+            index.get_region_mut(slot).unwrap().reserve();
+        }
+
+        // Without free neighbours, the region is only freed.
+        index.free_region(1).unwrap();
+        assert_eq!(index.get_region(1), Ok(MemoryRegion::new(16, 16, false)));
+        assert_eq!(index.regions().count(), 4);
+
+        // The region is merged with both its neighbours, and the following regions are shifted back.
+        index.free_region(3).unwrap();
+        index.free_region(2).unwrap();
+        assert_eq!(index.get_region(1), Ok(MemoryRegion::new(16, 48, false)));
+        assert_eq!(index.get_region(2), Err(IndexError::NoSuchRegion));
+        assert!(index.is_sorted());
+
+        index.free_region(0).unwrap();
+        assert_eq!(index.get_region(0), Ok(MemoryRegion::new(0, 64, false)));
+        assert_eq!(index.regions().count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "compact-index")]
+    fn test_compact_index_size() {
+        assert_eq!(core::mem::size_of::<Slot>(), 4);
+        // The slots, the sorted flag and the visit counter of the tests.
+        assert!(
+            core::mem::size_of::<MemoryIndex<64>>() <= 64 * 4 + 2 * core::mem::size_of::<usize>()
         );
     }
 }
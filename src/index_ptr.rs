@@ -0,0 +1,313 @@
+//! This module contains the [`IndexPtr`] compact pointer, an offset in the memory pool of an [`IndexAllocator`].
+
+use core::fmt::{self, Debug};
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+use core::num::{NonZeroU16, NonZeroU32};
+
+use crate::{IndexAllocator, IndexError};
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+}
+
+/// The integer types an [`IndexPtr`] can store its offset in, which are [`u16`] and [`u32`].
+///
+/// An [`IndexPtr`] using [`u16`] can point in memory pools up to 65535 bytes.
+pub trait PtrWidth: sealed::Sealed {
+    /// The non zero integer holding the offset plus one, so that `Option<Repr>` has the same size as the integer.
+    type Repr: Copy + Eq + Hash + Debug;
+
+    /// Encode an offset, or return `None` if it doesn't fit.
+    fn encode(offset: usize) -> Option<Self::Repr>;
+
+    /// Decode an offset.
+    fn decode(repr: Self::Repr) -> usize;
+}
+
+impl PtrWidth for u16 {
+    type Repr = NonZeroU16;
+
+    fn encode(offset: usize) -> Option<Self::Repr> {
+        offset
+            .checked_add(1)
+            .and_then(|offset| u16::try_from(offset).ok())
+            .and_then(NonZeroU16::new)
+    }
+
+    fn decode(repr: Self::Repr) -> usize {
+        usize::from(repr.get() - 1)
+    }
+}
+
+impl PtrWidth for u32 {
+    type Repr = NonZeroU32;
+
+    fn encode(offset: usize) -> Option<Self::Repr> {
+        offset
+            .checked_add(1)
+            .and_then(|offset| u32::try_from(offset).ok())
+            .and_then(NonZeroU32::new)
+    }
+
+    fn decode(repr: Self::Repr) -> usize {
+        (repr.get() - 1) as usize
+    }
+}
+
+/// A compact pointer to a value of type `T` living in the memory pool of an [`IndexAllocator`],
+/// stored as an offset from the start of the pool.
+///
+/// An [`IndexPtr`] is the size of its `W` integer (2 bytes by default) and can be null without taking more space.
+/// It carries no lifetime and stays valid if the memory pool bytes are saved and restored,
+/// which makes it suited for the links of data structures living in the pool.
+///
+/// It is created with [`IndexAllocator::ptr_to`] and resolved against the same allocator
+/// with [`IndexAllocator::resolve`] or [`IndexAllocator::resolve_unchecked`].
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::index_ptr::IndexPtr;
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+///
+/// let test_box = allocator.try_boxed(42u32).unwrap();
+/// let ptr: IndexPtr<u32> = allocator.ptr_to(&*test_box).unwrap();
+/// assert_eq!(core::mem::size_of_val(&ptr), 2);
+///
+/// assert_eq!(unsafe { allocator.resolve(ptr) }, Ok(&42));
+/// ```
+pub struct IndexPtr<T, W = u16>
+where
+    W: PtrWidth,
+{
+    offset: Option<W::Repr>,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, W> IndexPtr<T, W>
+where
+    W: PtrWidth,
+{
+    /// Create a null [`IndexPtr`].
+    #[must_use]
+    pub const fn null() -> Self {
+        Self {
+            offset: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Test if the [`IndexPtr`] is null.
+    #[must_use]
+    pub const fn is_null(&self) -> bool {
+        self.offset.is_none()
+    }
+
+    /// Get the offset of the value from the start of the memory pool, or `None` if the [`IndexPtr`] is null.
+    #[must_use]
+    pub fn offset(&self) -> Option<usize> {
+        self.offset.map(W::decode)
+    }
+}
+
+impl<T, W> Clone for IndexPtr<T, W>
+where
+    W: PtrWidth,
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, W> Copy for IndexPtr<T, W> where W: PtrWidth {}
+
+impl<T, W> PartialEq for IndexPtr<T, W>
+where
+    W: PtrWidth,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T, W> Eq for IndexPtr<T, W> where W: PtrWidth {}
+
+impl<T, W> Hash for IndexPtr<T, W>
+where
+    W: PtrWidth,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.offset.hash(state);
+    }
+}
+
+impl<T, W> Default for IndexPtr<T, W>
+where
+    W: PtrWidth,
+{
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T, W> Debug for IndexPtr<T, W>
+where
+    W: PtrWidth,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset() {
+            Some(offset) => write!(f, "IndexPtr({offset})"),
+            None => f.write_str("IndexPtr(null)"),
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Create an [`IndexPtr`] to a value living in the memory pool.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::OutOfMemory`] if the value isn't in the memory pool
+    /// or if its offset doesn't fit in `W`.
+    pub fn ptr_to<T, W>(&self, val: &T) -> Result<IndexPtr<T, W>, IndexError>
+    where
+        W: PtrWidth,
+    {
+        let offset = (val as *const T as usize)
+            .checked_sub(self.memory.get() as usize)
+            .filter(|offset| *offset < MEMORY_SIZE)
+            .ok_or(IndexError::OutOfMemory)?;
+
+        Ok(IndexPtr {
+            offset: Some(W::encode(offset).ok_or(IndexError::OutOfMemory)?),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Resolve an [`IndexPtr`] created by this allocator, checking it points to an aligned value
+    /// inside a used region of the memory pool.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::EmptyPtr`] if the [`IndexPtr`] is null,
+    /// an [`IndexError::OutOfMemory`] if the value isn't properly aligned or doesn't fit in a used region
+    /// and an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    ///
+    /// # Safety
+    ///
+    /// The checks can't tell what the region holds: the [`IndexPtr`] must point to a live value of type `T`,
+    /// which must not be mutably borrowed while the returned reference is alive.
+    pub unsafe fn resolve<T, W>(&self, ptr: IndexPtr<T, W>) -> Result<&T, IndexError>
+    where
+        W: PtrWidth,
+    {
+        let offset = ptr.offset().ok_or(IndexError::EmptyPtr)?;
+        let addr = self.memory.get().cast::<u8>().wrapping_add(offset);
+        if addr.align_offset(mem::align_of::<T>()) != 0 {
+            return Err(IndexError::OutOfMemory);
+        }
+
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region = index.get_region(index.find_region(offset)?)?;
+        if !region.used || offset + mem::size_of::<T>() > region.end() {
+            return Err(IndexError::OutOfMemory);
+        }
+
+        Ok(&*addr.cast::<T>())
+    }
+
+    /// Resolve an [`IndexPtr`] created by this allocator without any check.
+    ///
+    /// # Safety
+    ///
+    /// The [`IndexPtr`] must not be null and must point to a live value of type `T`,
+    /// which must not be mutably borrowed while the returned reference is alive.
+    #[must_use]
+    pub unsafe fn resolve_unchecked<T, W>(&self, ptr: IndexPtr<T, W>) -> &T
+    where
+        W: PtrWidth,
+    {
+        let offset = ptr.offset().unwrap_unchecked();
+        &*self.memory.get().cast::<u8>().add(offset).cast::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    struct Node {
+        val: u16,
+        next: IndexPtr<Node>,
+    }
+
+    #[test]
+    fn test_ptr_size() {
+        assert_eq!(mem::size_of::<IndexPtr<Node>>(), 2);
+        assert_eq!(mem::size_of::<IndexPtr<Node, u32>>(), 4);
+        assert_eq!(mem::size_of::<Node>(), 4);
+    }
+
+    #[test]
+    fn test_ptr_null() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let ptr: IndexPtr<Node> = IndexPtr::null();
+        assert!(ptr.is_null());
+        assert_eq!(ptr, IndexPtr::default());
+        assert_eq!(
+            unsafe { allocator.resolve(ptr) }.err(),
+            Some(IndexError::EmptyPtr)
+        );
+
+        let outside = 0u8;
+        assert_eq!(
+            allocator.ptr_to::<u8, u16>(&outside),
+            Err(IndexError::OutOfMemory)
+        );
+    }
+
+    #[test]
+    // Ignore MIRI because the allocator inner memory is directly written, wich MIRI don't like.
+    #[cfg_attr(miri, ignore)]
+    fn test_ptr_linked_list() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let mut head = IndexPtr::null();
+        for val in 1..=3 {
+            let node = unsafe { allocator.try_alloc_value(Node { val, next: head }) }.unwrap();
+            head = allocator.ptr_to(unsafe { node.as_ref() }).unwrap();
+        }
+
+        // Save the pool bytes, wipe the pool and restore it: the links are offsets and stay valid.
+        let saved: Vec<u8> = crate::tests::memory(&allocator).to_vec();
+        unsafe {
+            allocator.fill(0, 64, 0);
+            (*allocator.memory.get()).copy_from_slice(&saved);
+        }
+
+        let mut values = Vec::new();
+        let mut current = head;
+        while !current.is_null() {
+            let node = unsafe { allocator.resolve(current) }.unwrap();
+            values.push(node.val);
+            current = node.next;
+        }
+        assert_eq!(values, [3, 2, 1]);
+
+        let last = unsafe { allocator.resolve_unchecked(head) };
+        assert_eq!(last.val, 3);
+    }
+}
@@ -0,0 +1,230 @@
+//! This module contains the latency histogram, to characterize the jitter of the allocator on the target hardware.
+//!
+//! It is only available with the `profiling` feature. Once a cycle counter is set with [`IndexAllocator::set_cycle_counter`],
+//! the duration of every reservation and free of a region is recorded in buckets of powers of two cycles,
+//! apart for the successes and the failures. The counter is a plain `u32` which may wrap around, such as the
+//! `DWT` cycle counter of a Cortex-M: its unit is up to the user. Without a counter, nothing is recorded.
+//!
+//! The hooks called within an operation, such as the watermark hook, are timed along with it, unlike the observer.
+
+use core::cell::Cell;
+
+use crate::IndexAllocator;
+
+/// The number of buckets of the latency histogram, one for the durations of 0 cycles then one for every power of two.
+pub const LATENCY_BUCKETS: usize = u32::BITS as usize + 1;
+
+/// Get the bucket of the latency histogram counting the durations of `cycles`.
+///
+/// The bucket 0 counts the durations of 0 cycles, and the bucket `i` the durations from `2^(i - 1)` to `2^i - 1` cycles.
+#[must_use]
+pub const fn latency_bucket(cycles: u32) -> usize {
+    (u32::BITS - cycles.leading_zeros()) as usize
+}
+
+/// The durations recorded by an allocator, see [`IndexAllocator::latency_histogram`].
+///
+/// Every array counts the operations in each bucket, see [`latency_bucket`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// The reservations of a region which succeeded.
+    pub reserve_succeeded: [usize; LATENCY_BUCKETS],
+    /// The reservations of a region which failed.
+    pub reserve_failed: [usize; LATENCY_BUCKETS],
+    /// The frees of a region which succeeded.
+    pub free_succeeded: [usize; LATENCY_BUCKETS],
+    /// The frees of a region which failed.
+    pub free_failed: [usize; LATENCY_BUCKETS],
+}
+
+/// The operations timed by the latency histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    Reserve,
+    Free,
+}
+
+/// The cycle counter of an allocator, and the counters of its latency histogram.
+pub(crate) struct LatencyCounters {
+    cycle_counter: Cell<Option<fn() -> u32>>,
+    reserve_succeeded: [Cell<usize>; LATENCY_BUCKETS],
+    reserve_failed: [Cell<usize>; LATENCY_BUCKETS],
+    free_succeeded: [Cell<usize>; LATENCY_BUCKETS],
+    free_failed: [Cell<usize>; LATENCY_BUCKETS],
+}
+
+impl LatencyCounters {
+    pub const fn new() -> Self {
+        Self {
+            cycle_counter: Cell::new(None),
+            reserve_succeeded: [const { Cell::new(0) }; LATENCY_BUCKETS],
+            reserve_failed: [const { Cell::new(0) }; LATENCY_BUCKETS],
+            free_succeeded: [const { Cell::new(0) }; LATENCY_BUCKETS],
+            free_failed: [const { Cell::new(0) }; LATENCY_BUCKETS],
+        }
+    }
+
+    fn buckets(&self, operation: Operation, succeeded: bool) -> &[Cell<usize>; LATENCY_BUCKETS] {
+        match (operation, succeeded) {
+            (Operation::Reserve, true) => &self.reserve_succeeded,
+            (Operation::Reserve, false) => &self.reserve_failed,
+            (Operation::Free, true) => &self.free_succeeded,
+            (Operation::Free, false) => &self.free_failed,
+        }
+    }
+}
+
+/// Copy the counters of a bucket array.
+fn snapshot(buckets: &[Cell<usize>; LATENCY_BUCKETS]) -> [usize; LATENCY_BUCKETS] {
+    let mut counts = [0; LATENCY_BUCKETS];
+    for (count, bucket) in counts.iter_mut().zip(buckets) {
+        *count = bucket.get();
+    }
+    counts
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Set the cycle counter timing the following operations, replacing the previous one.
+    ///
+    /// The counter is read twice on every allocation and free, so it should be cheap,
+    /// and it must not allocate through this allocator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicU32, Ordering};
+    /// use index_alloc::latency::latency_bucket;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// static CYCLES: AtomicU32 = AtomicU32::new(0);
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// allocator.set_cycle_counter(|| CYCLES.fetch_add(100, Ordering::Relaxed));
+    ///
+    /// let test_box = allocator.try_boxed([1u8; 4]).unwrap();
+    /// assert_eq!(allocator.latency_histogram().reserve_succeeded[latency_bucket(100)], 1);
+    /// ```
+    pub fn set_cycle_counter(&self, counter: fn() -> u32) {
+        self.latency.cycle_counter.set(Some(counter));
+    }
+
+    /// Remove the cycle counter set with [`IndexAllocator::set_cycle_counter`], which stops the recording.
+    pub fn clear_cycle_counter(&self) {
+        self.latency.cycle_counter.set(None);
+    }
+
+    /// Get the durations recorded since the allocator was created, or since [`IndexAllocator::clear_latency_histogram`].
+    #[must_use]
+    pub fn latency_histogram(&self) -> LatencyHistogram {
+        LatencyHistogram {
+            reserve_succeeded: snapshot(&self.latency.reserve_succeeded),
+            reserve_failed: snapshot(&self.latency.reserve_failed),
+            free_succeeded: snapshot(&self.latency.free_succeeded),
+            free_failed: snapshot(&self.latency.free_failed),
+        }
+    }
+
+    /// Forget the durations recorded so far, keeping the cycle counter.
+    pub fn clear_latency_histogram(&self) {
+        for operation in [Operation::Reserve, Operation::Free] {
+            for succeeded in [true, false] {
+                for bucket in self.latency.buckets(operation, succeeded) {
+                    bucket.set(0);
+                }
+            }
+        }
+    }
+
+    /// Read the cycle counter, if it is set.
+    pub(crate) fn read_cycles(&self) -> Option<u32> {
+        self.latency.cycle_counter.get().map(|counter| counter())
+    }
+
+    /// Record the duration of an operation started at `start`, read with [`IndexAllocator::read_cycles`].
+    pub(crate) fn record_latency(&self, operation: Operation, start: Option<u32>, succeeded: bool) {
+        let (Some(start), Some(end)) = (start, self.read_cycles()) else {
+            return;
+        };
+        let buckets = self.latency.buckets(operation, succeeded);
+        if let Some(bucket) = buckets.get(latency_bucket(end.wrapping_sub(start))) {
+            bucket.set(bucket.get() + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::IndexError;
+
+    static CYCLES: AtomicU32 = AtomicU32::new(u32::MAX - 8);
+    static READS: AtomicUsize = AtomicUsize::new(0);
+    /// The cycles elapsed between the start and the end of the successive operations.
+    const DURATIONS: [u32; 6] = [0, 1, 40, 3, 1000, 40];
+
+    /// A counter advancing by the scripted durations when an operation ends, and by 1000 cycles between two operations.
+    fn scripted_counter() -> u32 {
+        let read = READS.fetch_add(1, Ordering::Relaxed);
+        let step = if read % 2 == 1 {
+            DURATIONS[read / 2]
+        } else {
+            1000
+        };
+        CYCLES.fetch_add(step, Ordering::Relaxed).wrapping_add(step)
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+        let untimed = allocator.try_boxed(0u8).unwrap();
+        allocator.set_cycle_counter(scripted_counter);
+
+        let first = allocator.try_boxed([0u8; 8]).unwrap();
+        let second = allocator.try_boxed([0u8; 8]).unwrap();
+        assert!(allocator.try_boxed([0u8; 128]).is_err());
+        drop(first);
+        let second_ptr = second.as_ptr();
+        drop(second);
+        assert_eq!(
+            unsafe { allocator.try_free(second_ptr.cast_mut().cast()) },
+            Err(IndexError::DoubleFree)
+        );
+        assert_eq!(READS.load(Ordering::Relaxed), 2 * DURATIONS.len());
+
+        let histogram = allocator.latency_histogram();
+        let mut expected = LatencyHistogram {
+            reserve_succeeded: [0; LATENCY_BUCKETS],
+            reserve_failed: [0; LATENCY_BUCKETS],
+            free_succeeded: [0; LATENCY_BUCKETS],
+            free_failed: [0; LATENCY_BUCKETS],
+        };
+        expected.reserve_succeeded[0] = 1;
+        expected.reserve_succeeded[1] = 1;
+        expected.reserve_failed[6] = 1;
+        expected.free_succeeded[2] = 1;
+        expected.free_succeeded[10] = 1;
+        expected.free_failed[6] = 1;
+        assert_eq!(histogram, expected);
+
+        allocator.clear_cycle_counter();
+        drop(untimed);
+        assert_eq!(allocator.latency_histogram(), histogram);
+        allocator.clear_latency_histogram();
+        assert_eq!(
+            allocator.latency_histogram().reserve_succeeded,
+            [0; LATENCY_BUCKETS]
+        );
+    }
+
+    #[test]
+    fn test_latency_bucket() {
+        assert_eq!(latency_bucket(0), 0);
+        assert_eq!(latency_bucket(1), 1);
+        assert_eq!(latency_bucket(2), 2);
+        assert_eq!(latency_bucket(3), 2);
+        assert_eq!(latency_bucket(4), 3);
+        assert_eq!(latency_bucket(u32::MAX), LATENCY_BUCKETS - 1);
+    }
+}
@@ -0,0 +1,56 @@
+//! This module contains the drop policy of the `lenient-drop` feature, under which smart pointers leak their memory
+//! instead of panicking when freeing it fails in their `Drop` implementation.
+//!
+//! By default, a smart pointer failing to free its memory when dropped panics in debug builds, so the failure is noticed.
+//! With the `lenient-drop` feature, the memory is leaked instead: the failure is flagged as usual
+//! (see [`IndexAllocator::has_failed_drop`]), the leak is counted by [`leaked_drops`],
+//! and the hook set with [`IndexAllocator::set_leak_hook`] is called.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{IndexAllocator, IndexError};
+
+/// The number of allocations leaked because freeing them failed in a `Drop` implementation, shared by every allocator.
+static LEAKED_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the number of allocations leaked because freeing them failed in a `Drop` implementation,
+/// across every [`IndexAllocator`] of the program.
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::{leak, IndexAllocator};
+///
+/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+///
+/// let leaked = leak::leaked_drops();
+/// drop(allocator.try_boxed(1u32).unwrap());
+/// assert_eq!(leak::leaked_drops(), leaked);
+/// ```
+#[must_use]
+pub fn leaked_drops() -> usize {
+    LEAKED_DROPS.load(Ordering::Relaxed)
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Set a hook called with the error whenever a smart pointer of this allocator leaks its memory
+    /// because freeing it failed in its `Drop` implementation, replacing the previous one.
+    ///
+    /// The hook is called from the `Drop` implementation, so it must not use the smart pointers of the allocator.
+    pub fn set_leak_hook(&self, hook: fn(IndexError)) {
+        self.leak_hook.set(Some(hook));
+    }
+
+    /// Remove the hook set with [`IndexAllocator::set_leak_hook`].
+    pub fn clear_leak_hook(&self) {
+        self.leak_hook.set(None);
+    }
+
+    /// Record the memory of a smart pointer leaked because freeing it failed with `err`.
+    pub(crate) fn record_leak(&self, err: IndexError) {
+        LEAKED_DROPS.fetch_add(1, Ordering::Relaxed);
+        if let Some(hook) = self.leak_hook.get() {
+            hook(err);
+        }
+    }
+}
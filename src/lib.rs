@@ -1,16 +1,102 @@
 #![doc = include_str!("../README.md")]
 #![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 
 use core::alloc::{GlobalAlloc, Layout};
-use core::cell::{RefCell, UnsafeCell};
-use core::ptr;
+use core::cell::{Cell, RefCell, UnsafeCell};
+use core::fmt::{self, Display};
+use core::mem::{self, MaybeUninit};
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
 
+#[cfg(test)]
+extern crate std;
+
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "age")]
+pub mod age;
 pub mod boxed;
+#[cfg(feature = "buddy")]
+pub mod buddy;
+mod bump;
+pub mod const_pool;
+#[cfg(feature = "embedded-dma")]
+mod dma;
+#[cfg(feature = "async")]
+pub mod future;
+#[cfg(feature = "generations")]
+pub mod generation;
+pub mod header;
+pub mod image;
+#[cfg(not(feature = "index-fixtures"))]
 mod index;
+#[cfg(feature = "index-fixtures")]
+pub mod index;
+pub mod index_ptr;
+#[cfg(feature = "profiling")]
+pub mod latency;
+#[cfg(feature = "lenient-drop")]
+pub mod leak;
+pub mod list;
+mod merge;
+#[cfg(feature = "observer")]
+pub mod observer;
+#[cfg(feature = "oplog")]
+pub mod oplog;
+pub mod poison;
+pub mod pool;
+pub mod prelude;
+pub mod priority;
+#[cfg(feature = "call-site")]
+pub mod profile;
 pub mod rc;
+#[cfg(feature = "redzone")]
+pub mod redzone;
+pub mod reservation;
+pub mod scope;
+pub mod stats;
+pub mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod uninit;
+pub mod vec;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "zeroize")]
+mod zeroize;
 
 use boxed::Box;
-use index::MemoryIndex;
+#[cfg(feature = "compact-index")]
+pub use index::COMPACT_MEMORY_SIZE;
+use index::{AllocationBaker, MemoryIndex};
+#[cfg(feature = "redzone")]
+use redzone::{REDZONE_BYTE, REDZONE_SIZE};
+
+#[cfg(all(
+    feature = "compact-index",
+    any(
+        feature = "call-site",
+        feature = "age",
+        feature = "redzone",
+        feature = "stats"
+    )
+))]
+compile_error!("the `compact-index` feature can't keep the per-region data of the `call-site`, `age`, `redzone` and `stats` features");
+
+/// Without the `redzone` feature, allocations have no gaps around them.
+#[cfg(not(feature = "redzone"))]
+const REDZONE_SIZE: usize = 0;
+
+/// Where an allocation is placed in the memory pool, see [`IndexAllocator::try_reserve_high`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    /// In the lowest free region fitting it.
+    Low,
+    /// At the end of the highest free region fitting it.
+    High,
+}
 
 /// The Error type wich the Allocator can raise.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -28,7 +114,49 @@ pub enum IndexError {
     /// The pointer provided is null.
     EmptyPtr,
     /// The `MemoryIndex` is already borrowed.
+    ///
+    /// The hooks set on the allocator, such as [`IndexAllocator::set_watermark`], are always called once the index
+    /// is released, so they can query it without hitting this error.
     IndexAlreadyBorrowed,
+    /// The region trying to be freed isn't allocated.
+    DoubleFree,
+    /// The allocator is used before being initialized, see [`uninit::UninitIndexAllocator`].
+    NotInitialized,
+    /// The image being imported is malformed or doesn't fit the allocator, see [`IndexAllocator::import_image`].
+    InvalidImage,
+    /// The size of the allocation overflows, such as an array too long for its type, see [`IndexAllocator::try_alloc_array`].
+    LayoutOverflow,
+    /// The allocator was stopped after heap corruption was detected, see [`IndexAllocator::poison`].
+    Poisoned,
+}
+
+impl Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NoSuchRegion => "the memory region doesn't exist",
+            Self::NoIndexAvailable => "the memory index is full",
+            Self::NoFittingRegion => "no free region fits the allocation",
+            Self::OutOfMemory => "the address isn't in the memory pool",
+            Self::RegionTooThin => "the region is too thin for the operation",
+            Self::EmptyPtr => "the pointer is null",
+            Self::IndexAlreadyBorrowed => "the memory index is already borrowed",
+            Self::DoubleFree => "the region is already free",
+            Self::NotInitialized => "the allocator isn't initialized",
+            Self::InvalidImage => "the image doesn't fit the allocator",
+            Self::LayoutOverflow => "the size of the allocation overflows",
+            Self::Poisoned => "the allocator is poisoned",
+        })
+    }
+}
+
+impl core::error::Error for IndexError {}
+
+/// Allows the use of `?` on [`IndexError`] in [`core::alloc::Allocator`] implementations.
+#[cfg(feature = "allocator_api")]
+impl From<IndexError> for core::alloc::AllocError {
+    fn from(_: IndexError) -> Self {
+        Self
+    }
 }
 
 /// The [`IndexAllocator`] struct is the main component of this crate, it creates a memory pool of size `MEMORY_SIZE` with an index of size `INDEX_SIZE`.
@@ -37,32 +165,158 @@ pub enum IndexError {
 ///
 /// For instance, setting `INDEX_SIZE` to 4 means no more allocations can be performed after 4 boxes are allocated, except if some of them are freed.
 ///
-/// [`IndexAllocator`] implement the [`GlobalAlloc`] trait which allows it to be used as the app allocator.
+/// [`IndexAllocator`] implement the [`GlobalAlloc`] trait which allows it to be used as the app allocator,
+/// once wrapped in one of the [`sync`] wrappers.
+///
+/// Allocation is deterministic: the first free region able to hold the value is used, and the remainder of the region
+/// takes the slot right after it in the index, so the same sequence of allocations and frees always produces the same layout.
+///
+/// # Failure atomicity
+///
+/// An allocation, deallocation or shrink which fails leaves the index exactly as it was,
+/// so the allocator stays usable and can be retried after an error.
+///
+/// # Execution time
+///
+/// The index is kept sorted, so no operation ever sorts it and the execution time only depends on `INDEX_SIZE`,
+/// not on the history of the allocator.
+/// An allocation visits at most 3 × `INDEX_SIZE` slots of the index (finding a fitting region, an empty slot,
+/// and shifting the following regions) and a deallocation at most 2 × `INDEX_SIZE` + 2 (finding the region,
+/// checking its two neighbours and shifting the following regions back after a merge).
+/// Filling memory, with [`IndexAllocator::set_free_scrub`] or the `redzone` feature, adds time proportional to the size of the allocation.
+///
+/// # Panics
+///
+/// In release builds, the allocation and deallocation paths (including the [`GlobalAlloc`] implementation
+/// and the smart pointers `Drop` implementations) never panic, errors are reported through [`IndexError`] instead.
+/// [`GlobalAlloc::alloc`] returns a null pointer on failure and [`GlobalAlloc::dealloc`] ignores invalid pointers.
+/// In debug builds, the smart pointers still panic when they fail to free their memory.
+///
+/// This is checked at link time by the `no-panic` crate of the workspace.
+///
+/// # Thread safety
+///
+/// The index and the counters are kept in cells which aren't thread-safe, so [`IndexAllocator`] isn't [`Sync`]
+/// and can't be shared between threads:
+///
+/// ```compile_fail
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+///
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| allocator.try_boxed(1u8).unwrap());
+///     scope.spawn(|| allocator.try_boxed(2u8).unwrap());
+/// });
+/// ```
+///
+/// A `static` allocator must be wrapped in [`SingleThreaded`](sync::SingleThreaded) or, with the `critical-section` feature,
+/// in `Locked`, see the [`sync`] module.
+/// The smart pointers borrow the [`IndexAllocator`], so they are never [`Send`], even in a `Locked` allocator,
+/// see the [`Box`](boxed::Box#thread-safety) documentation.
+///
+/// # Logging
+///
+/// With the `log` feature, allocation failures in the [`GlobalAlloc`] implementation, double frees,
+/// corrupted gaps found on free with the `redzone` feature
+/// and failed frees in the smart pointers `Drop` implementations emit `log` records.
+/// Records are only emitted once the index is released, so a logger allocating through the same allocator
+/// doesn't fail with [`IndexError::IndexAlreadyBorrowed`], but it shouldn't rely on the allocation
+/// which just failed to succeed.
 ///
 /// # Example
 ///
 /// ```rust
-/// use index_alloc::IndexAllocator;
+/// use index_alloc::sync::SingleThreaded;
 ///
 /// #[global_allocator]
-/// static ALLOCATOR: IndexAllocator<1024, 16> = IndexAllocator::empty();
+/// static ALLOCATOR: SingleThreaded<1024, 16> = unsafe { SingleThreaded::empty() };
 ///```
 pub struct IndexAllocator<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
     memory: UnsafeCell<[u8; MEMORY_SIZE]>,
     index: RefCell<MemoryIndex<INDEX_SIZE>>,
-}
-
-unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Sync
-    for IndexAllocator<MEMORY_SIZE, INDEX_SIZE>
-{
+    free_scrub: Cell<Option<u8>>,
+    used_bytes: Cell<usize>,
+    allocations: Cell<usize>,
+    initial_free_bytes: usize,
+    untouched: Cell<usize>,
+    failed_drop: Cell<bool>,
+    poison_reason: Cell<Option<poison::PoisonReason>>,
+    epoch: Cell<usize>,
+    bump: Cell<Option<bump::BumpArena>>,
+    reserve: Cell<usize>,
+    lazy_merge: Cell<bool>,
+    pending_merges: Cell<usize>,
+    watermark: Cell<Option<stats::Watermark>>,
+    extra_pools: pool::ExtraPools,
+    #[cfg(feature = "generations")]
+    stale_drops: Cell<usize>,
+    #[cfg(feature = "stats")]
+    size_histogram: stats::SizeHistogram,
+    #[cfg(feature = "async")]
+    wakers: future::WakerSlots,
+    #[cfg(feature = "lenient-drop")]
+    leak_hook: Cell<Option<fn(IndexError)>>,
+    #[cfg(feature = "age")]
+    clock: Cell<Option<fn() -> u32>>,
+    #[cfg(feature = "buddy")]
+    buddy: bool,
+    #[cfg(feature = "observer")]
+    observer: Cell<Option<&'static dyn observer::AllocObserver>>,
+    #[cfg(feature = "watch")]
+    watchpoints: watch::Watchpoints,
+    #[cfg(feature = "profiling")]
+    latency: latency::LatencyCounters,
+    #[cfg(feature = "testing")]
+    fail_after: Cell<Option<usize>>,
+    #[cfg(feature = "oplog")]
+    op_log: Cell<Option<oplog::OpLog>>,
 }
 
 impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
     #[must_use]
     const fn new(memory: [u8; MEMORY_SIZE], index: MemoryIndex<INDEX_SIZE>) -> Self {
+        #[cfg(feature = "compact-index")]
+        Self::check_compact_index();
         Self {
             memory: UnsafeCell::new(memory),
             index: RefCell::new(index),
+            free_scrub: Cell::new(None),
+            used_bytes: Cell::new(0),
+            allocations: Cell::new(0),
+            initial_free_bytes: MEMORY_SIZE,
+            untouched: Cell::new(0),
+            failed_drop: Cell::new(false),
+            poison_reason: Cell::new(None),
+            epoch: Cell::new(0),
+            bump: Cell::new(None),
+            reserve: Cell::new(0),
+            lazy_merge: Cell::new(false),
+            pending_merges: Cell::new(0),
+            watermark: Cell::new(None),
+            extra_pools: pool::ExtraPools::new(),
+            #[cfg(feature = "generations")]
+            stale_drops: Cell::new(0),
+            #[cfg(feature = "stats")]
+            size_histogram: stats::SizeHistogram::new(),
+            #[cfg(feature = "async")]
+            wakers: future::WakerSlots::new(),
+            #[cfg(feature = "lenient-drop")]
+            leak_hook: Cell::new(None),
+            #[cfg(feature = "age")]
+            clock: Cell::new(None),
+            #[cfg(feature = "buddy")]
+            buddy: false,
+            #[cfg(feature = "observer")]
+            observer: Cell::new(None),
+            #[cfg(feature = "watch")]
+            watchpoints: watch::Watchpoints::new(),
+            #[cfg(feature = "profiling")]
+            latency: latency::LatencyCounters::new(),
+            #[cfg(feature = "testing")]
+            fail_after: Cell::new(None),
+            #[cfg(feature = "oplog")]
+            op_log: Cell::new(None),
         }
     }
 
@@ -75,12 +329,109 @@ impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SI
     /// Note that the `MEMORY_SIZE` and `INDEX_SIZE` need to be inferred at this point.
     #[must_use]
     pub const fn empty() -> Self {
+        #[cfg(feature = "compact-index")]
+        Self::check_compact_index();
         Self::new([0; MEMORY_SIZE], MemoryIndex::empty(MEMORY_SIZE))
     }
 
+    /// Write an empty [`IndexAllocator`] at `this`, field by field, so that the memory pool isn't built on the stack.
+    /// The memory pool is only zeroed if `zeroed` is set, it is left as is otherwise.
+    ///
+    /// The fields are the ones of [`IndexAllocator::new`], which this must be kept in sync with.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be valid for writes and properly aligned.
+    unsafe fn init_in_place(this: *mut Self, zeroed: bool) {
+        #[cfg(feature = "compact-index")]
+        Self::check_compact_index();
+        if zeroed {
+            ptr::addr_of_mut!((*this).memory)
+                .cast::<u8>()
+                .write_bytes(0, MEMORY_SIZE);
+        }
+        ptr::addr_of_mut!((*this).index).write(RefCell::new(MemoryIndex::empty(MEMORY_SIZE)));
+        ptr::addr_of_mut!((*this).free_scrub).write(Cell::new(None));
+        ptr::addr_of_mut!((*this).used_bytes).write(Cell::new(0));
+        ptr::addr_of_mut!((*this).allocations).write(Cell::new(0));
+        ptr::addr_of_mut!((*this).initial_free_bytes).write(MEMORY_SIZE);
+        // Without zeroing, none of the memory pool is known to be zero.
+        ptr::addr_of_mut!((*this).untouched).write(Cell::new(if zeroed { 0 } else { MEMORY_SIZE }));
+        ptr::addr_of_mut!((*this).failed_drop).write(Cell::new(false));
+        ptr::addr_of_mut!((*this).poison_reason).write(Cell::new(None));
+        ptr::addr_of_mut!((*this).epoch).write(Cell::new(0));
+        ptr::addr_of_mut!((*this).bump).write(Cell::new(None));
+        ptr::addr_of_mut!((*this).reserve).write(Cell::new(0));
+        ptr::addr_of_mut!((*this).lazy_merge).write(Cell::new(false));
+        ptr::addr_of_mut!((*this).pending_merges).write(Cell::new(0));
+        ptr::addr_of_mut!((*this).watermark).write(Cell::new(None));
+        ptr::addr_of_mut!((*this).extra_pools).write(pool::ExtraPools::new());
+        #[cfg(feature = "generations")]
+        ptr::addr_of_mut!((*this).stale_drops).write(Cell::new(0));
+        #[cfg(feature = "stats")]
+        ptr::addr_of_mut!((*this).size_histogram).write(stats::SizeHistogram::new());
+        #[cfg(feature = "async")]
+        ptr::addr_of_mut!((*this).wakers).write(future::WakerSlots::new());
+        #[cfg(feature = "lenient-drop")]
+        ptr::addr_of_mut!((*this).leak_hook).write(Cell::new(None));
+        #[cfg(feature = "age")]
+        ptr::addr_of_mut!((*this).clock).write(Cell::new(None));
+        #[cfg(feature = "buddy")]
+        ptr::addr_of_mut!((*this).buddy).write(false);
+        #[cfg(feature = "observer")]
+        ptr::addr_of_mut!((*this).observer).write(Cell::new(None));
+        #[cfg(feature = "watch")]
+        ptr::addr_of_mut!((*this).watchpoints).write(watch::Watchpoints::new());
+        #[cfg(feature = "profiling")]
+        ptr::addr_of_mut!((*this).latency).write(latency::LatencyCounters::new());
+        #[cfg(feature = "testing")]
+        ptr::addr_of_mut!((*this).fail_after).write(Cell::new(None));
+        #[cfg(feature = "oplog")]
+        ptr::addr_of_mut!((*this).op_log).write(Cell::new(None));
+    }
+
+    /// Fail to compile if the memory pool is too large for the `compact-index` feature.
+    #[cfg(feature = "compact-index")]
+    const fn check_compact_index() {
+        const {
+            assert!(
+                MEMORY_SIZE <= index::COMPACT_MEMORY_SIZE,
+                "the memory pool is too large for the `compact-index` feature"
+            );
+        }
+    }
+
     /// Try to reserve some [`MemoryRegion`] based on [`Layout`] and then return an aligned address (inside the memory pool).
-    fn try_reserve(&self, layout: Layout) -> Result<usize, IndexError> {
-        let layout = layout.pad_to_align();
+    ///
+    /// Everything which can fail is computed before the index is mutated, so an error leaves the index unchanged.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    fn try_reserve(
+        &self,
+        layout: Layout,
+        priority: bool,
+        placement: Placement,
+    ) -> Result<usize, IndexError> {
+        self.check_poison()?;
+        #[cfg(feature = "testing")]
+        self.inject_failure()?;
+        // No region can be aligned further than the size of the memory pool, bail out before any offset math.
+        if layout.align() > MEMORY_SIZE {
+            return Err(IndexError::NoFittingRegion);
+        }
+        let budget = self.budget(priority);
+        if placement == Placement::Low {
+            if let Some(addr) = self.try_bump(layout, budget) {
+                self.check_watermark();
+                return Ok(addr);
+            }
+        }
+        #[cfg(feature = "stats")]
+        let requested_size = layout.size();
+        // The clock is read before the index is borrowed, in case it allocates.
+        #[cfg(feature = "age")]
+        let now = self.now();
+        // Only the start of the value needs to be aligned: the size isn't padded to the alignment,
+        // so a small over-aligned layout doesn't waste the padding after it, which the next allocation may use.
         let memory_start = self.memory.get() as usize;
 
         let mut index = self
@@ -88,94 +439,2379 @@ impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SI
             .try_borrow_mut()
             .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
 
-        let allocation_baker = index.size_region_available(memory_start, layout)?;
-
-        let (region_index, _) = index.split_region(
-            allocation_baker.region,
-            allocation_baker.offset + layout.size(),
-        )?;
+        // The value is aligned after the gap before it, and the gap after it is reserved as well.
+        let reserved_size = layout
+            .size()
+            .checked_add(2 * REDZONE_SIZE)
+            .ok_or(IndexError::NoFittingRegion)?;
+        let reserved_layout = Layout::from_size_align(reserved_size, layout.align())
+            .map_err(|_| IndexError::NoFittingRegion)?;
+        let mut reserved = self.reserve_region(
+            &mut index,
+            memory_start + REDZONE_SIZE,
+            reserved_layout,
+            budget,
+            placement,
+        );
+        // The regions freed in lazy merge mode may fit once merged, which is only tried once:
+        // the merge leaves nothing pending, so a second failure is final.
+        if let Err(IndexError::NoFittingRegion | IndexError::NoIndexAvailable) = reserved {
+            if self.merge_pending(&mut index) {
+                reserved = self.reserve_region(
+                    &mut index,
+                    memory_start + REDZONE_SIZE,
+                    reserved_layout,
+                    budget,
+                    placement,
+                );
+            }
+        }
+        let (region_index, allocation_baker) = reserved?;
 
-        let region = index.get_region_mut(region_index)?;
+        let mut region = index.get_region_mut(region_index)?;
         region.reserve();
+        #[cfg(feature = "call-site")]
+        {
+            region.location = Some(core::panic::Location::caller());
+        }
+        #[cfg(feature = "age")]
+        {
+            region.allocated_at = now;
+        }
+        #[cfg(feature = "stats")]
+        {
+            region.requested_size = requested_size;
+            self.size_histogram.record_alloc(requested_size);
+        }
+        let data = region.from + allocation_baker.offset + REDZONE_SIZE;
+        #[cfg(feature = "redzone")]
+        {
+            region.data_offset = data - region.from;
+            unsafe {
+                self.fill(data - REDZONE_SIZE, REDZONE_SIZE, REDZONE_BYTE);
+                self.fill(region.end() - REDZONE_SIZE, REDZONE_SIZE, REDZONE_BYTE);
+            }
+        }
 
-        Ok(region.from + allocation_baker.offset)
+        self.used_bytes.set(self.used_bytes.get() + region.size);
+        self.allocations.set(self.allocations.get() + 1);
+        self.touch(region.pool, region.end());
+        #[cfg(feature = "watch")]
+        let reserved = *region;
+        drop(region);
+        drop(index);
+
+        #[cfg(feature = "watch")]
+        self.report_watch(watch::WatchKind::Alloc, &reserved);
+        self.check_watermark();
+
+        Ok(data)
     }
 
     /// Try to free some [`MemoryRegion`] (here the address is the index in the memory pool).
     fn try_free_addr(&self, addr: usize) -> Result<(), IndexError> {
+        self.check_poison()?;
         let mut index = self
             .index
             .try_borrow_mut()
             .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
         let region_index = index.find_region(addr)?;
+        let region = index.get_region(region_index)?;
 
-        index.get_region_mut(region_index)?.free();
-        index.sort_merge();
+        if !region.used {
+            return Err(IndexError::DoubleFree);
+        }
+        // The allocations in a bump arena are never freed individually.
+        if region.arena {
+            return Ok(());
+        }
+
+        #[cfg(feature = "zeroize")]
+        unsafe {
+            self.wipe(region.from, region.size);
+        }
+        if let Some(byte) = self.free_scrub.get() {
+            unsafe { self.fill(region.from, region.size, byte) };
+        }
+
+        #[cfg(any(feature = "observer", feature = "oplog"))]
+        let size = region.size;
+        self.used_bytes
+            .set(self.used_bytes.get().saturating_sub(region.size));
+        self.allocations
+            .set(self.allocations.get().saturating_sub(1));
+        #[cfg(feature = "stats")]
+        self.size_histogram.record_free(region.requested_size);
+        self.free_region(&mut index, region_index)?;
+        drop(index);
+
+        #[cfg(feature = "observer")]
+        self.observe_free(addr, size);
+        #[cfg(feature = "oplog")]
+        self.record_op(oplog::LogOp::Free, addr, size);
+        #[cfg(feature = "watch")]
+        self.report_watch(watch::WatchKind::Free, &region);
+
+        self.check_watermark();
+        #[cfg(feature = "async")]
+        self.wake_pending();
 
         Ok(())
     }
 
+    /// Find a free region fitting `layout` and split it to the reserved size, or take the smallest fitting block in buddy mode.
+    /// Return the index of the region to reserve, and how to bake the allocation in it.
+    ///
+    /// With [`Placement::High`], the free region with the highest address is split instead, keeping its tail.
+    /// A region larger than `budget` bytes fails with an [`IndexError::NoFittingRegion`], leaving the index as it was.
+    fn reserve_region(
+        &self,
+        index: &mut MemoryIndex<INDEX_SIZE>,
+        memory_start: usize,
+        layout: Layout,
+        budget: usize,
+        placement: Placement,
+    ) -> Result<(usize, AllocationBaker), IndexError> {
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            let allocation_baker = index.buddy_reserve(memory_start, layout)?;
+            // The size of the block is only known once split, the halves are merged back if it is too large.
+            if index.get_region(allocation_baker.region)?.size > budget {
+                index.buddy_free(allocation_baker.region)?;
+                return Err(IndexError::NoFittingRegion);
+            }
+            return Ok((allocation_baker.region, allocation_baker));
+        }
+
+        if placement == Placement::High {
+            let allocation_baker = index.size_region_available_high(memory_start, layout)?;
+            let region = index.get_region(allocation_baker.region)?;
+            if region.size - allocation_baker.offset > budget {
+                return Err(IndexError::NoFittingRegion);
+            }
+            if allocation_baker.offset == 0 {
+                return Ok((allocation_baker.region, allocation_baker));
+            }
+            // The tail of the region is reserved, with the slack left by the alignment after the value.
+            let (_, tail) = index.split_region(allocation_baker.region, allocation_baker.offset)?;
+            return Ok((
+                tail,
+                AllocationBaker {
+                    region: tail,
+                    offset: 0,
+                },
+            ));
+        }
+
+        let allocation_baker = index.size_region_available(memory_start, layout)?;
+        if allocation_baker.offset + layout.size() > budget {
+            return Err(IndexError::NoFittingRegion);
+        }
+        // The split is the only mutation which can fail, and it checks an index is available before mutating anything.
+        let (region_index, _) = index.split_region(
+            allocation_baker.region,
+            allocation_baker.offset + layout.size(),
+        )?;
+        Ok((region_index, allocation_baker))
+    }
+
+    /// Free a region of the index, merging it with the free regions around it, or with its buddy in buddy mode.
+    fn free_region(
+        &self,
+        index: &mut MemoryIndex<INDEX_SIZE>,
+        region: usize,
+    ) -> Result<(), IndexError> {
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            return index.buddy_free(region);
+        }
+        if self.free_lazily(index, region)? {
+            return Ok(());
+        }
+        index.free_region(region)
+    }
+
+    /// Shrink a used region of the index to `size`, unless in buddy mode where the blocks keep their size class.
+    fn shrink_region(
+        &self,
+        index: &mut MemoryIndex<INDEX_SIZE>,
+        region: usize,
+        size: usize,
+    ) -> Result<(), IndexError> {
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            return Ok(());
+        }
+        index.shrink_region(region, size)
+    }
+
     /// Try to perform allocation based on [`Layout`], internally uses [`IndexAllocator::try_reserve`] and then perform pointer arithmetic.
+    ///
+    /// The allocation can't dip into the bytes kept for priority allocations, see [`IndexAllocator::set_reserve`].
+    #[cfg_attr(feature = "call-site", track_caller)]
     unsafe fn try_alloc(&self, layout: Layout) -> Result<*mut u8, IndexError> {
-        let offset = self.try_reserve(layout)?;
-        Ok(self.memory.get().cast::<u8>().wrapping_add(offset))
+        self.try_alloc_tier(layout, false, Placement::Low)
+    }
+
+    /// Try to perform allocation based on [`Layout`] like [`IndexAllocator::try_alloc`], with every byte set to zero.
+    ///
+    /// The memory pool of an empty allocator starts zeroed, so an allocation in bytes never handed out before
+    /// is already zero and isn't filled again.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn try_alloc_zeroed(&self, layout: Layout) -> Result<*mut u8, IndexError> {
+        let untouched = self.untouched.get();
+        let ptr = self.try_alloc(layout)?;
+        let memory_start = self.memory.get() as usize;
+        let fresh = (ptr as usize)
+            .checked_sub(memory_start)
+            .is_some_and(|offset| untouched <= offset && offset < MEMORY_SIZE);
+        if !fresh {
+            ptr.write_bytes(0, layout.size());
+        }
+
+        Ok(ptr)
+    }
+
+    /// Try to allocate `layout`, from every free byte if `priority` is set or leaving the reserve alone otherwise.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn try_alloc_tier(
+        &self,
+        layout: Layout,
+        priority: bool,
+        placement: Placement,
+    ) -> Result<*mut u8, IndexError> {
+        #[cfg(any(feature = "observer", feature = "oplog"))]
+        let used_bytes = self.used_bytes.get();
+        #[cfg(feature = "profiling")]
+        let start = self.read_cycles();
+        let offset = self.try_reserve(layout, priority, placement);
+        #[cfg(feature = "profiling")]
+        self.record_latency(latency::Operation::Reserve, start, offset.is_ok());
+        #[cfg(feature = "observer")]
+        self.observe_alloc(layout, offset, self.used_bytes.get() - used_bytes);
+        #[cfg(feature = "oplog")]
+        if let Ok(offset) = offset {
+            self.record_op(
+                oplog::LogOp::Reserve,
+                offset,
+                self.used_bytes.get() - used_bytes,
+            );
+        }
+        Ok(self.ptr_at(offset?))
+    }
+
+    /// Test if `ptr` points inside the memory pool of the allocator, or a buffer attached with [`IndexAllocator::add_region`].
+    ///
+    /// With several allocators, this tells which one a pointer must be freed through.
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let fast: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// let slow: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = fast.try_boxed([1u8, 2, 3, 4]).unwrap();
+    /// assert!(fast.owns(test_box.as_ptr()));
+    /// assert!(!slow.owns(test_box.as_ptr()));
+    /// ```
+    #[must_use]
+    pub fn owns(&self, ptr: *const u8) -> bool {
+        let offset = (ptr as usize).wrapping_sub(self.memory.get() as usize);
+        offset < MEMORY_SIZE || self.in_extra_pool(offset)
+    }
+
+    /// Get a pointer to the byte at `offset` from the start of the memory pool, such as the offset of a blob
+    /// placed by a [`ConstPoolBuilder`](const_pool::ConstPoolBuilder).
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::OutOfMemory`] if `offset` isn't in the memory pool
+    /// nor in a buffer attached with [`IndexAllocator::add_region`].
+    pub fn ptr_at_offset(&self, offset: usize) -> Result<NonNull<u8>, IndexError> {
+        if offset >= MEMORY_SIZE && !self.in_extra_pool(offset) {
+            return Err(IndexError::OutOfMemory);
+        }
+        NonNull::new(self.ptr_at(offset)).ok_or(IndexError::OutOfMemory)
+    }
+
+    /// Get the addresses spanned by the memory pool, to report pointers it doesn't own.
+    fn memory_range(&self) -> core::ops::Range<*const u8> {
+        let start = self.memory.get().cast::<u8>().cast_const();
+        start..start.wrapping_add(MEMORY_SIZE)
+    }
+
+    /// Compute the offset of `ptr` in the memory pool, failing with an [`IndexError::EmptyPtr`] if it is null
+    /// or an [`IndexError::OutOfMemory`] if the allocator doesn't own it, see [`IndexAllocator::owns`].
+    fn offset_of(&self, ptr: *const u8) -> Result<usize, IndexError> {
+        if ptr.is_null() {
+            return Err(IndexError::EmptyPtr);
+        }
+        // A pointer below the memory pool must not wrap around into it, only the extra pools use wrapped offsets.
+        let base = self.memory.get() as usize;
+        if let Some(offset) = (ptr as usize).checked_sub(base) {
+            if offset < MEMORY_SIZE {
+                return Ok(offset);
+            }
+        }
+        let offset = (ptr as usize).wrapping_sub(base);
+        if !self.in_extra_pool(offset) {
+            return Err(IndexError::OutOfMemory);
+        }
+
+        Ok(offset)
     }
 
     /// Try to free the [`MemoryRegion`] associated with the pointer given, internally using [`IndexAllocator::try_free_addr`].
+    ///
+    /// A null pointer fails with an [`IndexError::EmptyPtr`] and a pointer outside the memory pool with an [`IndexError::OutOfMemory`],
+    /// such as a pointer freed through the wrong allocator.
+    /// A pointer inside an allocation rather than at its start frees the whole allocation.
     unsafe fn try_free(&self, ptr: *mut u8) -> Result<(), IndexError> {
-        let offset = ptr as usize - self.memory.get() as usize;
-        self.try_free_addr(offset)?;
+        let offset = match self.offset_of(ptr) {
+            Ok(offset) => offset,
+            Err(err) => {
+                if err == IndexError::OutOfMemory {
+                    log_record!(
+                        error,
+                        "{ptr:p} is freed through an allocator which doesn't own it, its memory pool spans {:?}",
+                        self.memory_range()
+                    );
+                }
+                return Err(err);
+            }
+        };
+        #[cfg(feature = "redzone")]
+        if let Err(
+            err @ (redzone::IntegrityError::Underrun { offset: corrupted }
+            | redzone::IntegrityError::Overrun { offset: corrupted }),
+        ) = self.check_allocation(offset)
+        {
+            log_record!(
+                error,
+                "Heap corruption detected while freeing {ptr:p}: {err}"
+            );
+            self.poison(poison::PoisonReason::Canary { offset: corrupted });
+        }
+
+        #[cfg(feature = "profiling")]
+        let start = self.read_cycles();
+        let result = self.try_free_addr(offset);
+        #[cfg(feature = "profiling")]
+        self.record_latency(latency::Operation::Free, start, result.is_ok());
+
+        // Logged here, once the index borrow is released, as the logger may allocate.
+        if result == Err(IndexError::DoubleFree) {
+            log_record!(error, "Double free of {ptr:p}");
+        }
+
+        result
+    }
+
+    /// Fill `size` bytes of the memory pool with `byte`, starting at `from` (relative to the memory pool).
+    /// The bytes must be in a single pool, as the bytes of a region are.
+    unsafe fn fill(&self, from: usize, size: usize, byte: u8) {
+        ptr::write_bytes(self.ptr_at(from), byte, size);
+    }
+
+    /// Record that the bytes of the memory pool up to `end` were handed out, so they may no longer be zero.
+    /// The buffers attached with [`IndexAllocator::add_region`] are never known to be zero, `pool` tells them apart.
+    fn touch(&self, pool: usize, end: usize) {
+        if pool == 0 && end > self.untouched.get() {
+            self.untouched.set(end);
+        }
+    }
+
+    /// Compute how many bytes the region holding the `size` bytes at `ptr` reserves beyond them.
+    fn try_slack(&self, ptr: *const u8, size: usize) -> Result<usize, IndexError> {
+        let offset = self.offset_of(ptr)?;
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region = index.get_region(index.find_region(offset)?)?;
+
+        Ok(region.size.saturating_sub(size + 2 * REDZONE_SIZE))
+    }
+
+    /// Try to shrink the region holding `ptr` so that it ends `new_size` bytes after `ptr`,
+    /// giving the tail back to the memory pool. On failure, the index is left unchanged.
+    unsafe fn try_shrink(&self, ptr: *mut u8, new_size: usize) -> Result<(), IndexError> {
+        self.check_poison()?;
+        let offset = self.offset_of(ptr)?;
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region_index = index.find_region(offset)?;
+        // A copy, as the region is still needed once the index is mutated.
+        let region = index.region_at(region_index)?;
+
+        if !region.used {
+            return Err(IndexError::DoubleFree);
+        }
+        // The allocations in a bump arena are never freed individually.
+        if region.arena {
+            return Ok(());
+        }
+        // The blocks of the buddy mode keep their size class.
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            return Ok(());
+        }
+
+        let size = (offset - region.from)
+            .checked_add(new_size)
+            .and_then(|size| size.checked_add(REDZONE_SIZE))
+            .ok_or(IndexError::RegionTooThin)?;
+        index.shrink_region(region_index, size)?;
+
+        #[cfg(feature = "zeroize")]
+        self.wipe(region.from + size, region.size - size);
+        if let Some(byte) = self.free_scrub.get() {
+            self.fill(region.from + size, region.size - size, byte);
+        }
+        #[cfg(feature = "redzone")]
+        self.fill(
+            region.from + size - REDZONE_SIZE,
+            REDZONE_SIZE,
+            REDZONE_BYTE,
+        );
+        self.used_bytes
+            .set(self.used_bytes.get().saturating_sub(region.size - size));
+        drop(index);
+
+        self.check_watermark();
+        #[cfg(feature = "async")]
+        self.wake_pending();
+
         Ok(())
     }
 
-    unsafe fn try_alloc_value<T>(&self, val: T) -> Result<&mut T, IndexError> {
-        let layout = Layout::for_value(&val);
-        let inner_ptr = self.try_alloc(layout)?.cast::<T>();
-        ptr::write(inner_ptr, val);
-        let inner_ref = inner_ptr.as_mut().ok_or(IndexError::EmptyPtr)?;
+    /// Try to grow the region holding `ptr` so that it ends `new_size` bytes after `ptr`,
+    /// taking the missing bytes from the free region right after it. On failure, the index is left unchanged.
+    ///
+    /// The allocations of a bump arena and the blocks of the buddy mode can't grow,
+    /// and fail with an [`IndexError::RegionTooThin`].
+    unsafe fn try_grow(&self, ptr: *mut u8, new_size: usize) -> Result<(), IndexError> {
+        self.check_poison()?;
+        let offset = self.offset_of(ptr)?;
+        let budget = self.budget(false);
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region_index = index.find_region(offset)?;
+        let region = index.region_at(region_index)?;
+
+        if !region.used {
+            return Err(IndexError::DoubleFree);
+        }
+        if region.arena {
+            return Err(IndexError::RegionTooThin);
+        }
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            return Err(IndexError::RegionTooThin);
+        }
+
+        let size = (offset - region.from)
+            .checked_add(new_size)
+            .and_then(|size| size.checked_add(REDZONE_SIZE))
+            .ok_or(IndexError::NoFittingRegion)?;
+        // The slack of the region may already hold the new size.
+        let Some(extra) = size.checked_sub(region.size).filter(|extra| *extra > 0) else {
+            return Ok(());
+        };
+        if extra > budget {
+            return Err(IndexError::NoFittingRegion);
+        }
+        index.grow_region(region_index, size)?;
+
+        #[cfg(feature = "redzone")]
+        self.fill(
+            region.from + size - REDZONE_SIZE,
+            REDZONE_SIZE,
+            REDZONE_BYTE,
+        );
+        self.used_bytes.set(self.used_bytes.get() + extra);
+        self.touch(region.pool, region.from + size);
+        drop(index);
+
+        self.check_watermark();
 
-        Ok(inner_ref)
+        Ok(())
     }
 
-    unsafe fn try_free_value<T: ?Sized>(&self, val: &T) -> Result<(), IndexError> {
-        self.try_free(ptr::from_ref(val).cast_mut().cast::<u8>())
+    /// Try to split the region holding `ptr` so that its bytes from `size` bytes after `ptr` on form an allocation
+    /// of their own, freed independently. On failure, the index is left unchanged.
+    ///
+    /// The allocations of a bump arena are never freed individually, so they are left as is.
+    /// The blocks of the buddy mode can't be split, nor can the regions with the `redzone` feature,
+    /// as there would be no gap between the two parts: both fail with an [`IndexError::RegionTooThin`].
+    unsafe fn try_split(&self, ptr: *mut u8, size: usize) -> Result<(), IndexError> {
+        self.check_poison()?;
+        let offset = self.offset_of(ptr)?;
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region_index = index.find_region(offset)?;
+        let region = index.region_at(region_index)?;
+
+        if !region.used {
+            return Err(IndexError::DoubleFree);
+        }
+        if region.arena {
+            return Ok(());
+        }
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            return Err(IndexError::RegionTooThin);
+        }
+        if cfg!(feature = "redzone") {
+            return Err(IndexError::RegionTooThin);
+        }
+
+        let left_size = (offset - region.from)
+            .checked_add(size)
+            .ok_or(IndexError::RegionTooThin)?;
+        #[cfg_attr(not(feature = "stats"), allow(unused_variables))]
+        let right_index = index.split_used_region(region_index, left_size)?;
+        #[cfg(feature = "stats")]
+        {
+            let right_size = region.requested_size.saturating_sub(size);
+            index.get_region_mut(region_index)?.requested_size = size;
+            index.get_region_mut(right_index)?.requested_size = right_size;
+            self.size_histogram.record_free(region.requested_size);
+            self.size_histogram.record_alloc(size);
+            self.size_histogram.record_alloc(right_size);
+        }
+        self.allocations.set(self.allocations.get() + 1);
+
+        Ok(())
     }
 
-    /// Try to allocate the value in the memory pool and then return a [`Box`] smart pointer which manage the memory.
+    /// Try to allocate `layout`, returning the memory reserved for it, left uninitialized.
+    ///
+    /// The slice may be longer than `layout.size()` when the region holds bytes after the value,
+    /// such as the rest of a buddy block: all of them can be used. A layout taking no space reserves nothing
+    /// and gets a dangling, aligned pointer. The memory must be given back with [`IndexAllocator::try_deallocate`].
+    ///
+    /// ```
+    /// use core::alloc::Layout;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let layout = Layout::from_size_align(12, 4).unwrap();
+    /// let memory = allocator.try_allocate(layout).unwrap();
+    /// assert!(memory.len() >= 12);
+    /// assert_eq!(memory.cast::<u8>().as_ptr() as usize % 4, 0);
+    ///
+    /// unsafe { allocator.try_deallocate(memory.cast(), layout) }.unwrap();
+    /// assert_eq!(allocator.heap_stats().allocations, 0);
+    /// ```
     ///
     /// # Errors
     ///
-    /// The method return a [`IndexError`] if the allocation failed.
-    pub fn try_boxed<'a, T, U>(
-        &'a self,
-        val: U,
-    ) -> Result<Box<T, MEMORY_SIZE, INDEX_SIZE>, IndexError>
-    where
-        U: 'a,
-        T: ?Sized,
-        &'a mut T: From<&'a mut U>,
-    {
-        Box::try_new(val, self)
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, IndexError> {
+        if layout.size() == 0 {
+            let dangling = ptr::without_provenance_mut::<u8>(layout.align());
+            return Ok(NonNull::slice_from_raw_parts(
+                NonNull::new(dangling).ok_or(IndexError::EmptyPtr)?,
+                0,
+            ));
+        }
+
+        let ptr = unsafe { self.try_alloc(layout)? };
+        let len = self.try_usable_size(ptr, layout.size())?;
+        Ok(NonNull::slice_from_raw_parts(
+            NonNull::new(ptr).ok_or(IndexError::EmptyPtr)?,
+            len,
+        ))
     }
-}
 
-impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Default
-    for IndexAllocator<MEMORY_SIZE, INDEX_SIZE>
-{
-    #[must_use]
-    fn default() -> Self {
-        Self::empty()
+    /// Free the memory at `ptr` allocated with [`IndexAllocator::try_allocate`].
+    /// Layouts taking no space were never reserved and are left alone.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::DoubleFree`] if the memory was already freed,
+    /// an [`IndexError::OutOfMemory`] if the allocator doesn't own `ptr`, or another [`IndexError`] if the free failed.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been allocated by this allocator with `layout`, or a layout of the same alignment
+    /// whose size is at most the length of the slice returned, and must not be used anymore.
+    pub unsafe fn try_deallocate(
+        &self,
+        ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), IndexError> {
+        if layout.size() == 0 {
+            return Ok(());
+        }
+        self.try_free(ptr.as_ptr())
     }
-}
 
-unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> GlobalAlloc
-    for IndexAllocator<MEMORY_SIZE, INDEX_SIZE>
-{
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.try_alloc(layout).unwrap()
+    /// Compute how many bytes can be used from `ptr`, which was just allocated with `size` bytes:
+    /// the bytes up to the end of its region, or only `size` in a bump arena, which the next allocations share.
+    fn try_usable_size(&self, ptr: *const u8, size: usize) -> Result<usize, IndexError> {
+        let offset = self.offset_of(ptr)?;
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region = index.get_region(index.find_region(offset)?)?;
+        if region.arena {
+            return Ok(size);
+        }
+
+        Ok((region.end() - REDZONE_SIZE - offset).max(size))
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-        self.try_free(ptr).unwrap();
+    /// Resize the allocation at `ptr` from `old_layout` to `new_layout`, returning the pointer to the resized allocation.
+    ///
+    /// An allocation resized to an alignment its pointer already satisfies, which is always the case
+    /// when the alignment decreases, is moved as a last resort only. Shrinking gives the tail of its region back
+    /// to the memory pool, or keeps it if it can't be. Growing takes the bytes needed from the free region
+    /// right after it, if it is large enough. Otherwise, the data is copied to a new allocation and the old one is freed.
+    ///
+    /// ```
+    /// use core::alloc::{GlobalAlloc, Layout};
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+    ///
+    /// let old_layout = Layout::from_size_align(64, 16).unwrap();
+    /// let new_layout = Layout::from_size_align(16, 8).unwrap();
+    /// unsafe {
+    ///     let ptr = allocator.alloc(old_layout);
+    ///     assert_eq!(allocator.try_realloc(ptr, old_layout, new_layout), Ok(ptr));
+    ///     // The bytes given back are free right after the allocation, so it grows in place.
+    ///     assert_eq!(allocator.try_realloc(ptr, new_layout, old_layout), Ok(ptr));
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation had to move and the new allocation failed
+    /// or `ptr` couldn't be freed, the old allocation being left untouched.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been allocated by this allocator with `old_layout`,
+    /// and must not be used anymore if the method returns another pointer.
+    pub unsafe fn try_realloc(
+        &self,
+        ptr: *mut u8,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<*mut u8, IndexError> {
+        // Alignments are powers of two, masking the address avoids the panicking path of `align_offset`.
+        if (ptr as usize) & (new_layout.align() - 1) == 0 {
+            if new_layout.size() <= old_layout.size() {
+                // The allocation still holds the data if its tail can't be given back.
+                let _ = self.try_shrink(ptr, new_layout.size());
+                return Ok(ptr);
+            }
+            if self.try_grow(ptr, new_layout.size()).is_ok() {
+                return Ok(ptr);
+            }
+        }
+
+        let new_ptr = self.try_alloc(new_layout)?;
+        ptr::copy_nonoverlapping(ptr, new_ptr, old_layout.size().min(new_layout.size()));
+        if let Err(err) = self.try_free(ptr) {
+            let _ = self.try_free(new_ptr);
+            return Err(err);
+        }
+
+        Ok(new_ptr)
+    }
+
+    /// Try to allocate room for `len` values of type `T`, left uninitialized,
+    /// without reserving anything if it takes no space.
+    ///
+    /// The size of the array is computed with overflow checks, so a `len` too large for `T` fails cleanly.
+    /// The array must be given back with [`IndexAllocator::try_free_array`].
+    ///
+    /// ```
+    /// use index_alloc::{IndexAllocator, IndexError};
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let array = allocator.try_alloc_array::<u32>(4).unwrap();
+    /// assert_eq!(array.len(), 4);
+    /// assert_eq!(
+    ///     allocator.try_alloc_array::<u32>(usize::MAX / 2).err(),
+    ///     Some(IndexError::LayoutOverflow)
+    /// );
+    ///
+    /// unsafe { allocator.try_free_array(array.cast::<u32>(), array.len()) }.unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::LayoutOverflow`] if the size of the array overflows,
+    /// or another [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_alloc_array<T>(&self, len: usize) -> Result<NonNull<[T]>, IndexError> {
+        let layout = Layout::array::<T>(len).map_err(|_| IndexError::LayoutOverflow)?;
+        let inner_ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(unsafe { self.try_alloc(layout)? }.cast::<T>())
+                .ok_or(IndexError::EmptyPtr)?
+        };
+
+        Ok(NonNull::slice_from_raw_parts(inner_ptr, len))
+    }
+
+    /// Try to allocate room for `len` values of type `T` like [`IndexAllocator::try_alloc_array`],
+    /// with every byte set to zero.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::LayoutOverflow`] if the size of the array overflows,
+    /// or another [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_alloc_array_zeroed<T>(&self, len: usize) -> Result<NonNull<[T]>, IndexError> {
+        let layout = Layout::array::<T>(len).map_err(|_| IndexError::LayoutOverflow)?;
+        let inner_ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(unsafe { self.try_alloc_zeroed(layout)? }.cast::<T>())
+                .ok_or(IndexError::EmptyPtr)?
+        };
+
+        Ok(NonNull::slice_from_raw_parts(inner_ptr, len))
+    }
+
+    /// Free an array of `len` values allocated with [`IndexAllocator::try_alloc_array`], without dropping the values.
+    /// Arrays taking no space were never reserved and are left alone.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the array couldn't be freed.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and `len` must be the start and the length of an array allocated by this allocator,
+    /// which must not be accessed afterwards.
+    pub unsafe fn try_free_array<T>(&self, ptr: NonNull<T>, len: usize) -> Result<(), IndexError> {
+        if mem::size_of::<T>() == 0 || len == 0 {
+            return Ok(());
+        }
+
+        self.try_free(ptr.as_ptr().cast::<u8>())
+    }
+
+    /// Try to allocate `layout` at the highest address possible, from the tail of the highest free region fitting it,
+    /// whereas the other allocations take the lowest free region fitting them.
+    ///
+    /// Allocating the short-lived or stack-like values this way makes them grow down from the top of the memory pool,
+    /// away from the other allocations growing up from its bottom, so that they don't fragment the free memory between them.
+    /// The allocation is freed like any other, such as with [`GlobalAlloc::dealloc`].
+    /// A `layout` taking no space isn't reserved, its pointer is dangling.
+    ///
+    /// Bump mode isn't used, and in buddy mode the allocation takes the smallest fitting block as usual.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::alloc::{GlobalAlloc, Layout};
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+    ///
+    /// let layout = Layout::new::<[u32; 4]>();
+    /// let frame = allocator.try_reserve_high(layout).unwrap();
+    /// let value = allocator.try_boxed(1u32).unwrap();
+    /// assert!(core::ptr::from_ref(&*value).cast::<u8>() < frame.as_ptr().cast_const());
+    ///
+    /// unsafe { allocator.dealloc(frame.as_ptr(), layout) };
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_reserve_high(&self, layout: Layout) -> Result<NonNull<u8>, IndexError> {
+        if layout.size() == 0 {
+            return NonNull::new(ptr::without_provenance_mut(layout.align()))
+                .ok_or(IndexError::EmptyPtr);
+        }
+
+        let inner_ptr = unsafe { self.try_alloc_tier(layout, false, Placement::High)? };
+        NonNull::new(inner_ptr).ok_or(IndexError::EmptyPtr)
+    }
+
+    /// Inlined so that `val` is written to the memory pool from the frame of the caller, rather than copied to one more frame.
+    #[inline(always)]
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn try_alloc_value<T>(&self, val: T) -> Result<NonNull<T>, IndexError> {
+        let inner_ptr = self.try_alloc_array::<T>(1)?.cast::<T>();
+        ptr::write(inner_ptr.as_ptr(), val);
+
+        Ok(inner_ptr)
+    }
+
+    /// Free a value allocated with [`IndexAllocator::try_alloc_value`] or [`IndexAllocator::try_alloc_array`].
+    /// Values taking no space were never reserved and are left alone, but a null pointer fails with an [`IndexError::EmptyPtr`].
+    unsafe fn try_free_value<T: ?Sized>(&self, val: *mut T) -> Result<(), IndexError> {
+        if val.is_null() {
+            return Err(IndexError::EmptyPtr);
+        }
+        if mem::size_of_val(&*val) == 0 {
+            return Ok(());
+        }
+
+        self.try_free(val.cast::<u8>())
+    }
+
+    /// Fill every free region of the memory pool with `byte`.
+    ///
+    /// Used regions are left untouched, so this can be called while allocations are alive.
+    /// Combined with [`IndexAllocator::set_free_scrub`], it makes any read from unallocated memory
+    /// produce an obvious poison pattern, which helps catching use-after-free in debug builds.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn clear_to_pattern(&self, byte: u8) -> Result<(), IndexError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        for region in index.regions().filter(|region| !region.used) {
+            unsafe { self.fill(region.from, region.size, byte) };
+            self.touch(region.pool, region.end());
+        }
+
+        Ok(())
+    }
+
+    /// Touch every page of `page_size` bytes spanned by the free memory, so that an operating system committing memory lazily,
+    /// on the first write to each page, commits it now rather than during a later allocation.
+    ///
+    /// A byte of each page is read and written back, so the content of the memory is left unchanged.
+    /// The pages only spanned by allocations are left alone, as they are committed once written by their owner.
+    /// A `page_size` of 0 is handled as 1, touching every free byte.
+    ///
+    /// ```
+    /// use index_alloc::sync::SingleThreaded;
+    ///
+    /// static ALLOCATOR: SingleThreaded<{ 4 * 4096 }, 64> = unsafe { SingleThreaded::empty() };
+    ///
+    /// // Commit the memory pool during the startup, not during the first allocations.
+    /// ALLOCATOR.prefault(4096).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn prefault(&self, page_size: usize) -> Result<(), IndexError> {
+        self.touch_pages(page_size, |offset| unsafe {
+            let byte = self.ptr_at(offset);
+            byte.write_volatile(byte.read_volatile());
+        })
+    }
+
+    /// Call `touch` with an offset in each page of `page_size` bytes spanned by the free regions, see [`IndexAllocator::prefault`].
+    fn touch_pages(
+        &self,
+        page_size: usize,
+        mut touch: impl FnMut(usize),
+    ) -> Result<(), IndexError> {
+        let page_size = page_size.max(1);
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        for region in index
+            .regions()
+            .filter(|region| !region.used && region.size > 0)
+        {
+            // The first byte of the region, then the first byte of every page starting in it.
+            let start = self.ptr_at(region.from) as usize;
+            let mut offset = region.from;
+            while offset < region.end() {
+                touch(offset);
+                let in_page = (start + (offset - region.from)) % page_size;
+                offset = match offset.checked_add(page_size - in_page) {
+                    Some(next) => next,
+                    None => break,
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy the bytes of the allocation holding `ptr`, from `ptr` on, to `dst`, without knowing the type of the allocation.
+    /// At most the bytes left in the allocation after `ptr` are copied, even if `dst` is larger.
+    ///
+    /// This suits crash dumps, where the allocations are read as raw bytes.
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    /// let mut dump = [0; 8];
+    /// let copied = allocator.copy_out(test_box.as_ptr(), &mut dump).unwrap();
+    /// assert_eq!(&dump[..copied], &[1, 2, 3, 4][..copied]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::EmptyPtr`] if `ptr` is null,
+    /// an [`IndexError::OutOfMemory`] if it isn't in the memory pool,
+    /// an [`IndexError::NoSuchRegion`] if it isn't in a used region,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    /// `ptr` needs no alignment: any byte of an allocation is accepted.
+    pub fn copy_out(&self, ptr: *const u8, dst: &mut [u8]) -> Result<usize, IndexError> {
+        let (offset, available) = self.allocation_bytes(ptr)?;
+        let len = dst.len().min(available);
+        unsafe {
+            ptr::copy_nonoverlapping(self.ptr_at(offset), dst.as_mut_ptr(), len);
+        }
+
+        Ok(len)
+    }
+
+    /// Copy `src` to the allocation holding `ptr`, from `ptr` on, without knowing the type of the allocation.
+    /// At most the bytes left in the allocation after `ptr` are written, even if `src` is larger.
+    ///
+    /// This suits pre-seeding buffers, for instance from flash.
+    ///
+    /// # Safety
+    ///
+    /// The bytes written must be valid for the type of the allocation,
+    /// and the allocation must not be borrowed while they are written.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::EmptyPtr`] if `ptr` is null,
+    /// an [`IndexError::OutOfMemory`] if it isn't in the memory pool,
+    /// an [`IndexError::NoSuchRegion`] if it isn't in a used region,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    /// `ptr` needs no alignment: any byte of an allocation is accepted.
+    pub unsafe fn copy_in(&self, ptr: *mut u8, src: &[u8]) -> Result<usize, IndexError> {
+        let (offset, available) = self.allocation_bytes(ptr)?;
+        let len = src.len().min(available);
+        ptr::copy_nonoverlapping(src.as_ptr(), self.ptr_at(offset), len);
+
+        Ok(len)
+    }
+
+    /// Find the allocation holding `ptr`, returning the offset of `ptr` in the memory pool
+    /// and the number of bytes of the allocation from `ptr` on (the gap after it excluded).
+    fn allocation_bytes(&self, ptr: *const u8) -> Result<(usize, usize), IndexError> {
+        let offset = self.offset_of(ptr)?;
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region = index.get_region(index.find_region(offset)?)?;
+        if !region.used {
+            return Err(IndexError::NoSuchRegion);
+        }
+
+        Ok((offset, (region.end() - REDZONE_SIZE).saturating_sub(offset)))
+    }
+
+    /// Set the byte freed regions are overwritten with, or `None` to leave freed memory as is (the default).
+    ///
+    /// A sentinel such as `0xDD` makes reads from freed memory easy to spot,
+    /// the same way debug allocators do on desktop.
+    pub fn set_free_scrub(&self, byte: Option<u8>) {
+        self.free_scrub.set(byte);
+    }
+
+    /// Merge the adjacent free regions of the index now, without freeing anything,
+    /// to make room for a large allocation.
+    ///
+    /// Frees already merge the region they free with its free neighbours, so this only helps
+    /// with an index which was built unsorted, or in lazy merge mode (see [`IndexAllocator::set_lazy_merge`]).
+    /// Allocations are never moved.
+    /// Unlike [`IndexAllocator::reset`], every allocation stays valid.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn compact(&self) -> Result<(), IndexError> {
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        // The blocks of the buddy mode only merge with their buddy, which freeing them already does.
+        #[cfg(feature = "buddy")]
+        if self.buddy {
+            return Ok(());
+        }
+        index.sort_merge();
+        self.pending_merges.set(0);
+        drop(index);
+
+        #[cfg(feature = "async")]
+        self.wake_pending();
+
+        Ok(())
+    }
+
+    /// Free every allocation at once, making the whole memory pool available again.
+    ///
+    /// The reset starts a new epoch of the allocator, see [`IndexAllocator::epoch`].
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated before the reset may be accessed afterwards, as its memory may be handed out again.
+    /// Without the `generations` feature, the smart pointers outstanding must not be dropped either,
+    /// or they would free the allocations made after the reset: they have to be forgotten with [`core::mem::forget`].
+    /// With the feature, dropping them does nothing.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub unsafe fn reset(&self) -> Result<(), IndexError> {
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        *index = self.empty_index()?;
+
+        #[cfg(feature = "zeroize")]
+        {
+            self.wipe(0, MEMORY_SIZE);
+            self.wipe_extra_pools();
+        }
+        if let Some(byte) = self.free_scrub.get() {
+            self.fill(0, MEMORY_SIZE, byte);
+            self.fill_extra_pools(byte);
+            self.touch(0, MEMORY_SIZE);
+        }
+        self.used_bytes.set(0);
+        self.allocations.set(0);
+        self.pending_merges.set(0);
+        #[cfg(feature = "stats")]
+        self.size_histogram.clear_live();
+        self.epoch.set(self.epoch.get().wrapping_add(1));
+        self.bump.set(None);
+        drop(index);
+
+        self.check_watermark();
+        #[cfg(feature = "async")]
+        self.wake_pending();
+
+        Ok(())
+    }
+
+    /// Borrow the memory index, so that every operation needing it fails with an [`IndexError::IndexAlreadyBorrowed`]
+    /// until the borrow is released, to exercise the failure paths in integration tests.
+    #[cfg(feature = "index-fixtures")]
+    pub fn borrow_index(&self) -> core::cell::RefMut<'_, MemoryIndex<INDEX_SIZE>> {
+        self.index.borrow_mut()
+    }
+
+    /// Return the current epoch of the allocator, which starts at 0 and increases with every [`IndexAllocator::reset`].
+    #[must_use]
+    pub fn epoch(&self) -> usize {
+        self.epoch.get()
+    }
+
+    /// Test if a smart pointer failed to free its memory when dropped, see [`IndexAllocator::try_boxed_no_panic`].
+    ///
+    /// The memory which couldn't be freed is leaked: it stays reserved until the allocator is dropped.
+    /// Unlike [`IndexAllocator::poison`], this flag doesn't stop the allocator.
+    #[must_use]
+    pub fn has_failed_drop(&self) -> bool {
+        self.failed_drop.get()
+    }
+
+    /// Reset the flag set when a smart pointer failed to free its memory, see [`IndexAllocator::has_failed_drop`].
+    pub fn clear_failed_drop(&self) {
+        self.failed_drop.set(false);
+    }
+
+    /// Record an error which happened while a smart pointer was freeing its memory in its `Drop` implementation,
+    /// where it can't be returned.
+    ///
+    /// The failure is flagged, see [`IndexAllocator::has_failed_drop`], and the error is logged with the `log` feature.
+    /// With the `lenient-drop` feature, the memory is also recorded as leaked, see `leak::leaked_drops`.
+    /// Return whether there was an error.
+    fn flag_drop_error(&self, what: &str, result: Result<(), IndexError>) -> bool {
+        match result {
+            Ok(()) => false,
+            Err(err) => {
+                self.failed_drop.set(true);
+                log_record!(error, "Failed to free {what}: {err}");
+                #[cfg(feature = "lenient-drop")]
+                self.record_leak(err);
+                true
+            }
+        }
+    }
+
+    /// Record an error which happened while a smart pointer was freeing its memory in its `Drop` implementation,
+    /// see [`IndexAllocator::flag_drop_error`], and panic in debug builds unless the `lenient-drop` feature is enabled
+    /// or the allocator was stopped with [`IndexAllocator::poison`], which already reported the corruption.
+    fn report_drop_error(&self, what: &str, result: Result<(), IndexError>) {
+        let failed = self.flag_drop_error(what, result);
+        debug_assert!(
+            cfg!(feature = "lenient-drop") || !failed || result == Err(IndexError::Poisoned),
+            "Failed to free {what}"
+        );
+    }
+
+    /// Free a value from the `Drop` implementation of a smart pointer, where errors can't be returned,
+    /// see [`IndexAllocator::report_drop_error`].
+    ///
+    /// Every smart pointer frees its memory through this method, so they all follow the same drop policy.
+    unsafe fn drop_free<T: ?Sized>(&self, what: &str, val: *mut T) {
+        let result = self.try_free_value(val);
+        // A smart pointer holding the wrong allocator, for instance when built with `from_raw_ref`.
+        debug_assert!(
+            cfg!(feature = "lenient-drop") || result != Err(IndexError::OutOfMemory),
+            "{what} at {val:p} isn't in the memory pool of its allocator, spanning {:?}",
+            self.memory_range()
+        );
+        self.report_drop_error(what, result);
+    }
+
+    /// Try to allocate the value in the memory pool and then return a [`Box`] smart pointer which manage the memory.
+    ///
+    /// The [`Box`] only needs to live as long as the value it holds, not as long as the allocator:
+    /// a value borrowing from shorter-lived data can be boxed in a long-lived allocator.
+    ///
+    /// ```
+    /// use index_alloc::sync::SingleThreaded;
+    ///
+    /// static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+    ///
+    /// struct Header<'p>(&'p [u8]);
+    ///
+    /// let packet = [0x42, 0x13, 0x37];
+    /// let header = ALLOCATOR.try_boxed(Header(&packet[..2])).unwrap();
+    /// assert_eq!(header.0, &[0x42, 0x13]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_boxed<'a, 'b, T, U>(
+        &'a self,
+        val: U,
+    ) -> Result<Box<'b, T, MEMORY_SIZE, INDEX_SIZE>, IndexError>
+    where
+        'a: 'b,
+        U: 'b,
+        T: ?Sized,
+        &'b mut T: From<&'b mut U>,
+    {
+        Box::try_new(val, self)
+    }
+
+    /// Allocate the value in the memory pool and then return a [`Box`] smart pointer which manage the memory,
+    /// see [`IndexAllocator::try_boxed`].
+    ///
+    /// # Panics
+    ///
+    /// The method panics if the allocation failed, the panic location being the caller of [`IndexAllocator::boxed`].
+    #[track_caller]
+    pub fn boxed<'a, 'b, T, U>(&'a self, val: U) -> Box<'b, T, MEMORY_SIZE, INDEX_SIZE>
+    where
+        'a: 'b,
+        U: 'b,
+        T: ?Sized,
+        &'b mut T: From<&'b mut U>,
+    {
+        Box::new(val, self)
+    }
+
+    /// Try to allocate `len` zeroed bytes in the memory pool and then return a [`Box`] smart pointer which manage the memory,
+    /// see [`Box::try_new_slice_zeroed`].
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let buffer = allocator.try_boxed_slice_zeroed(16).unwrap();
+    /// assert_eq!(*buffer, [0; 16]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_boxed_slice_zeroed(
+        &self,
+        len: usize,
+    ) -> Result<Box<'_, [u8], MEMORY_SIZE, INDEX_SIZE>, IndexError> {
+        Box::try_new_slice_zeroed(len, self)
+    }
+
+    /// Try to allocate the value in the memory pool like [`IndexAllocator::try_boxed`],
+    /// unless it would leave less than `min_reserve` free bytes, keeping headroom for critical allocations.
+    ///
+    /// The bytes the allocation takes are the size of the value and the gaps around it with the `redzone` feature,
+    /// the alignment padding isn't accounted for. Values taking no space are never refused.
+    ///
+    /// ```
+    /// use index_alloc::{IndexAllocator, IndexError};
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let _message = allocator.try_boxed_checked([0u8; 16], 16).unwrap();
+    /// assert_eq!(allocator.try_boxed_checked([0u8; 40], 16).err(), Some(IndexError::NoFittingRegion));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::NoFittingRegion`] if the allocation would leave less than `min_reserve` free bytes,
+    /// or an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_boxed_checked<'a, 'b, T, U>(
+        &'a self,
+        val: U,
+        min_reserve: usize,
+    ) -> Result<Box<'b, T, MEMORY_SIZE, INDEX_SIZE>, IndexError>
+    where
+        'a: 'b,
+        U: 'b,
+        T: ?Sized,
+        &'b mut T: From<&'b mut U>,
+    {
+        let size = mem::size_of::<U>();
+        if size > 0 {
+            let left = self
+                .heap_stats()
+                .free_bytes
+                .checked_sub(size + 2 * REDZONE_SIZE);
+            if left.is_none_or(|left| left < min_reserve) {
+                return Err(IndexError::NoFittingRegion);
+            }
+        }
+
+        Box::try_new(val, self)
+    }
+
+    /// Try to allocate the value in the memory pool and then return a [`Box`] of the type `coerce` converts it to,
+    /// typically a trait object for which no [`From`] conversion can be implemented, such as `dyn Fn()`.
+    ///
+    /// Like with [`IndexAllocator::try_boxed`], the value only needs to outlive the [`Box`],
+    /// so the trait object may borrow local data.
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let base = 40;
+    /// let add = allocator
+    ///     .try_boxed_as(move |x: u32| base + x, |f| f as &mut dyn Fn(u32) -> u32)
+    ///     .unwrap();
+    /// assert_eq!(add(2), 42);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`IndexError`] if the allocation failed,
+    /// or an [`IndexError::OutOfMemory`] if `coerce` doesn't return a reference to the whole value it was given,
+    /// in which case the value is dropped and freed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_boxed_as<'a, 'b, T, U>(
+        &'a self,
+        val: U,
+        coerce: fn(&'b mut U) -> &'b mut T,
+    ) -> Result<Box<'b, T, MEMORY_SIZE, INDEX_SIZE>, IndexError>
+    where
+        'a: 'b,
+        U: 'b,
+        T: ?Sized,
+    {
+        let inner_ptr = unsafe { self.try_alloc_value(val)? };
+        let coerced = coerce(unsafe { &mut *inner_ptr.as_ptr() });
+
+        // Only the value itself can be owned by the box, not a reference to some other data.
+        if !ptr::addr_eq(ptr::from_mut(coerced), inner_ptr.as_ptr())
+            || mem::size_of_val(coerced) != mem::size_of::<U>()
+        {
+            unsafe {
+                ptr::drop_in_place(inner_ptr.as_ptr());
+                self.try_free_value(inner_ptr.as_ptr())?;
+            }
+            return Err(IndexError::OutOfMemory);
+        }
+
+        Ok(unsafe { Box::from_raw_ref(coerced, self) })
+    }
+
+    /// Try to allocate the value in the memory pool and then return a [`Box`] which never panics when dropped,
+    /// for `panic = "abort"` targets where an abort is worse than a leak.
+    ///
+    /// A [`Box`] normally panics in debug builds if freeing its memory fails when dropped (release builds only log the error).
+    /// This one silently leaks the memory instead, which stays reserved until the allocator is dropped,
+    /// and flags the failure so that it can be detected with [`IndexAllocator::has_failed_drop`].
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed_no_panic([1u8, 2, 3, 4]).unwrap();
+    /// drop(test_box);
+    /// assert!(!allocator.has_failed_drop());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_boxed_no_panic<'a, 'b, T, U>(
+        &'a self,
+        val: U,
+    ) -> Result<Box<'b, T, MEMORY_SIZE, INDEX_SIZE>, IndexError>
+    where
+        'a: 'b,
+        U: 'b,
+        T: ?Sized,
+        &'b mut T: From<&'b mut U>,
+    {
+        let mut boxed = Box::try_new(val, self)?;
+        boxed.no_panic = true;
+        Ok(boxed)
+    }
+
+    /// Try to allocate the value in the memory pool and leak it right away, see [`Box::leak`].
+    ///
+    /// This suits singletons living as long as a `static` allocator.
+    ///
+    /// ```
+    /// use index_alloc::sync::SingleThreaded;
+    ///
+    /// static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+    ///
+    /// let counter: &'static mut u32 = ALLOCATOR.try_leak(0).unwrap();
+    /// *counter += 1;
+    /// assert_eq!(*counter, 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_leak<'a, T>(&'a self, val: T) -> Result<&'a mut T, IndexError>
+    where
+        T: 'a,
+    {
+        Ok(Box::leak(self.try_boxed(val)?))
+    }
+
+    /// Reserve room for a value and let `init` initialize it in place, at its final address,
+    /// then return it pinned in a [`Box`].
+    ///
+    /// As the value never moves, `init` can store pointers to the value itself,
+    /// which is the building block of self-referential and intrusive data structures.
+    ///
+    /// ```
+    /// use core::marker::PhantomPinned;
+    /// use core::ptr;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// struct SelfRef {
+    ///     data: [u8; 4],
+    ///     first: *const u8,
+    ///     _pinned: PhantomPinned,
+    /// }
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let pinned = unsafe {
+    ///     allocator.try_pin_with::<SelfRef, _>(|slot| {
+    ///         let this = slot.get_unchecked_mut().as_mut_ptr();
+    ///         ptr::addr_of_mut!((*this).data).write([1, 2, 3, 4]);
+    ///         ptr::addr_of_mut!((*this).first).write(ptr::addr_of!((*this).data).cast());
+    ///         ptr::addr_of_mut!((*this)._pinned).write(PhantomPinned);
+    ///     })
+    /// }
+    /// .unwrap();
+    /// assert_eq!(unsafe { *pinned.first }, 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`IndexError`] if the allocation failed, without calling `init`.
+    ///
+    /// # Safety
+    ///
+    /// `init` must fully initialize the value. If it panics, the memory is freed without dropping the value.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub unsafe fn try_pin_with<'a, T, F>(
+        &'a self,
+        init: F,
+    ) -> Result<Pin<Box<'a, T, MEMORY_SIZE, INDEX_SIZE>>, IndexError>
+    where
+        T: 'a,
+        F: FnOnce(Pin<&mut MaybeUninit<T>>),
+    {
+        let inner_ptr = self.try_alloc_array::<T>(1)?.cast::<T>();
+        let guard = UnwindGuard::new(self, inner_ptr.as_ptr());
+        init(Pin::new_unchecked(
+            &mut *inner_ptr.as_ptr().cast::<MaybeUninit<T>>(),
+        ));
+        guard.disarm();
+
+        Ok(Box::into_pin(Box::from_raw_ref(
+            &mut *inner_ptr.as_ptr(),
+            self,
+        )))
+    }
+}
+
+/// Free an allocation if a panic unwinds while user code initializes it, unless disarmed once the code returned.
+///
+/// The value is only dropped first if it was fully written before the user code ran, see [`UnwindGuard::initialized`].
+pub(crate) struct UnwindGuard<'a, T: ?Sized, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    val: *mut T,
+    initialized: bool,
+}
+
+impl<'a, T: ?Sized, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    UnwindGuard<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Guard `val`, allocated with [`IndexAllocator::try_alloc_value`] or [`IndexAllocator::try_alloc_array`].
+    pub(crate) fn new(allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>, val: *mut T) -> Self {
+        Self {
+            allocator,
+            val,
+            initialized: false,
+        }
+    }
+
+    /// Guard `val` like [`UnwindGuard::new`], the value being already written, so that it is dropped before being freed.
+    pub(crate) fn initialized(
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+        val: *mut T,
+    ) -> Self {
+        Self {
+            allocator,
+            val,
+            initialized: true,
+        }
+    }
+
+    /// Keep the allocation, the user code having returned.
+    pub(crate) fn disarm(self) {
+        mem::forget(self);
+    }
+}
+
+impl<T: ?Sized, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Drop
+    for UnwindGuard<'_, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { ptr::drop_in_place(self.val) };
+        }
+        // A failure can't be reported while unwinding, the allocation is leaked then.
+        let _ = unsafe { self.allocator.try_free_value(self.val) };
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Default
+    for IndexAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> GlobalAlloc
+    for IndexAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.try_alloc(layout) {
+            Ok(ptr) => ptr,
+            Err(err) => {
+                log_record!(
+                    warn,
+                    "Failed to allocate {layout:?}: {err} (largest free block: {} bytes)",
+                    self.largest_free_block().unwrap_or_default()
+                );
+                ptr::null_mut()
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match self.try_alloc_zeroed(layout) {
+            Ok(ptr) => ptr,
+            Err(err) => {
+                log_record!(
+                    warn,
+                    "Failed to allocate {layout:?}: {err} (largest free block: {} bytes)",
+                    self.largest_free_block().unwrap_or_default()
+                );
+                ptr::null_mut()
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        // Freeing a null pointer does nothing, as with the C `free`.
+        if ptr.is_null() {
+            return;
+        }
+        // A global allocator must not unwind, the errors are only logged, such as a pointer it doesn't own.
+        let _ = self.try_free(ptr);
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        self.try_realloc(ptr, layout, new_layout)
+            .unwrap_or(ptr::null_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+    use crate::index::MemoryRegion;
+
+    /// Read the whole memory pool of an allocator.
+    pub(crate) fn memory<const MEMORY_SIZE: usize, const INDEX_SIZE: usize>(
+        allocator: &IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> &[u8; MEMORY_SIZE] {
+        unsafe { &*allocator.memory.get() }
+    }
+
+    #[test]
+    // Ignore MIRI because the allocator inner memory is directly read, wich MIRI don't like.
+    #[cfg_attr(miri, ignore)]
+    fn test_free_scrub() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        allocator.set_free_scrub(Some(0xDD));
+
+        let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+        drop(test_box);
+
+        assert_eq!(&memory(&allocator)[..4], &[0xDD; 4]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
+    fn test_clear_to_pattern() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+        allocator.clear_to_pattern(0xAA).unwrap();
+
+        assert_eq!(*test_box, [1, 2, 3, 4]);
+        assert_eq!(&memory(&allocator)[4..], &[0xAA; 60]);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut regions = [const { None }; 8];
+        regions[0] = Some(MemoryRegion::new(32, 16, false));
+        regions[1] = Some(MemoryRegion::new(0, 16, true));
+        regions[2] = Some(MemoryRegion::new(16, 16, false));
+        regions[3] = Some(MemoryRegion::new(48, 16, false));
+        let allocator: IndexAllocator<64, 8> =
+            IndexAllocator::new([0; 64], MemoryIndex::new(regions));
+        assert_eq!(allocator.largest_free_block(), Ok(16));
+
+        allocator.compact().unwrap();
+        assert_eq!(allocator.largest_free_block(), Ok(48));
+        assert!(allocator.index.borrow().is_sorted());
+
+        let index = allocator.index.borrow();
+        assert_eq!(index.get_region(0), Ok(MemoryRegion::new(0, 16, true)));
+    }
+
+    #[test]
+    fn test_copy_in_out() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let buffer = allocator.try_boxed([0u8; 8]).unwrap();
+        let ptr = buffer.as_ptr().cast_mut();
+        let slack = buffer.slack().unwrap();
+        assert_eq!(
+            unsafe { allocator.copy_in(ptr, &[0xAB; 32]) },
+            Ok(8 + slack)
+        );
+        assert_eq!(*buffer, [0xAB; 8]);
+
+        let mut dump = [0; 32];
+        assert_eq!(
+            allocator.copy_out(ptr.wrapping_add(2), &mut dump),
+            Ok(6 + slack)
+        );
+        assert_eq!(dump[..6], [0xAB; 6]);
+        assert_eq!(dump[6 + slack..], [0; 32][6 + slack..]);
+
+        let outside = allocator.memory.get().cast::<u8>().wrapping_add(256);
+        assert_eq!(
+            allocator.copy_out(outside, &mut dump),
+            Err(IndexError::OutOfMemory)
+        );
+        let before = allocator.memory.get().cast::<u8>().wrapping_sub(1);
+        assert_eq!(
+            unsafe { allocator.copy_in(before, &dump) },
+            Err(IndexError::OutOfMemory)
+        );
+        let free = ptr.wrapping_add(32);
+        assert_eq!(
+            allocator.copy_out(free, &mut dump),
+            Err(IndexError::NoSuchRegion)
+        );
+    }
+
+    #[test]
+    fn test_realloc_shrink_in_place() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let old_layout = Layout::from_size_align(64, 16).unwrap();
+        let new_layout = Layout::from_size_align(16, 8).unwrap();
+        unsafe {
+            let ptr = allocator.alloc(old_layout);
+            ptr.write_bytes(0xAB, 64);
+            assert_eq!(allocator.try_realloc(ptr, old_layout, new_layout), Ok(ptr));
+            assert_eq!(*ptr.add(15), 0xAB);
+            assert_eq!(allocator.heap_stats().allocations, 1);
+            // The region may keep the padding aligning it to 16 bytes, but not the tail.
+            assert!(allocator.heap_stats().used_bytes < 16 + 16 + 2 * REDZONE_SIZE);
+
+            // The tail given back is right after the allocation, which grows in place.
+            let grown = allocator.realloc(ptr, new_layout, 32);
+            assert_eq!(grown, ptr);
+            assert_eq!(*grown.add(15), 0xAB);
+            assert_eq!(allocator.heap_stats().allocations, 1);
+        }
+    }
+
+    /// Check that the regions of the memory pool are contiguous, with no two free regions side by side,
+    /// and that the used bytes match the used regions.
+    fn assert_index_consistent<const MEMORY_SIZE: usize, const INDEX_SIZE: usize>(
+        allocator: &IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) {
+        let index = allocator.index.borrow();
+        let mut regions: std::vec::Vec<MemoryRegion> = index.regions().collect();
+        regions.sort_by_key(|region| region.from);
+
+        let mut end = 0;
+        for pair in regions.windows(2) {
+            assert!(
+                pair[0].used || pair[1].used,
+                "unmerged free regions {pair:?}"
+            );
+        }
+        for region in &regions {
+            assert_eq!(region.from, end);
+            end = region.end();
+        }
+        assert_eq!(end, MEMORY_SIZE);
+        let used_bytes: usize = regions
+            .iter()
+            .filter(|region| region.used)
+            .map(|region| region.size)
+            .sum();
+        assert_eq!(allocator.heap_stats().used_bytes, used_bytes);
+    }
+
+    #[test]
+    fn test_realloc_grow_in_place() {
+        let allocator: IndexAllocator<512, 8> = IndexAllocator::empty();
+        let layout = Layout::array::<u8>(16).unwrap();
+
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            ptr.write_bytes(0xAB, 16);
+
+            // Mixed grow and shrink sequences keep the allocation, its data and the index in place.
+            let mut size = 16;
+            for new_size in [48, 24, 96, 8, 64, 32, 128] {
+                let old_layout = Layout::array::<u8>(size).unwrap();
+                assert_eq!(allocator.realloc(ptr, old_layout, new_size), ptr);
+                assert_eq!(*ptr.add(7), 0xAB);
+                assert_index_consistent(&allocator);
+                size = new_size;
+            }
+            assert_eq!(allocator.heap_stats().allocations, 1);
+
+            // An allocation right after it moves the allocation instead.
+            let blocker = allocator.alloc(layout);
+            assert_index_consistent(&allocator);
+            let old_layout = Layout::array::<u8>(size).unwrap();
+            let moved = allocator.realloc(ptr, old_layout, size + 16);
+            assert!(!moved.is_null());
+            assert_ne!(moved, ptr);
+            assert_eq!(*moved.add(7), 0xAB);
+            assert_eq!(allocator.heap_stats().allocations, 2);
+            assert_index_consistent(&allocator);
+
+            allocator.dealloc(blocker, layout);
+            allocator.dealloc(moved, Layout::array::<u8>(size + 16).unwrap());
+            assert_index_consistent(&allocator);
+            assert_eq!(allocator.heap_stats().used_bytes, 0);
+        }
+    }
+
+    #[test]
+    fn test_realloc_grow_respects_reserve() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+        let layout = Layout::array::<u8>(16).unwrap();
+
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            allocator.set_reserve(64);
+            // Growing into the reserve fails, leaving the allocation as it was.
+            assert!(allocator.realloc(ptr, layout, 96).is_null());
+            assert_index_consistent(&allocator);
+            assert_eq!(allocator.realloc(ptr, layout, 32), ptr);
+        }
+    }
+
+    #[test]
+    fn test_alloc_zeroed_reuses_zero_memory() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        let layout = Layout::array::<u8>(64).unwrap();
+        let is_zero = |ptr: *mut u8| {
+            unsafe { core::slice::from_raw_parts(ptr, 64) }
+                .iter()
+                .all(|&byte| byte == 0)
+        };
+
+        unsafe {
+            // Fresh bytes are known to be zero.
+            let fresh = allocator.alloc_zeroed(layout);
+            assert!(is_zero(fresh));
+            assert!(allocator.untouched.get() >= 64);
+
+            // Freed bytes are filled with garbage, and zeroed again once reallocated.
+            fresh.write_bytes(0xAB, 64);
+            allocator.set_free_scrub(Some(0xCD));
+            allocator.dealloc(fresh, layout);
+            let reused = allocator.alloc_zeroed(layout);
+            assert_eq!(reused, fresh);
+            assert!(is_zero(reused));
+
+            // An allocation straddling the bytes handed out before is zeroed as well.
+            allocator.dealloc(reused, layout);
+            let larger = Layout::array::<u8>(128).unwrap();
+            let straddling = allocator.alloc_zeroed(larger);
+            assert!(core::slice::from_raw_parts(straddling, 128)
+                .iter()
+                .all(|&byte| byte == 0));
+            allocator.dealloc(straddling, larger);
+        }
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_alloc_zeroed_after_clear_to_pattern() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        let layout = Layout::array::<u8>(64).unwrap();
+        allocator.clear_to_pattern(0xAA).unwrap();
+
+        unsafe {
+            // The pattern covers bytes never handed out, they aren't zero anymore.
+            let ptr = allocator.alloc_zeroed(layout);
+            assert!(!ptr.is_null());
+            assert!(core::slice::from_raw_parts(ptr, 64)
+                .iter()
+                .all(|&byte| byte == 0));
+            allocator.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn test_alloc_zeroed_after_scrubbed_reset() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        let layout = Layout::array::<u8>(64).unwrap();
+        allocator.set_free_scrub(Some(0xCD));
+
+        unsafe {
+            // The whole pool is scrubbed by the reset, including the bytes never handed out.
+            allocator.reset().unwrap();
+            let ptr = allocator.alloc_zeroed(layout);
+            assert!(!ptr.is_null());
+            assert!(core::slice::from_raw_parts(ptr, 64)
+                .iter()
+                .all(|&byte| byte == 0));
+            allocator.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn test_try_allocate() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        let other: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        // Every allocation is aligned, and the slice covers at least the layout.
+        let byte = Layout::new::<u8>();
+        let first = allocator.try_allocate(byte).unwrap();
+        let aligned = Layout::from_size_align(24, 32).unwrap();
+        let second = allocator.try_allocate(aligned).unwrap();
+        assert!(!first.is_empty());
+        assert!(second.len() >= 24);
+        assert_eq!(second.cast::<u8>().as_ptr() as usize % 32, 0);
+        // The whole slice can be written.
+        unsafe { second.cast::<u8>().as_ptr().write_bytes(0xAB, second.len()) };
+        #[cfg(feature = "redzone")]
+        assert_eq!(allocator.check_integrity(), Ok(()));
+
+        // A layout taking no space reserves nothing.
+        let empty = allocator
+            .try_allocate(Layout::from_size_align(0, 16).unwrap())
+            .unwrap();
+        assert_eq!(empty.len(), 0);
+        assert_eq!(empty.cast::<u8>().as_ptr() as usize % 16, 0);
+        assert_eq!(allocator.heap_stats().allocations, 2);
+
+        // A pointer owned by another allocator is rejected.
+        let foreign = other.try_allocate(byte).unwrap();
+        assert_eq!(
+            unsafe { allocator.try_deallocate(foreign.cast(), byte) },
+            Err(IndexError::OutOfMemory)
+        );
+        assert_eq!(other.heap_stats().allocations, 1);
+
+        unsafe {
+            allocator.try_deallocate(first.cast(), byte).unwrap();
+            allocator.try_deallocate(second.cast(), aligned).unwrap();
+            assert_eq!(
+                allocator.try_deallocate(second.cast(), aligned),
+                Err(IndexError::DoubleFree)
+            );
+        }
+        assert_eq!(allocator.heap_stats().allocations, 0);
+
+        assert_eq!(
+            allocator.try_allocate(Layout::new::<[u8; 512]>()),
+            Err(IndexError::NoFittingRegion)
+        );
+    }
+
+    #[test]
+    fn test_wrong_allocator_free() {
+        let first: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let second: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let ptr = unsafe { first.try_alloc(Layout::new::<[u8; 16]>()).unwrap() };
+        let _other = unsafe { second.try_alloc(Layout::new::<[u8; 16]>()).unwrap() };
+        assert!(first.owns(ptr));
+        assert!(!second.owns(ptr));
+        assert!(!first.owns(first.memory_range().end));
+
+        let snapshot = second.index.borrow().snapshot();
+        assert_eq!(
+            unsafe { second.try_free(ptr) },
+            Err(IndexError::OutOfMemory)
+        );
+        assert_eq!(second.index.borrow().snapshot(), snapshot);
+        assert_eq!(first.heap_stats().allocations, 1);
+        assert_eq!(second.heap_stats().allocations, 1);
+
+        unsafe { first.try_free(ptr).unwrap() };
+    }
+
+    #[test]
+    fn test_wrong_allocator_dealloc() {
+        let first: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let second: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        // The pointer is rejected without unwinding, leaving both allocators alone.
+        let layout = Layout::new::<[u8; 16]>();
+        let ptr = unsafe { first.alloc(layout) };
+        unsafe { second.dealloc(ptr, layout) };
+        assert_eq!(first.heap_stats().allocations, 1);
+        assert_eq!(second.heap_stats().allocations, 0);
+        assert!(!second.has_failed_drop());
+
+        unsafe { first.dealloc(ptr, layout) };
+        assert_eq!(first.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_null_ptr() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let _test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+        let stats = allocator.heap_stats();
+
+        assert_eq!(
+            unsafe { allocator.try_free(ptr::null_mut()) },
+            Err(IndexError::EmptyPtr)
+        );
+        assert_eq!(
+            unsafe { allocator.try_free_value(ptr::null_mut::<[u8; 4]>()) },
+            Err(IndexError::EmptyPtr)
+        );
+        assert_eq!(
+            allocator.copy_out(ptr::null(), &mut [0; 4]),
+            Err(IndexError::EmptyPtr)
+        );
+        assert_eq!(
+            unsafe { allocator.copy_in(ptr::null_mut(), &[0; 4]) },
+            Err(IndexError::EmptyPtr)
+        );
+        assert_eq!(allocator.offset_of(ptr::null()), Err(IndexError::EmptyPtr));
+        assert_eq!(
+            unsafe { Box::<[u8; 4], 64, 8>::from_raw_parts(ptr::null_mut(), 4, &allocator) }.err(),
+            Some(IndexError::EmptyPtr)
+        );
+
+        unsafe { allocator.dealloc(ptr::null_mut(), Layout::new::<u8>()) };
+        assert_eq!(allocator.heap_stats(), stats);
+        assert!(!allocator.has_failed_drop());
+    }
+
+    #[test]
+    fn test_try_boxed_checked() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let _first = allocator.try_boxed_checked([0u8; 64], 64).unwrap();
+        // The value fits physically, but it would eat into the reserve.
+        assert!(allocator.largest_free_block().unwrap() >= 160 + 2 * REDZONE_SIZE);
+        assert_eq!(
+            allocator.try_boxed_checked([0u8; 160], 64).err(),
+            Some(IndexError::NoFittingRegion)
+        );
+        assert_eq!(allocator.heap_stats().allocations, 1);
+
+        // Exactly the reserve is left.
+        let reserve = 32 - 4 * REDZONE_SIZE;
+        let second = allocator.try_boxed_checked([1u8; 160], reserve).unwrap();
+        assert_eq!(*second, [1; 160]);
+        assert_eq!(allocator.heap_stats().free_bytes, reserve);
+        assert!(allocator.try_boxed_checked((), usize::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_try_leak() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let leaked = allocator.try_leak([1u8, 2, 3, 4]).unwrap();
+        leaked[0] = 5;
+        assert_eq!(*leaked, [5, 2, 3, 4]);
+
+        let used_region = allocator.index.borrow().regions().any(|region| region.used);
+        assert!(used_region);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+    }
+
+    #[test]
+    fn test_alignment_larger_than_memory() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let layout = Layout::from_size_align(1, 128).unwrap();
+        assert_eq!(
+            unsafe { allocator.try_alloc(layout) },
+            Err(IndexError::NoFittingRegion)
+        );
+        assert!(unsafe { allocator.alloc(layout) }.is_null());
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_free_below_memory_pool() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let _test_box = allocator.try_boxed(0u32).unwrap();
+        let regions_before: std::vec::Vec<_> = allocator.index.borrow().regions().collect();
+
+        let below = allocator.memory.get().cast::<u8>().wrapping_sub(1);
+        assert_eq!(
+            unsafe { allocator.try_free(below) },
+            Err(IndexError::OutOfMemory)
+        );
+        let far_below = allocator.memory.get().cast::<u8>().wrapping_sub(4096);
+        assert_eq!(
+            unsafe { allocator.try_free(far_below) },
+            Err(IndexError::OutOfMemory)
+        );
+
+        let regions_after: std::vec::Vec<_> = allocator.index.borrow().regions().collect();
+        assert_eq!(regions_before, regions_after);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+    }
+
+    #[test]
+    fn test_global_alloc_exhausted() {
+        let allocator: IndexAllocator<64, 4> = IndexAllocator::empty();
+        let layout = Layout::new::<u8>();
+
+        let ptrs: std::vec::Vec<*mut u8> =
+            core::iter::from_fn(|| Some(unsafe { allocator.alloc(layout) }))
+                .take_while(|ptr| !ptr.is_null())
+                .collect();
+        assert!(!ptrs.is_empty());
+        assert!(unsafe { allocator.alloc(layout) }.is_null());
+        assert!(unsafe { allocator.alloc(Layout::new::<[u8; 128]>()) }.is_null());
+
+        // A pointer foreign to the memory pool is ignored instead of panicking in release builds.
+        let allocations = allocator.heap_stats().allocations;
+        if !cfg!(debug_assertions) {
+            let mut outside = 0u8;
+            unsafe { allocator.dealloc(&mut outside, layout) };
+        }
+        assert_eq!(allocator.heap_stats().allocations, allocations);
+
+        for ptr in ptrs {
+            unsafe { allocator.dealloc(ptr, layout) };
+        }
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
+    fn test_byte_aligned_allocations_packed() {
+        let allocator: IndexAllocator<64, 16> = IndexAllocator::empty();
+        let memory_start = allocator.memory.get() as usize;
+
+        // Byte-aligned layouts need no padding nor offset, so the allocations follow each other without gaps.
+        let mut expected = memory_start;
+        for size in 1..=10 {
+            let layout = Layout::from_size_align(size, 1).unwrap();
+            assert_eq!(layout.pad_to_align().size(), size);
+
+            let ptr = unsafe { allocator.try_alloc(layout) }.unwrap();
+            assert_eq!(ptr as usize, expected);
+            expected += size;
+        }
+        assert_eq!(allocator.heap_stats().used_bytes, 55);
+    }
+
+    #[test]
+    fn test_reserve_high() {
+        let allocator: IndexAllocator<256, 16> = IndexAllocator::empty();
+        let offset = |ptr: *const u8| allocator.offset_of(ptr).unwrap();
+        const BLOCK: usize = 16 + 2 * REDZONE_SIZE;
+        let layout = Layout::new::<[u8; 16]>();
+
+        let low = allocator.try_boxed([0u8; 16]).unwrap();
+        let high = allocator.try_reserve_high(layout).unwrap();
+        let higher_aligned = allocator.try_reserve_high(Layout::new::<u64>()).unwrap();
+        let next_low = allocator.try_boxed([0u8; 16]).unwrap();
+
+        // The high allocations grow down from the top of the pool, the other ones grow up from its bottom.
+        assert_eq!(offset(low.as_ptr().cast()), REDZONE_SIZE);
+        assert_eq!(offset(next_low.as_ptr().cast()), BLOCK + REDZONE_SIZE);
+        assert_eq!(offset(high.as_ptr()), 256 - BLOCK + REDZONE_SIZE);
+        assert!(higher_aligned.as_ptr().cast::<u64>().is_aligned());
+        assert!(offset(higher_aligned.as_ptr()) + 8 + REDZONE_SIZE <= 256 - BLOCK);
+        assert!(offset(higher_aligned.as_ptr()) > 256 - 2 * BLOCK);
+
+        // A freed high allocation is taken back by the next one.
+        unsafe { allocator.dealloc(high.as_ptr(), layout) };
+        let again = allocator.try_reserve_high(layout).unwrap();
+        assert_eq!(again, high);
+        assert_eq!(allocator.heap_stats().allocations, 4);
+
+        unsafe {
+            allocator.dealloc(again.as_ptr(), layout);
+            allocator.dealloc(higher_aligned.as_ptr(), Layout::new::<u64>());
+        }
+        drop((low, next_low));
+        assert_eq!(allocator.largest_free_block(), Ok(256));
+        assert!(allocator
+            .try_reserve_high(Layout::new::<[u8; 512]>())
+            .is_err());
+    }
+
+    #[test]
+    fn test_boxed_large_array() {
+        let allocator: IndexAllocator<4096, 4> = IndexAllocator::empty();
+        // Start the array at an odd offset, so that it needs to be aligned.
+        let _byte = allocator.try_boxed(1u8).unwrap();
+        let used_bytes = allocator.heap_stats().used_bytes;
+        allocator.index.borrow().take_visits();
+
+        let array = allocator.try_boxed([7u32; 1000]).unwrap();
+        assert!(array.as_ptr().cast::<u32>().is_aligned());
+        assert!(array.iter().all(|value| *value == 7));
+
+        // A single region holds the whole array, reserved in a single search of the index.
+        let index = allocator.index.borrow();
+        assert!(index.take_visits() <= 3 * 4);
+        let offset = allocator.offset_of(array.as_ptr().cast()).unwrap();
+        let region = index
+            .get_region(index.find_region(offset).unwrap())
+            .unwrap();
+        assert!(region.used);
+        assert!(
+            region.size >= 4000 + 2 * REDZONE_SIZE && region.size < 4000 + 2 * REDZONE_SIZE + 4
+        );
+        assert_eq!(allocator.heap_stats().used_bytes, used_bytes + region.size);
+        assert_eq!(allocator.heap_stats().allocations, 2);
+    }
+
+    /// Test by brute force if some free region of the allocator could hold `layout`, gaps included.
+    fn fits_somewhere<const MEMORY_SIZE: usize, const INDEX_SIZE: usize>(
+        allocator: &IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+        layout: Layout,
+    ) -> bool {
+        let memory_start = allocator.memory.get() as usize;
+        let index = allocator.index.borrow();
+        let fits = index.regions().filter(|region| !region.used).any(|region| {
+            (region.from..region.end()).any(|from| {
+                (memory_start + from + REDZONE_SIZE).is_multiple_of(layout.align())
+                    && from + layout.size() + 2 * REDZONE_SIZE <= region.end()
+            })
+        });
+        fits
+    }
+
+    #[test]
+    fn test_over_aligned_small_layouts() {
+        for align in (1..=7).map(|shift| 1 << shift) {
+            for size in 1..=32 {
+                let layout = Layout::from_size_align(size, align).unwrap();
+                // The index can hold a region for every byte, so only the memory pool limits the allocations.
+                let allocator: IndexAllocator<256, 257> = IndexAllocator::empty();
+                let mut ptrs = std::vec::Vec::new();
+
+                // Fill the pool, then free every other allocation and fill the holes left.
+                for round in 0..2 {
+                    loop {
+                        match unsafe { allocator.try_alloc(layout) } {
+                            Ok(ptr) => {
+                                assert!(
+                                    (ptr as usize).is_multiple_of(align),
+                                    "{layout:?} misaligned"
+                                );
+                                ptrs.push(ptr);
+                            }
+                            Err(err) => {
+                                assert_eq!(err, IndexError::NoFittingRegion, "{layout:?}");
+                                assert!(!fits_somewhere(&allocator, layout), "{layout:?} rejected");
+                                break;
+                            }
+                        }
+                    }
+                    if round == 0 {
+                        for ptr in ptrs.iter().step_by(2) {
+                            unsafe { allocator.try_free(*ptr).unwrap() };
+                        }
+                    }
+                }
+
+                // Without padding the size to the alignment, the allocations are as packed as the alignment allows.
+                let memory_start = allocator.memory.get() as usize;
+                let aligned_starts = (0..256)
+                    .filter(|offset| (memory_start + offset).is_multiple_of(align))
+                    .filter(|offset| offset + size <= 256)
+                    .count();
+                if !cfg!(feature = "redzone") && size <= align {
+                    assert_eq!(
+                        allocator.heap_stats().allocations,
+                        aligned_starts,
+                        "{layout:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Check the number of slots visited by each operation of a pseudo random sequence stays within the documented bounds.
+    #[test]
+    fn test_operation_bounds() {
+        const INDEX_SIZE: usize = 16;
+        let allocator: IndexAllocator<1024, INDEX_SIZE> = IndexAllocator::empty();
+        let mut live: [Option<*mut u8>; INDEX_SIZE] = [None; INDEX_SIZE];
+        let mut seed: u32 = 0x2545_F491;
+
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let slot = (seed >> 16) as usize % INDEX_SIZE;
+            let size = 1 + (seed >> 8) as usize % 96;
+            let align = 1 << ((seed >> 4) as usize % 4);
+
+            allocator.index.borrow().take_visits();
+            match live[slot].take() {
+                Some(ptr) => {
+                    unsafe { allocator.try_free(ptr).unwrap() };
+                    assert!(allocator.index.borrow().take_visits() <= 2 * INDEX_SIZE + 2);
+                }
+                None => {
+                    let layout = Layout::from_size_align(size, align).unwrap();
+                    live[slot] = unsafe { allocator.try_alloc(layout) }.ok();
+                    assert!(allocator.index.borrow().take_visits() <= 3 * INDEX_SIZE);
+                }
+            }
+            assert!(allocator.index.borrow().is_sorted());
+        }
+    }
+
+    #[test]
+    fn test_failed_operations_leave_index_unchanged() {
+        let allocator: IndexAllocator<256, 3> = IndexAllocator::empty();
+        let snapshot = || allocator.index.borrow().snapshot();
+
+        let first = unsafe { allocator.try_alloc(Layout::new::<[u8; 16]>()).unwrap() };
+        let before = snapshot();
+
+        let errors = [
+            // No region is large enough.
+            Layout::new::<[u8; 512]>(),
+            // The alignment isn't supported by the memory pool.
+            Layout::from_size_align(1, 512).unwrap(),
+        ]
+        .map(|layout| unsafe { allocator.try_alloc(layout) }.map(|_| ()));
+        assert_eq!(
+            errors,
+            [
+                Err(IndexError::NoFittingRegion),
+                Err(IndexError::NoFittingRegion)
+            ]
+        );
+        assert_eq!(snapshot(), before);
+
+        unsafe {
+            assert_eq!(
+                allocator.try_shrink(first, 32),
+                Err(IndexError::RegionTooThin)
+            );
+            assert_eq!(
+                allocator.try_shrink(first, usize::MAX),
+                Err(IndexError::RegionTooThin)
+            );
+            assert_eq!(
+                allocator.try_shrink(allocator.memory.get().cast::<u8>().wrapping_add(256), 1),
+                Err(IndexError::OutOfMemory)
+            );
+        }
+        assert_eq!(snapshot(), before);
+
+        // Fill the index, the last region being free.
+        let second = unsafe { allocator.try_alloc(Layout::new::<[u8; 16]>()).unwrap() };
+        let before = snapshot();
+        unsafe {
+            assert_eq!(
+                allocator.try_alloc(Layout::new::<u8>()),
+                Err(IndexError::NoIndexAvailable)
+            );
+            // The tail of the first region needs a new index, as the region after it is used.
+            assert_eq!(
+                allocator.try_shrink(first, 1),
+                Err(IndexError::NoIndexAvailable)
+            );
+        }
+        assert_eq!(snapshot(), before);
+
+        unsafe { allocator.try_free(second).unwrap() };
+        let before = snapshot();
+        unsafe {
+            assert_eq!(allocator.try_free(second), Err(IndexError::DoubleFree));
+            assert_eq!(allocator.try_shrink(second, 1), Err(IndexError::DoubleFree));
+        }
+        assert_eq!(snapshot(), before);
+    }
+
+    #[test]
+    fn test_prefault_pages() {
+        const PAGE_SIZE: usize = 64;
+        let allocator: IndexAllocator<1024, 8> = IndexAllocator::empty();
+        let memory_start = allocator.memory.get() as usize;
+        let page_of = |offset: usize| (memory_start + offset) / PAGE_SIZE;
+
+        let mut touched = std::vec::Vec::new();
+        allocator
+            .touch_pages(PAGE_SIZE, |offset| touched.push(offset))
+            .unwrap();
+        // Every page spanned by the memory pool is touched exactly once.
+        let pages: std::vec::Vec<usize> = touched.iter().map(|offset| page_of(*offset)).collect();
+        assert_eq!(
+            pages,
+            (page_of(0)..=page_of(1023)).collect::<std::vec::Vec<_>>()
+        );
+
+        // The pages only spanned by allocations are left alone.
+        let _used = allocator.try_boxed([1u8; 512]).unwrap();
+        touched.clear();
+        allocator
+            .touch_pages(PAGE_SIZE, |offset| touched.push(offset))
+            .unwrap();
+        assert!(touched.iter().all(|offset| *offset >= 512));
+        assert_eq!(
+            touched.last().map(|offset| page_of(*offset)),
+            Some(page_of(1023))
+        );
+
+        // Prefaulting leaves the memory unchanged.
+        allocator.clear_to_pattern(0xAA).unwrap();
+        allocator.prefault(PAGE_SIZE).unwrap();
+        assert!(memory(&allocator)[512 + 2 * REDZONE_SIZE..]
+            .iter()
+            .all(|byte| *byte == 0xAA));
+        assert!(allocator.prefault(0).is_ok());
+    }
+
+    #[test]
+    fn test_alloc_array() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        assert_eq!(
+            allocator.try_alloc_array::<u32>(usize::MAX / 2).err(),
+            Some(IndexError::LayoutOverflow)
+        );
+        assert_eq!(allocator.heap_stats().allocations, 0);
+
+        let array = allocator.try_alloc_array::<u32>(6).unwrap();
+        assert_eq!(array.len(), 6);
+        assert!(array.cast::<u32>().as_ptr().is_aligned());
+        // The padding before the array to align it is reserved as well.
+        let used_bytes = allocator.heap_stats().used_bytes;
+        assert!((6 * 4 + 2 * REDZONE_SIZE..6 * 4 + 2 * REDZONE_SIZE + 4).contains(&used_bytes));
+
+        unsafe { array.cast::<u32>().as_ptr().write_bytes(0xFF, 6) };
+        unsafe { allocator.try_free_array(array.cast::<u32>(), array.len()) }.unwrap();
+        let zeroed = allocator.try_alloc_array_zeroed::<u32>(6).unwrap();
+        assert_eq!(unsafe { zeroed.as_ref() }, [0; 6]);
+        unsafe { allocator.try_free_array(zeroed.cast::<u32>(), zeroed.len()) }.unwrap();
+
+        // Arrays taking no space reserve nothing, but keep their length.
+        let empty = allocator.try_alloc_array::<()>(usize::MAX).unwrap();
+        assert_eq!(empty.len(), usize::MAX);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            IndexError::NoFittingRegion.to_string(),
+            "no free region fits the allocation"
+        );
+    }
+
+    #[cfg(feature = "allocator_api")]
+    #[test]
+    fn test_alloc_error_conversion() {
+        fn allocate() -> Result<(), core::alloc::AllocError> {
+            Err(IndexError::NoIndexAvailable)?
+        }
+
+        assert_eq!(allocate(), Err(core::alloc::AllocError));
     }
 }
@@ -0,0 +1,329 @@
+//! This module contains the [`RcList`] doubly linked list, built on [`Rc`] and [`Weak`] in an [`IndexAllocator`].
+
+use core::cell::RefCell;
+use core::fmt::{self, Debug};
+
+use crate::rc::{Rc, Weak};
+use crate::{IndexAllocator, IndexError};
+
+/// A node of an [`RcList`], owning the next node and weakly referencing the previous one.
+struct ListNode<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    val: T,
+    next: RefCell<Option<Rc<'a, Self, MEMORY_SIZE, INDEX_SIZE>>>,
+    prev: RefCell<Option<Weak<'a, Self, MEMORY_SIZE, INDEX_SIZE>>>,
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    ListNode<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn new(val: T) -> Self {
+        Self {
+            val,
+            next: RefCell::new(None),
+            prev: RefCell::new(None),
+        }
+    }
+}
+
+type Link<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> =
+    Rc<'a, ListNode<'a, T, MEMORY_SIZE, INDEX_SIZE>, MEMORY_SIZE, INDEX_SIZE>;
+
+/// A doubly linked list whose nodes are [`Rc`] allocated in an [`IndexAllocator`].
+///
+/// Each node holds the next one with an [`Rc`] and the previous one with a [`Weak`],
+/// so that the links never form a reference cycle and every node is freed once popped or once the list is dropped.
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::list::RcList;
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: IndexAllocator<512, 16> = IndexAllocator::empty();
+///
+/// let mut list = RcList::new(&allocator);
+/// list.push_back(2).unwrap();
+/// list.push_back(3).unwrap();
+/// list.push_front(1).unwrap();
+///
+/// assert!(list.iter().eq(&[1, 2, 3]));
+/// assert_eq!(list.pop_back(), Some(3));
+/// assert_eq!(list.pop_front(), Some(1));
+/// assert_eq!(list.len(), 1);
+/// ```
+pub struct RcList<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    head: Option<Link<'a, T, MEMORY_SIZE, INDEX_SIZE>>,
+    tail: Option<Weak<'a, ListNode<'a, T, MEMORY_SIZE, INDEX_SIZE>, MEMORY_SIZE, INDEX_SIZE>>,
+    len: usize,
+    allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    RcList<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Create an empty [`RcList`] allocating its nodes in `allocator`.
+    #[must_use]
+    pub fn new(allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        Self {
+            head: None,
+            tail: None,
+            len: 0,
+            allocator,
+        }
+    }
+
+    /// Add a value at the front of the list.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the node allocation failed, in which case the list is left unchanged.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn push_front(&mut self, val: T) -> Result<(), IndexError> {
+        let node = Rc::try_new(ListNode::new(val), self.allocator)?;
+
+        match self.head.take() {
+            Some(old_head) => {
+                *old_head.prev.borrow_mut() = Some(node.downgrade());
+                *node.next.borrow_mut() = Some(old_head);
+            }
+            None => self.tail = Some(node.downgrade()),
+        }
+        self.head = Some(node);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Add a value at the back of the list.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the node allocation failed, in which case the list is left unchanged.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn push_back(&mut self, val: T) -> Result<(), IndexError> {
+        let node = Rc::try_new(ListNode::new(val), self.allocator)?;
+        let old_tail = self.tail.replace(node.downgrade());
+
+        match old_tail.as_ref().and_then(Weak::upgrade) {
+            Some(old_tail) => {
+                *node.prev.borrow_mut() = Some(old_tail.downgrade());
+                *old_tail.next.borrow_mut() = Some(node);
+            }
+            None => self.head = Some(node),
+        }
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Remove the value at the front of the list and return it, or `None` if the list is empty.
+    pub fn pop_front(&mut self) -> Option<T> {
+        let head = self.head.take()?;
+
+        match head.next.borrow_mut().take() {
+            Some(next) => {
+                next.prev.borrow_mut().take();
+                self.head = Some(next);
+            }
+            None => {
+                self.tail.take();
+            }
+        }
+        self.len -= 1;
+
+        Rc::try_unwrap(head).ok().map(|node| node.val)
+    }
+
+    /// Remove the value at the back of the list and return it, or `None` if the list is empty.
+    pub fn pop_back(&mut self) -> Option<T> {
+        let tail = self.tail.take()?.upgrade()?;
+
+        let prev = tail.prev.borrow_mut().take();
+        match prev.as_ref().and_then(Weak::upgrade) {
+            Some(prev) => {
+                prev.next.borrow_mut().take();
+                self.tail = Some(prev.downgrade());
+            }
+            None => {
+                self.head.take();
+            }
+        }
+        self.len -= 1;
+
+        Rc::try_unwrap(tail).ok().map(|node| node.val)
+    }
+
+    /// Get a reference to the value at the front of the list.
+    #[must_use]
+    pub fn front(&self) -> Option<&T> {
+        self.head.as_ref().map(|head| &head.val)
+    }
+
+    /// Iterate over the values of the list, from front to back.
+    pub fn iter(&self) -> Iter<'_, 'a, T, MEMORY_SIZE, INDEX_SIZE> {
+        Iter {
+            next: self.head.as_ref(),
+        }
+    }
+
+    /// Return the number of values in the list.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Test if the list is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get a reference to the [`IndexAllocator`] used by the list.
+    #[must_use]
+    pub fn allocator(&self) -> &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+        self.allocator
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Drop
+    for RcList<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn drop(&mut self) {
+        // Unlink the nodes one by one rather than recursively, which could overflow the stack on long lists.
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Debug
+    for RcList<'a, T, MEMORY_SIZE, INDEX_SIZE>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the values of an [`RcList`], obtained with [`RcList::iter`].
+pub struct Iter<'l, 'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    next: Option<&'l Link<'a, T, MEMORY_SIZE, INDEX_SIZE>>,
+}
+
+impl<'l, 'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Iterator
+    for Iter<'l, 'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    type Item = &'l T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node: &'l ListNode<'a, T, MEMORY_SIZE, INDEX_SIZE> = self.next?;
+        // The links are only mutated through `&mut RcList`, so they can't change while the list is borrowed by the iterator.
+        self.next = unsafe { node.next.try_borrow_unguarded() }
+            .ok()
+            .and_then(Option::as_ref);
+
+        Some(&node.val)
+    }
+}
+
+impl<'l, 'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IntoIterator
+    for &'l RcList<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    type Item = &'l T;
+    type IntoIter = Iter<'l, 'a, T, MEMORY_SIZE, INDEX_SIZE>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::MemoryRegion;
+
+    use super::*;
+
+    #[test]
+    fn test_list_push_pop() {
+        let allocator: IndexAllocator<2048, 32> = IndexAllocator::empty();
+
+        let mut list = RcList::new(&allocator);
+        assert!(list.is_empty());
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+
+        list.push_back(2).unwrap();
+        list.push_front(1).unwrap();
+        list.push_back(3).unwrap();
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.front(), Some(&1));
+        assert!(list.iter().eq(&[1, 2, 3]));
+
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+
+        list.push_front(4).unwrap();
+        assert_eq!(list.pop_back(), Some(4));
+
+        assert_eq!(
+            allocator.index.borrow().get_region(0),
+            Ok(MemoryRegion::new(0, 2048, false))
+        );
+    }
+
+    #[test]
+    fn test_list_drop_frees_nodes() {
+        let allocator: IndexAllocator<2048, 32> = IndexAllocator::empty();
+
+        let mut list = RcList::new(&allocator);
+        for i in 0..8u32 {
+            list.push_back(i).unwrap();
+        }
+        assert!(allocator.heap_stats().allocations > 0);
+
+        drop(list);
+
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(
+            allocator.index.borrow().get_region(0),
+            Ok(MemoryRegion::new(0, 2048, false))
+        );
+    }
+
+    /// A value counting how many times it was dropped.
+    #[cfg(not(feature = "compact-index"))]
+    struct Counted<'c>(&'c core::cell::Cell<usize>);
+
+    #[cfg(not(feature = "compact-index"))]
+    impl Drop for Counted<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    // The compact index can't address a memory pool large enough for the list.
+    #[cfg(not(feature = "compact-index"))]
+    fn test_list_drop_long() {
+        use crate::sync::SingleThreaded;
+
+        const NODES: usize = 10_000;
+        static ALLOCATOR: SingleThreaded<{ 128 * NODES }, 8> = unsafe { SingleThreaded::empty() };
+        let drops = core::cell::Cell::new(0);
+
+        // The nodes are allocated in bump mode, as searching the index for each of them would make the test slow.
+        ALLOCATOR.enter_bump_mode().unwrap();
+        let mut list = RcList::new(&ALLOCATOR);
+        for _ in 0..NODES {
+            list.push_back(Counted(&drops)).unwrap();
+        }
+        ALLOCATOR.exit_bump_mode().unwrap();
+        assert_eq!(list.len(), NODES);
+
+        // Dropping the nodes recursively would overflow the stack of the test thread.
+        drop(list);
+        assert_eq!(drops.get(), NODES);
+    }
+}
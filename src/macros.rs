@@ -0,0 +1,152 @@
+//! This module contains the macros exported by the crate.
+
+/// Declare an [`IndexAllocator`](crate::IndexAllocator) as the global allocator,
+/// along with a `heap_stats` function returning its [`HeapStats`](crate::stats::HeapStats).
+///
+/// The macro takes the name of the static, the `MEMORY_SIZE` and the `INDEX_SIZE` of the allocator.
+/// Attributes placed before `static` are passed through to the static,
+/// which allows placing the memory pool in a specific section with `#[link_section]`.
+///
+/// With the `critical-section` feature, the static is a `Locked` allocator (see the [`sync`](crate::sync) module),
+/// which is safe to use from several threads.
+/// Otherwise it is a [`SingleThreaded`](crate::sync::SingleThreaded) allocator, and the static must be declared
+/// as `unsafe static`: a plain `static` doesn't compile without the `critical-section` feature.
+/// With the feature, `unsafe static` is accepted as well, so the same declaration builds either way.
+///
+/// With the `alloc_error_handler` feature, it also declares an `#[alloc_error_handler]`
+/// panicking with the size of the failed allocation.
+/// This is only needed by `no_std` binaries on toolchains which require it,
+/// and needs the `#![feature(alloc_error_handler)]` attribute in the binary crate.
+///
+/// # Safety
+///
+/// Without the `critical-section` feature, the program must never allocate from two threads,
+/// nor from an interrupt handler, as for [`SingleThreaded::empty`](crate::sync::SingleThreaded::empty).
+///
+/// # Example
+///
+/// ```
+/// // Safety: the program never allocates from two threads.
+/// index_alloc::index_global_alloc!(unsafe static HEAP: 16384, 128);
+///
+/// fn main() {
+///     let test_str = String::from("Hello World");
+///     assert!(heap_stats().used_bytes >= test_str.len());
+/// }
+/// ```
+#[cfg_attr(
+    not(feature = "critical-section"),
+    doc = r#"
+Without the `critical-section` feature, a plain `static` is refused:
+
+```compile_fail
+index_alloc::index_global_alloc!(static HEAP: 16384, 128);
+
+fn main() {}
+```
+"#
+)]
+#[macro_export]
+macro_rules! index_global_alloc {
+    ($(#[$attr:meta])* $vis:vis unsafe static $name:ident: $memory_size:expr, $index_size:expr $(;)?) => {
+        $crate::__index_global_alloc!($(#[$attr])* $vis static $name: $memory_size, $index_size, (unsafe));
+    };
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $memory_size:expr, $index_size:expr $(;)?) => {
+        $crate::__index_global_alloc!($(#[$attr])* $vis static $name: $memory_size, $index_size, ());
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __index_global_alloc {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $memory_size:expr, $index_size:expr, ($($unsafe:tt)?)) => {
+        $(#[$attr])*
+        #[global_allocator]
+        $vis static $name: $crate::__index_global_alloc_type!($memory_size, $index_size) =
+            $crate::__index_global_alloc_empty!($($unsafe)?);
+
+        /// Get the statistics of the global allocator.
+        #[allow(dead_code)]
+        pub fn heap_stats() -> $crate::stats::HeapStats {
+            $name.heap_stats()
+        }
+
+        $crate::__index_alloc_error_handler!();
+    };
+}
+
+#[cfg(feature = "critical-section")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __index_global_alloc_type {
+    ($memory_size:expr, $index_size:expr) => {
+        $crate::sync::Locked<{ $memory_size }, { $index_size }>
+    };
+}
+
+#[cfg(feature = "critical-section")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __index_global_alloc_empty {
+    ($($unsafe:tt)?) => {
+        $crate::sync::Locked::empty()
+    };
+}
+
+#[cfg(not(feature = "critical-section"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __index_global_alloc_type {
+    ($memory_size:expr, $index_size:expr) => {
+        $crate::sync::SingleThreaded<{ $memory_size }, { $index_size }>
+    };
+}
+
+#[cfg(not(feature = "critical-section"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __index_global_alloc_empty {
+    (unsafe) => {
+        // Safety: the caller of `index_global_alloc!` wrote `unsafe`, promising not to allocate from several threads.
+        unsafe { $crate::sync::SingleThreaded::empty() }
+    };
+    () => {
+        ::core::compile_error!(
+            "without the `critical-section` feature, the allocator isn't thread-safe: \
+             declare it with `index_global_alloc!(unsafe static ...)`"
+        )
+    };
+}
+
+#[cfg(feature = "alloc_error_handler")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __index_alloc_error_handler {
+    () => {
+        #[alloc_error_handler]
+        fn __index_alloc_error_handler(layout: ::core::alloc::Layout) -> ! {
+            ::core::panic!("memory allocation of {} bytes failed", layout.size())
+        }
+    };
+}
+
+#[cfg(not(feature = "alloc_error_handler"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __index_alloc_error_handler {
+    () => {};
+}
+
+/// Emit a `log` record at the given level with the `log` feature, or nothing without it.
+///
+/// The arguments are still type-checked without the feature, so they don't trigger unused warnings.
+macro_rules! log_record {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        ::log::$level!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)+);
+        }
+    };
+}
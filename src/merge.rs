@@ -0,0 +1,150 @@
+//! This module contains the lazy merge mode, in which frees only mark their region free,
+//! leaving the free regions next to each other unmerged until an allocation fails or [`IndexAllocator::merge_now`] is called.
+
+use crate::index::MemoryIndex;
+use crate::{IndexAllocator, IndexError};
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Enable or disable the lazy merge mode, making frees as cheap as possible at the cost of a temporary fragmentation.
+    ///
+    /// In lazy merge mode, a free only marks its region free, without merging it with the free regions around it.
+    /// An allocation finding no fitting region merges them and tries once more, and [`IndexAllocator::merge_now`]
+    /// merges them up front. Only the allocations retry: until the regions are merged, [`IndexAllocator::largest_free_block`]
+    /// may be smaller than the free memory allows, and the other operations, such as [`IndexAllocator::enter_bump_mode`], may fail.
+    ///
+    /// Disabling the mode doesn't merge the regions freed in it. The buddy mode always merges the blocks it frees with their buddy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<256, 16> = IndexAllocator::empty();
+    /// allocator.set_lazy_merge(true);
+    ///
+    /// let values: [_; 4] = core::array::from_fn(|_| allocator.try_boxed([0u8; 32]).unwrap());
+    /// drop(values);
+    /// assert_eq!(allocator.pending_merges(), 4);
+    ///
+    /// allocator.merge_now().unwrap();
+    /// assert_eq!(allocator.pending_merges(), 0);
+    /// assert_eq!(allocator.largest_free_block(), Ok(256));
+    /// ```
+    pub fn set_lazy_merge(&self, lazy: bool) {
+        self.lazy_merge.set(lazy);
+    }
+
+    /// Test if the allocator is in lazy merge mode, see [`IndexAllocator::set_lazy_merge`].
+    #[must_use]
+    pub fn is_lazy_merge(&self) -> bool {
+        self.lazy_merge.get()
+    }
+
+    /// Return the number of regions freed in lazy merge mode since the free regions were last merged.
+    #[must_use]
+    pub fn pending_merges(&self) -> usize {
+        self.pending_merges.get()
+    }
+
+    /// Merge the regions freed in lazy merge mode with the free regions around them, see [`IndexAllocator::compact`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn merge_now(&self) -> Result<(), IndexError> {
+        self.compact()
+    }
+
+    /// Free a region without merging it, if in lazy merge mode, returning whether it was freed.
+    pub(crate) fn free_lazily(
+        &self,
+        index: &mut MemoryIndex<INDEX_SIZE>,
+        region: usize,
+    ) -> Result<bool, IndexError> {
+        if !self.lazy_merge.get() {
+            return Ok(false);
+        }
+        index.get_region_mut(region)?.free();
+        self.pending_merges.set(self.pending_merges.get() + 1);
+        Ok(true)
+    }
+
+    /// Merge the free regions if some were freed in lazy merge mode, returning whether any was.
+    pub(crate) fn merge_pending(&self, index: &mut MemoryIndex<INDEX_SIZE>) -> bool {
+        if self.pending_merges.get() == 0 {
+            return false;
+        }
+        index.sort_merge();
+        self.pending_merges.set(0);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::IndexAllocator;
+
+    /// The bytes taken by an allocation of 16 bytes.
+    const BLOCK: usize = 16 + 2 * crate::REDZONE_SIZE;
+
+    #[test]
+    fn test_lazy_merge_retry() {
+        let allocator: IndexAllocator<{ 16 * BLOCK }, 64> = IndexAllocator::empty();
+        allocator.set_lazy_merge(true);
+
+        let mut values: Vec<_> = (0..16)
+            .map(|_| allocator.try_boxed([0u8; 16]).unwrap())
+            .collect();
+        let last = values.pop().unwrap();
+        drop(values);
+        assert_eq!(allocator.pending_merges(), 15);
+
+        // Every region but the last one is free, but none of them fits the allocation on its own.
+        assert_eq!(allocator.largest_free_block(), Ok(BLOCK));
+        const LARGE: usize = 15 * BLOCK - 2 * crate::REDZONE_SIZE;
+        let large = allocator.try_boxed([1u8; LARGE]).unwrap();
+        assert_eq!(allocator.pending_merges(), 0);
+        assert_eq!(*large, [1u8; LARGE]);
+
+        // A failure with nothing pending doesn't retry.
+        assert!(allocator.try_boxed([0u8; 16]).is_err());
+        drop((large, last));
+    }
+
+    #[test]
+    fn test_lazy_merge_now() {
+        let allocator: IndexAllocator<256, 16> = IndexAllocator::empty();
+        allocator.set_lazy_merge(true);
+        assert!(allocator.is_lazy_merge());
+
+        let first = allocator.try_boxed([0u8; 16]).unwrap();
+        let second = allocator.try_boxed([0u8; 16]).unwrap();
+        let third = allocator.try_boxed([0u8; 16]).unwrap();
+        let fourth = allocator.try_boxed([0u8; 16]).unwrap();
+        drop(second);
+        drop(third);
+        drop(first);
+        assert_eq!(allocator.pending_merges(), 3);
+        assert_eq!(allocator.index.borrow().len(), 5);
+
+        allocator.merge_now().unwrap();
+        assert_eq!(allocator.pending_merges(), 0);
+        assert_eq!(allocator.index.borrow().len(), 3);
+        assert_eq!(
+            allocator
+                .index
+                .borrow()
+                .region_at(0)
+                .map(|region| region.used),
+            Ok(false)
+        );
+
+        // Leaving the mode brings back the eager merging.
+        allocator.set_lazy_merge(false);
+        drop(fourth);
+        assert_eq!(allocator.pending_merges(), 0);
+        assert_eq!(allocator.index.borrow().len(), 1);
+    }
+}
@@ -0,0 +1,168 @@
+//! This module contains the [`AllocObserver`] trait, to plug tracing or accounting schemes in an [`IndexAllocator`].
+//!
+//! It is only available with the `observer` feature, so that allocators without an observer don't even check for one.
+//! An observer set with [`IndexAllocator::set_observer`] is called after every allocation, free and failed allocation,
+//! once the index is released: it may query the allocator, such as its [`IndexAllocator::heap_stats`].
+//!
+//! Resizing an allocation in place and [`IndexAllocator::reset`] aren't reported,
+//! nor are the frees of the allocations made in bump mode, which are ignored.
+
+use core::alloc::Layout;
+
+use crate::{IndexAllocator, IndexError};
+
+/// The hooks called by an [`IndexAllocator`] on its allocations, see [`IndexAllocator::set_observer`].
+///
+/// The hooks are called in the allocation and free paths, so they should be cheap, and they must not allocate
+/// through the allocator they observe. The observer is shared through a `'static` reference,
+/// so it needs interior mutability, such as atomics, to keep state.
+pub trait AllocObserver: Sync {
+    /// Called after `layout` was allocated at `offset` from the start of the memory pool, reserving `size` bytes.
+    fn on_alloc(&self, offset: usize, size: usize, layout: Layout);
+
+    /// Called after the allocation at `offset` was freed, giving back `size` bytes.
+    fn on_free(&self, offset: usize, size: usize);
+
+    /// Called after allocating `layout` failed with `err`.
+    fn on_fail(&self, layout: Layout, err: IndexError);
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Set the observer called on the following allocations and frees, replacing the previous one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::alloc::Layout;
+    /// use core::sync::atomic::{AtomicUsize, Ordering};
+    /// use index_alloc::observer::AllocObserver;
+    /// use index_alloc::{IndexAllocator, IndexError};
+    ///
+    /// struct Failures(AtomicUsize);
+    ///
+    /// impl AllocObserver for Failures {
+    ///     fn on_alloc(&self, _offset: usize, _size: usize, _layout: Layout) {}
+    ///     fn on_free(&self, _offset: usize, _size: usize) {}
+    ///     fn on_fail(&self, _layout: Layout, _err: IndexError) {
+    ///         self.0.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// }
+    ///
+    /// static FAILURES: Failures = Failures(AtomicUsize::new(0));
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// allocator.set_observer(&FAILURES);
+    ///
+    /// assert!(allocator.try_boxed([0u8; 128]).is_err());
+    /// assert_eq!(FAILURES.0.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn set_observer(&self, observer: &'static dyn AllocObserver) {
+        self.observer.set(Some(observer));
+    }
+
+    /// Remove the observer set with [`IndexAllocator::set_observer`].
+    pub fn clear_observer(&self) {
+        self.observer.set(None);
+    }
+
+    /// Report the outcome of the allocation of `layout` to the observer, `size` being the bytes it reserved.
+    pub(crate) fn observe_alloc(
+        &self,
+        layout: Layout,
+        result: Result<usize, IndexError>,
+        size: usize,
+    ) {
+        if let Some(observer) = self.observer.get() {
+            match result {
+                Ok(offset) => observer.on_alloc(offset, size, layout),
+                Err(err) => observer.on_fail(layout, err),
+            }
+        }
+    }
+
+    /// Report the free of the allocation at `offset`, which gave back `size` bytes, to the observer.
+    pub(crate) fn observe_free(&self, offset: usize, size: usize) {
+        if let Some(observer) = self.observer.get() {
+            observer.on_free(offset, size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::sync::SingleThreaded;
+
+    static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+    static RECORDER: Recorder = Recorder {
+        events: Mutex::new(Vec::new()),
+    };
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Event {
+        Alloc(usize, usize),
+        Free(usize, usize),
+        Fail(usize, IndexError),
+    }
+
+    /// An observer recording every callback, checking the index is released when it is called.
+    struct Recorder {
+        events: Mutex<Vec<Event>>,
+    }
+
+    impl Recorder {
+        fn record(&self, event: Event) {
+            assert!(ALLOCATOR.largest_free_block().is_ok());
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    impl AllocObserver for Recorder {
+        fn on_alloc(&self, offset: usize, size: usize, layout: Layout) {
+            assert!(size >= layout.size());
+            self.record(Event::Alloc(offset, size));
+        }
+
+        fn on_free(&self, offset: usize, size: usize) {
+            self.record(Event::Free(offset, size));
+        }
+
+        fn on_fail(&self, layout: Layout, err: IndexError) {
+            self.record(Event::Fail(layout.size(), err));
+        }
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the offsets and sizes"
+    )]
+    fn test_observer_sequence() {
+        let allocator = &*ALLOCATOR;
+
+        let unobserved = allocator.try_boxed([0u8; 8]).unwrap();
+        allocator.set_observer(&RECORDER);
+
+        let first = allocator.try_boxed([1u8; 16]).unwrap();
+        let second = allocator.try_boxed([2u8; 32]).unwrap();
+        assert!(allocator.try_boxed([3u8; 16]).is_err());
+        drop(first);
+        drop(unobserved);
+        allocator.clear_observer();
+        drop(second);
+
+        assert_eq!(
+            *RECORDER.events.lock().unwrap(),
+            [
+                Event::Alloc(8, 16),
+                Event::Alloc(24, 32),
+                Event::Fail(16, IndexError::NoFittingRegion),
+                Event::Free(8, 16),
+                Event::Free(0, 8),
+            ]
+        );
+    }
+}
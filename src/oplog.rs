@@ -0,0 +1,196 @@
+//! This module contains the operation log of the `oplog` feature, recording the allocation decisions of an [`IndexAllocator`]
+//! in a ring buffer provided by the user, to replay them or analyze them after a crash.
+//!
+//! Once a buffer is set with [`IndexAllocator::set_op_log`], every allocation and free appends a [`LogEntry`],
+//! overwriting the oldest one once the buffer is full. Each entry carries a sequence number,
+//! so the buffer can be put back in order on its own, such as from a RAM dump taken after a crash.
+//! The entries are written before the operation returns, so a future cancelled at any await point never leaves one half-written.
+//!
+//! As with the `observer` feature, resizing an allocation in place and [`IndexAllocator::reset`] aren't recorded,
+//! nor are the frees of the allocations made in bump mode, which are ignored.
+
+use core::ptr::NonNull;
+
+use crate::IndexAllocator;
+
+/// The operation recorded by a [`LogEntry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogOp {
+    /// The slot of the ring buffer was never written.
+    Empty,
+    /// An allocation reserved the bytes.
+    Reserve,
+    /// A free gave the bytes back.
+    Free,
+}
+
+/// An operation recorded in the ring buffer of the operation log, see [`oplog`](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry {
+    /// The position of the operation among all the ones recorded since the buffer was set, starting at 0.
+    pub seq: usize,
+    /// The operation recorded.
+    pub op: LogOp,
+    /// The offset of the allocation from the start of the memory pool.
+    pub offset: usize,
+    /// The number of bytes reserved or given back, including alignment padding.
+    pub size: usize,
+}
+
+impl LogEntry {
+    /// An entry never written, to initialize the ring buffer with.
+    pub const EMPTY: Self = Self {
+        seq: 0,
+        op: LogOp::Empty,
+        offset: 0,
+        size: 0,
+    };
+}
+
+/// The ring buffer an allocator records its operations in.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OpLog {
+    entries: NonNull<LogEntry>,
+    len: usize,
+    /// The number of entries written since the buffer was set.
+    written: usize,
+}
+
+// The buffer was borrowed mutably for `'static`, so the allocator owns it as it owns its memory pool.
+unsafe impl Send for OpLog {}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Record the following allocations and frees in `buf`, replacing the previous buffer, see [`oplog`](self).
+    ///
+    /// The buffer is cleared first. It is only given back by [`IndexAllocator::take_op_log`],
+    /// an empty buffer recording nothing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::oplog::{LogEntry, LogOp};
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// static mut LOG: [LogEntry; 16] = [LogEntry::EMPTY; 16];
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// allocator.set_op_log(unsafe { &mut *core::ptr::addr_of_mut!(LOG) });
+    ///
+    /// drop(allocator.try_boxed([1u8, 2, 3, 4]).unwrap());
+    ///
+    /// let ops: Vec<LogOp> = allocator.op_log_entries().map(|entry| entry.op).collect();
+    /// assert_eq!(ops, [LogOp::Reserve, LogOp::Free]);
+    /// ```
+    pub fn set_op_log(&self, buf: &'static mut [LogEntry]) {
+        buf.fill(LogEntry::EMPTY);
+        let entries = NonNull::new(buf.as_mut_ptr()).filter(|_| !buf.is_empty());
+        self.op_log.set(entries.map(|entries| OpLog {
+            entries,
+            len: buf.len(),
+            written: 0,
+        }));
+    }
+
+    /// Stop recording the operations, giving back the buffer set with [`IndexAllocator::set_op_log`].
+    pub fn take_op_log(&self) -> Option<&'static mut [LogEntry]> {
+        let log = self.op_log.take()?;
+        Some(unsafe { core::slice::from_raw_parts_mut(log.entries.as_ptr(), log.len) })
+    }
+
+    /// Iterate over the entries of the operation log still in its buffer, the oldest first.
+    pub fn op_log_entries(&self) -> impl Iterator<Item = LogEntry> + '_ {
+        let log = self.op_log.get();
+        let (len, written) = log.map_or((0, 0), |log| (log.len, log.written));
+        let first = written.saturating_sub(len);
+
+        (first..written).filter_map(move |seq| {
+            let log = log?;
+            let slot = seq.checked_rem(log.len)?;
+            Some(unsafe { log.entries.as_ptr().add(slot).read() })
+        })
+    }
+
+    /// Append an entry to the operation log, if a buffer is set.
+    pub(crate) fn record_op(&self, op: LogOp, offset: usize, size: usize) {
+        let Some(mut log) = self.op_log.get() else {
+            return;
+        };
+        let Some(slot) = log.written.checked_rem(log.len) else {
+            return;
+        };
+
+        let entry = LogEntry {
+            seq: log.written,
+            op,
+            offset,
+            size,
+        };
+        unsafe { log.entries.as_ptr().add(slot).write(entry) };
+        log.written += 1;
+        self.op_log.set(Some(log));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box as StdBox;
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn test_op_log() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        allocator.set_op_log(StdBox::leak(StdBox::new([LogEntry::EMPTY; 8])));
+
+        let first = allocator.try_boxed([1u8; 16]).unwrap();
+        let second = allocator.try_boxed([2u32; 4]).unwrap();
+        let first_offset = first.as_ptr() as usize - allocator.memory.get() as usize;
+        let second_offset = second.as_ptr() as usize - allocator.memory.get() as usize;
+        drop(first);
+        drop(second);
+        assert!(allocator.try_boxed([0u8; 512]).is_err());
+
+        let entries: Vec<(LogOp, usize)> = allocator
+            .op_log_entries()
+            .map(|entry| (entry.op, entry.offset))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (LogOp::Reserve, first_offset),
+                (LogOp::Reserve, second_offset),
+                (LogOp::Free, first_offset),
+                (LogOp::Free, second_offset),
+            ]
+        );
+        let sizes: Vec<usize> = allocator.op_log_entries().map(|entry| entry.size).collect();
+        assert_eq!(sizes[0], sizes[2]);
+        assert_eq!(sizes[1], sizes[3]);
+        assert!(allocator
+            .op_log_entries()
+            .enumerate()
+            .all(|(seq, entry)| entry.seq == seq));
+
+        let buf = allocator.take_op_log().unwrap();
+        assert_eq!(buf[4], LogEntry::EMPTY);
+        drop(allocator.try_boxed(0u8).unwrap());
+        assert_eq!(allocator.op_log_entries().count(), 0);
+    }
+
+    #[test]
+    fn test_op_log_wraps_around() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        allocator.set_op_log(StdBox::leak(StdBox::new([LogEntry::EMPTY; 3])));
+
+        for _ in 0..4 {
+            drop(allocator.try_boxed(0u32).unwrap());
+        }
+
+        // Only the last 3 of the 8 entries are kept, in order.
+        let seqs: Vec<usize> = allocator.op_log_entries().map(|entry| entry.seq).collect();
+        assert_eq!(seqs, [5, 6, 7]);
+        let ops: Vec<LogOp> = allocator.op_log_entries().map(|entry| entry.op).collect();
+        assert_eq!(ops, [LogOp::Free, LogOp::Reserve, LogOp::Free]);
+    }
+}
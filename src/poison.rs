@@ -0,0 +1,153 @@
+//! This module contains the fail-stop mode of the allocator, stopping it once heap corruption is detected.
+//!
+//! Allocating from a damaged memory pool usually spreads the damage and makes the post-mortem harder.
+//! Once [`IndexAllocator::poison`] is called, every allocation and free fails with an [`IndexError::Poisoned`]:
+//! the global allocator returns null pointers and ignores frees, and the smart pointers leak their memory when dropped,
+//! without panicking. The diagnostics which only read the allocator, such as [`IndexAllocator::heap_stats`]
+//! or the integrity check of the `redzone` feature, keep working so that the failure can be reported.
+//!
+//! With the `redzone` feature, the allocator poisons itself when a free finds the gaps around its allocation overwritten.
+//! The other detectors, such as a failed integrity check or a double free, are up to the caller.
+
+use core::fmt::{self, Display};
+
+use crate::{IndexAllocator, IndexError};
+
+/// The reason an allocator was poisoned, see [`IndexAllocator::poison`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoisonReason {
+    /// The gap around an allocation was overwritten, the offset (in the memory pool) is the first corrupted byte.
+    Canary { offset: usize },
+    /// An integrity check of the memory pool failed.
+    Integrity,
+    /// A region was freed twice.
+    DoubleFree,
+    /// Another corruption, detected by the user.
+    Other(&'static str),
+}
+
+impl Display for PoisonReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Canary { offset } => write!(f, "corrupted canary at offset {offset}"),
+            Self::Integrity => f.write_str("failed integrity check"),
+            Self::DoubleFree => f.write_str("double free"),
+            Self::Other(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Stop the allocator after heap corruption was detected, see [`poison`](self).
+    ///
+    /// Only the first reason is kept, the following corruptions being likely consequences of the first one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::poison::PoisonReason;
+    /// use index_alloc::{IndexAllocator, IndexError};
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    /// allocator.poison(PoisonReason::Other("checksum mismatch"));
+    ///
+    /// assert_eq!(allocator.try_boxed(5u8).err(), Some(IndexError::Poisoned));
+    /// assert_eq!(allocator.heap_stats().allocations, 1);
+    ///
+    /// // The box leaks its memory when dropped.
+    /// drop(test_box);
+    /// assert_eq!(allocator.heap_stats().allocations, 1);
+    /// ```
+    pub fn poison(&self, reason: PoisonReason) {
+        if self.poison_reason.get().is_none() {
+            log_record!(error, "Allocator poisoned: {reason}");
+            self.poison_reason.set(Some(reason));
+        }
+    }
+
+    /// Return the reason the allocator was poisoned with [`IndexAllocator::poison`], if it was.
+    #[must_use]
+    pub fn poison_reason(&self) -> Option<PoisonReason> {
+        self.poison_reason.get()
+    }
+
+    /// Restart an allocator stopped with [`IndexAllocator::poison`].
+    ///
+    /// # Safety
+    ///
+    /// The corruption which poisoned the allocator must have been repaired, or be known to be harmless,
+    /// such as one simulated in a test: the allocator trusts its index and memory pool again.
+    pub unsafe fn unpoison(&self) {
+        self.poison_reason.set(None);
+    }
+
+    /// Fail with an [`IndexError::Poisoned`] if the allocator is poisoned.
+    pub(crate) fn check_poison(&self) -> Result<(), IndexError> {
+        match self.poison_reason.get() {
+            Some(_) => Err(IndexError::Poisoned),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::{GlobalAlloc, Layout};
+
+    use super::*;
+
+    #[test]
+    fn test_poison() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+        let layout = Layout::new::<u32>();
+
+        let test_box = allocator.try_boxed([0u8; 8]).unwrap();
+        let ptr = unsafe { allocator.alloc(layout) };
+        allocator.poison(PoisonReason::DoubleFree);
+        allocator.poison(PoisonReason::Integrity);
+        assert_eq!(allocator.poison_reason(), Some(PoisonReason::DoubleFree));
+
+        assert_eq!(allocator.try_boxed(0u8).err(), Some(IndexError::Poisoned));
+        assert!(unsafe { allocator.alloc(layout) }.is_null());
+        unsafe { allocator.dealloc(ptr, layout) };
+        drop(test_box);
+        assert_eq!(allocator.heap_stats().allocations, 2);
+        assert_eq!(allocator.index.borrow().len(), 3);
+
+        unsafe { allocator.unpoison() };
+        assert_eq!(allocator.poison_reason(), None);
+        unsafe { allocator.dealloc(ptr, layout) };
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        assert!(allocator.try_boxed(0u8).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "redzone")]
+    fn test_poison_on_canary() {
+        use crate::REDZONE_SIZE;
+
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        let mut buffer = allocator.try_boxed([0u8; 16]).unwrap();
+        let end = unsafe { buffer.as_mut_ptr().add(16) };
+        let offset = end as usize - allocator.memory.get() as usize;
+        unsafe { end.write(0) };
+
+        drop(buffer);
+        assert_eq!(
+            allocator.poison_reason(),
+            Some(PoisonReason::Canary { offset })
+        );
+        assert_eq!(allocator.try_boxed(0u8).err(), Some(IndexError::Poisoned));
+
+        // The diagnostics still run on the damaged memory pool.
+        assert!(allocator.check_integrity().is_err());
+        assert_eq!(allocator.heap_stats().used_bytes, 16 + 2 * REDZONE_SIZE);
+        assert_eq!(
+            allocator.largest_free_block(),
+            Ok(128 - 16 - 2 * REDZONE_SIZE)
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! This module lets an [`IndexAllocator`] grow at runtime, by attaching buffers to serve allocations
+//! once its memory pool is exhausted, see [`IndexAllocator::add_region`].
+//!
+//! Regions are addressed by their offset from the start of the memory pool, and the attached buffers keep that scheme:
+//! the offset of a byte in a buffer is its distance to the memory pool, wrapping around the address space
+//! for buffers below it. Offsets of different buffers never overlap, but they may touch when the buffers are adjacent,
+//! which is why every region records the pool it belongs to.
+
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use crate::index::{MemoryIndex, MemoryRegion};
+use crate::{IndexAllocator, IndexError};
+
+/// The number of buffers which can be attached to an [`IndexAllocator`].
+pub const EXTRA_POOLS: usize = 4;
+
+/// A buffer attached to an allocator, with its offset relative to the memory pool.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ExtraPool {
+    start: NonNull<u8>,
+    offset: usize,
+    len: usize,
+}
+
+// The buffer was borrowed mutably for `'static`, so the allocator owns it as it owns its memory pool.
+unsafe impl Send for ExtraPool {}
+
+impl ExtraPool {
+    fn contains(&self, offset: usize) -> bool {
+        offset.wrapping_sub(self.offset) < self.len
+    }
+}
+
+/// The buffers attached to an allocator, in the order they were attached.
+pub(crate) struct ExtraPools {
+    pools: Cell<[Option<ExtraPool>; EXTRA_POOLS]>,
+}
+
+impl ExtraPools {
+    pub const fn new() -> Self {
+        Self {
+            pools: Cell::new([None; EXTRA_POOLS]),
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Attach `buf` to the allocator, as a free region serving the allocations which don't fit in the memory pool.
+    ///
+    /// The buffer is never given back: it is freed and reused as the memory pool is,
+    /// and [`IndexAllocator::reset`] frees it as well. Allocations never span several buffers,
+    /// even when they are adjacent. The buffer takes a slot of the index, like any region.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// static mut EXTRA: [u8; 256] = [0; 256];
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// # if cfg!(feature = "compact-index") { return; }
+    ///
+    /// let _first = allocator.try_boxed([1u8; 48]).unwrap();
+    /// assert!(allocator.try_boxed([2u8; 48]).is_err());
+    ///
+    /// allocator.add_region(unsafe { &mut *core::ptr::addr_of_mut!(EXTRA) }).unwrap();
+    /// let second = allocator.try_boxed([2u8; 48]).unwrap();
+    /// assert!(allocator.owns(second.as_ptr().cast()));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::RegionTooThin`] if `buf` is empty,
+    /// an [`IndexError::OutOfMemory`] with the `compact-index` feature, which doesn't support attached buffers,
+    /// an [`IndexError::NoIndexAvailable`] if the index is full or [`EXTRA_POOLS`] buffers are already attached,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn add_region(&self, buf: &'static mut [u8]) -> Result<(), IndexError> {
+        // The compact index can't hold the offsets of a buffer outside the memory pool.
+        if cfg!(feature = "compact-index") {
+            return Err(IndexError::OutOfMemory);
+        }
+
+        let start = buf.as_mut_ptr();
+        let offset = (start as usize).wrapping_sub(self.memory.get() as usize);
+        // A buffer ending right before the memory pool would end at offset 0 once wrapped around, so its last byte is left out.
+        let len = buf.len().min(usize::MAX - offset);
+        if len == 0 {
+            return Err(IndexError::RegionTooThin);
+        }
+
+        let mut pools = self.extra_pools.pools.get();
+        let (slot, free) = pools
+            .iter_mut()
+            .enumerate()
+            .find(|(_, pool)| pool.is_none())
+            .ok_or(IndexError::NoIndexAvailable)?;
+
+        let mut index = self
+            .index
+            .try_borrow_mut()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let mut region = MemoryRegion::new(offset, len, false);
+        region.pool = slot + 1;
+        index.insert_region(region)?;
+        drop(index);
+
+        *free = Some(ExtraPool {
+            start: NonNull::new(start).ok_or(IndexError::EmptyPtr)?,
+            offset,
+            len,
+        });
+        self.extra_pools.pools.set(pools);
+
+        #[cfg(feature = "async")]
+        self.wake_pending();
+
+        Ok(())
+    }
+
+    /// Get the pointer to the byte at `offset`, in the memory pool or in an attached buffer.
+    pub(crate) fn ptr_at(&self, offset: usize) -> *mut u8 {
+        if offset < MEMORY_SIZE {
+            return self.memory.get().cast::<u8>().wrapping_add(offset);
+        }
+
+        self.extra_pools
+            .pools
+            .get()
+            .into_iter()
+            .flatten()
+            .find(|pool| pool.contains(offset))
+            .map_or_else(
+                || self.memory.get().cast::<u8>().wrapping_add(offset),
+                |pool| {
+                    pool.start
+                        .as_ptr()
+                        .wrapping_add(offset.wrapping_sub(pool.offset))
+                },
+            )
+    }
+
+    /// Test if `offset` is inside an attached buffer.
+    pub(crate) fn in_extra_pool(&self, offset: usize) -> bool {
+        self.extra_pools
+            .pools
+            .get()
+            .into_iter()
+            .flatten()
+            .any(|pool| pool.contains(offset))
+    }
+
+    /// Compute the number of bytes of the attached buffers.
+    pub(crate) fn extra_bytes(&self) -> usize {
+        self.extra_pools
+            .pools
+            .get()
+            .into_iter()
+            .flatten()
+            .map(|pool| pool.len)
+            .sum()
+    }
+
+    /// Build the index of the empty allocator: the memory pool and every attached buffer as free regions.
+    pub(crate) fn empty_index(&self) -> Result<MemoryIndex<INDEX_SIZE>, IndexError> {
+        #[cfg(feature = "buddy")]
+        let mut index = if self.buddy {
+            MemoryIndex::buddy(MEMORY_SIZE)
+        } else {
+            MemoryIndex::empty(MEMORY_SIZE)
+        };
+        #[cfg(not(feature = "buddy"))]
+        let mut index = MemoryIndex::empty(MEMORY_SIZE);
+        self.insert_extra_pools(&mut index)?;
+
+        Ok(index)
+    }
+
+    /// Insert every attached buffer in `index` as a free region.
+    pub(crate) fn insert_extra_pools(
+        &self,
+        index: &mut MemoryIndex<INDEX_SIZE>,
+    ) -> Result<(), IndexError> {
+        for (slot, pool) in self.extra_pools.pools.get().into_iter().enumerate() {
+            if let Some(pool) = pool {
+                let mut region = MemoryRegion::new(pool.offset, pool.len, false);
+                region.pool = slot + 1;
+                index.insert_region(region)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fill every attached buffer with `byte`.
+    pub(crate) unsafe fn fill_extra_pools(&self, byte: u8) {
+        for pool in self.extra_pools.pools.get().into_iter().flatten() {
+            pool.start.as_ptr().write_bytes(byte, pool.len);
+        }
+    }
+
+    /// Overwrite the buffers attached to the allocator with zeros, see the `zeroize` feature.
+    #[cfg(feature = "zeroize")]
+    pub(crate) unsafe fn wipe_extra_pools(&self) {
+        for pool in self.extra_pools.pools.get().into_iter().flatten() {
+            crate::zeroize::wipe_bytes(pool.start.as_ptr(), pool.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box as StdBox;
+    use std::vec;
+
+    use super::*;
+
+    /// Leak a buffer of `len` bytes, to attach it to an allocator.
+    fn leaked_buffer(len: usize) -> &'static mut [u8] {
+        StdBox::leak(vec![0u8; len].into_boxed_slice())
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "compact-index",
+        ignore = "the compact index can't hold the regions of an attached buffer"
+    )]
+    fn test_add_region_past_memory_pool() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let first = allocator.try_boxed([1u8; 48]).unwrap();
+        assert_eq!(
+            allocator.try_boxed([2u8; 48]).err(),
+            Some(IndexError::NoFittingRegion)
+        );
+
+        let buf = leaked_buffer(128);
+        let buf_range = buf.as_ptr_range();
+        allocator.add_region(buf).unwrap();
+        assert_eq!(
+            allocator.heap_stats().free_bytes,
+            64 + 128 - allocator.heap_stats().used_bytes
+        );
+
+        let second = allocator.try_boxed([2u8; 48]).unwrap();
+        let second_ptr = second.as_ptr().cast::<u8>();
+        assert!(buf_range.contains(&second_ptr));
+        assert!(allocator.owns(second_ptr));
+        assert_eq!(*first, [1; 48]);
+        assert_eq!(*second, [2; 48]);
+
+        let mut dump = [0; 4];
+        assert_eq!(allocator.copy_out(second_ptr, &mut dump), Ok(4));
+        assert_eq!(dump, [2; 4]);
+
+        drop(second);
+        drop(first);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.largest_free_block(), Ok(128));
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "compact-index",
+        ignore = "the compact index can't hold the regions of an attached buffer"
+    )]
+    fn test_add_region_never_merged() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        allocator.add_region(leaked_buffer(64)).unwrap();
+        allocator.add_region(leaked_buffer(64)).unwrap();
+
+        // Each pool holds one allocation filling it, none spanning two pools.
+        const SIZE: usize = 64 - 2 * crate::REDZONE_SIZE;
+        let boxes = [
+            allocator.try_boxed([0u8; SIZE]).unwrap(),
+            allocator.try_boxed([1u8; SIZE]).unwrap(),
+            allocator.try_boxed([2u8; SIZE]).unwrap(),
+        ];
+        assert!(allocator.try_boxed(0u8).is_err());
+        drop(boxes);
+
+        assert_eq!(allocator.index.borrow().regions().count(), 3);
+        assert_eq!(
+            allocator.try_boxed([0u8; 128]).err(),
+            Some(IndexError::NoFittingRegion)
+        );
+
+        unsafe { allocator.reset().unwrap() };
+        assert_eq!(allocator.index.borrow().regions().count(), 3);
+        assert_eq!(allocator.heap_stats().free_bytes, 192);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "compact-index",
+        ignore = "the compact index can't hold the regions of an attached buffer"
+    )]
+    fn test_add_region_errors() {
+        let allocator: IndexAllocator<64, 2> = IndexAllocator::empty();
+
+        assert_eq!(
+            allocator.add_region(&mut []),
+            Err(IndexError::RegionTooThin)
+        );
+        allocator.add_region(leaked_buffer(16)).unwrap();
+        assert_eq!(
+            allocator.add_region(leaked_buffer(16)),
+            Err(IndexError::NoIndexAvailable)
+        );
+        assert_eq!(allocator.extra_bytes(), 16);
+    }
+}
@@ -0,0 +1,22 @@
+//! This module re-exports the types most programs need, so that a single line imports them:
+//!
+//! ```
+//! use index_alloc::prelude::*;
+//!
+//! let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+//!
+//! let test_box: Box<u32, 128, 8> = allocator.try_boxed(42).unwrap();
+//! let test_rc = Rc::try_new(*test_box, &allocator).unwrap();
+//! assert_eq!(test_rc.downgrade().upgrade().as_deref(), Some(&42));
+//! ```
+//!
+//! The [`Box`] of this crate shadows the one of the standard library once the prelude is imported.
+
+pub use crate::boxed::{Box, StaticBox};
+pub use crate::rc::{Rc, RcCell, Weak};
+pub use crate::stats::{HeapStats, MemoryStats};
+#[cfg(feature = "critical-section")]
+pub use crate::sync::Locked;
+pub use crate::sync::SingleThreaded;
+pub use crate::vec::IndexVec;
+pub use crate::{IndexAllocator, IndexError};
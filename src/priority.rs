@@ -0,0 +1,163 @@
+//! This module contains the reserve of the allocator, a number of bytes only priority allocations may use,
+//! so that a critical path, such as an interrupt handler, still gets its small buffers once the rest of the program filled the heap.
+//!
+//! The reserve is a budget rather than a range of addresses: normal allocations fail once the free bytes would drop below it,
+//! wherever they are, and it replenishes as allocations are freed. As any free bytes, the reserve may be fragmented,
+//! so a priority allocation only succeeds if a free region fits it.
+
+use core::alloc::Layout;
+use core::ptr::{self, NonNull};
+
+use crate::{IndexAllocator, IndexError, Placement};
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Keep `bytes` free bytes for the priority allocations, such as [`Box::try_new_priority`](crate::boxed::Box::try_new_priority),
+    /// replacing the previous reserve. The other allocations fail rather than leaving fewer free bytes.
+    ///
+    /// Setting a reserve larger than the free bytes doesn't free anything:
+    /// the normal allocations fail until enough bytes are given back.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::boxed::Box;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+    /// allocator.set_reserve(64);
+    ///
+    /// assert!(allocator.try_boxed([0u8; 80]).is_err());
+    /// let background = allocator.try_boxed([0u8; 32]).unwrap();
+    /// assert!(allocator.try_boxed([0u8; 48]).is_err());
+    ///
+    /// let critical = Box::try_new_priority([0u8; 16], &allocator).unwrap();
+    /// ```
+    pub fn set_reserve(&self, bytes: usize) {
+        self.reserve.set(bytes);
+    }
+
+    /// Get the bytes kept for the priority allocations, see [`IndexAllocator::set_reserve`].
+    #[must_use]
+    pub fn reserved_bytes(&self) -> usize {
+        self.reserve.get()
+    }
+
+    /// Get the bytes an allocation may take, all the free bytes for a `priority` one, or the ones above the reserve otherwise.
+    pub(crate) fn budget(&self, priority: bool) -> usize {
+        let free_bytes = (MEMORY_SIZE + self.extra_bytes()).saturating_sub(self.used_bytes.get());
+        if priority {
+            free_bytes
+        } else {
+            free_bytes.saturating_sub(self.reserve.get())
+        }
+    }
+
+    /// Try to allocate `layout` like [`IndexAllocator::try_alloc`], dipping into the reserve if needed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub(crate) unsafe fn try_alloc_priority(&self, layout: Layout) -> Result<*mut u8, IndexError> {
+        self.try_alloc_tier(layout, true, Placement::Low)
+    }
+
+    /// Try to move `val` in a priority allocation, without reserving anything if it takes no space.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub(crate) unsafe fn try_alloc_value_priority<T>(
+        &self,
+        val: T,
+    ) -> Result<NonNull<T>, IndexError> {
+        let layout = Layout::new::<T>();
+        let inner_ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(self.try_alloc_priority(layout)?.cast::<T>())
+                .ok_or(IndexError::EmptyPtr)?
+        };
+        ptr::write(inner_ptr.as_ptr(), val);
+
+        Ok(inner_ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::boxed::Box;
+    use crate::IndexAllocator;
+
+    /// The bytes taken by an allocation of 16 bytes.
+    const BLOCK: usize = 16 + 2 * crate::REDZONE_SIZE;
+
+    #[test]
+    fn test_priority_reserve() {
+        let allocator: IndexAllocator<512, 64> = IndexAllocator::empty();
+        allocator.set_reserve(4 * BLOCK);
+        assert_eq!(allocator.reserved_bytes(), 4 * BLOCK);
+
+        let mut normal = Vec::new();
+        while let Ok(boxed) = allocator.try_boxed([0u8; 16]) {
+            normal.push(boxed);
+        }
+        assert_eq!(normal.len(), (512 - 4 * BLOCK) / BLOCK);
+
+        let mut priority = Vec::new();
+        while let Ok(boxed) = Box::try_new_priority([0u8; 16], &allocator) {
+            priority.push(boxed);
+        }
+        assert!(priority.len() >= 4);
+        assert!(allocator.heap_stats().free_bytes < BLOCK);
+
+        // Freeing a normal allocation gives its bytes back to the reserve first.
+        drop(normal.pop());
+        assert!(allocator.try_boxed([0u8; 16]).is_err());
+
+        // Once the reserve is full again, the normal allocations get the bytes above it.
+        priority.clear();
+        normal.push(allocator.try_boxed([0u8; 16]).unwrap());
+        assert!(allocator.try_boxed([0u8; 16]).is_err());
+        assert_eq!(
+            allocator.heap_stats().free_bytes,
+            512 - normal.len() * BLOCK
+        );
+
+        normal.clear();
+        allocator.set_reserve(0);
+        assert!(allocator
+            .try_boxed([0u8; 512 - 2 * crate::REDZONE_SIZE])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_priority_reserve_in_bump_mode() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        allocator.set_reserve(64);
+
+        allocator.enter_bump_mode().unwrap();
+        let mut values = 0;
+        while allocator.try_leak([0u8; 16]).is_ok() {
+            values += 1;
+        }
+        assert_eq!(values, (256 - 64) / 16);
+        assert!(Box::try_new_priority([0u8; 16], &allocator).is_ok());
+        allocator.exit_bump_mode().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "buddy")]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the size classes"
+    )]
+    fn test_priority_reserve_buddy() {
+        let allocator: IndexAllocator<256, 16> = IndexAllocator::empty_buddy();
+        allocator.set_reserve(128);
+
+        let _first = allocator.try_boxed([0u8; 64]).unwrap();
+        let snapshot = allocator.index.borrow().snapshot();
+        // The free block of 128 bytes fits, but it is larger than the bytes above the reserve.
+        assert!(allocator.try_boxed([0u8; 65]).is_err());
+        assert_eq!(allocator.index.borrow().snapshot(), snapshot);
+
+        let _second = allocator.try_boxed([0u8; 64]).unwrap();
+        assert!(Box::try_new_priority([0u8; 100], &allocator).is_ok());
+    }
+}
@@ -0,0 +1,281 @@
+//! This module contains the allocation profiling tools, grouping live allocations by call site.
+//!
+//! It is only available with the `call-site` feature, which records the call site of every allocation.
+
+use core::cmp::Reverse;
+use core::fmt::{self, Write};
+use core::panic::Location;
+
+use crate::IndexAllocator;
+
+/// The maximum number of distinct call sites a profile report can hold.
+///
+/// Live allocations coming from call sites beyond the first [`PROFILE_SITES`] encountered
+/// are grouped together in a single `<other>` entry, so that the report never needs to allocate.
+pub const PROFILE_SITES: usize = 16;
+
+/// The live allocations made from a single call site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SiteStats {
+    /// The call site, or `None` for the sites which didn't fit in the table.
+    pub location: Option<&'static Location<'static>>,
+    /// The number of live allocations made from the call site.
+    pub count: usize,
+    /// The total size of the regions reserved from the call site.
+    pub bytes: usize,
+    /// The age of the oldest live allocation made from the call site, if it has one, see [`IndexAllocator::set_clock`].
+    #[cfg(feature = "age")]
+    pub oldest: Option<u32>,
+}
+
+impl SiteStats {
+    const fn empty(location: Option<&'static Location<'static>>) -> Self {
+        Self {
+            location,
+            count: 0,
+            bytes: 0,
+            #[cfg(feature = "age")]
+            oldest: None,
+        }
+    }
+
+    /// Count an allocation of `bytes` bytes, `age` ticks old, in the site.
+    fn add(&mut self, bytes: usize, #[cfg(feature = "age")] age: Option<u32>) {
+        self.count += 1;
+        self.bytes += bytes;
+        #[cfg(feature = "age")]
+        {
+            self.oldest = self.oldest.max(age);
+        }
+    }
+}
+
+/// A fixed size table of [`SiteStats`], sorted by descending size.
+struct SiteTable {
+    sites: [Option<SiteStats>; PROFILE_SITES],
+    other: SiteStats,
+}
+
+impl SiteTable {
+    /// Record an allocation of `bytes` bytes made from `location`, `age` ticks old.
+    fn record(
+        &mut self,
+        location: &'static Location<'static>,
+        bytes: usize,
+        #[cfg(feature = "age")] age: Option<u32>,
+    ) {
+        let site = self
+            .sites
+            .iter_mut()
+            .find(|slot| {
+                slot.as_ref()
+                    .is_none_or(|site| site.location == Some(location))
+            })
+            .map(|slot| slot.get_or_insert(SiteStats::empty(Some(location))))
+            .unwrap_or(&mut self.other);
+        site.add(
+            bytes,
+            #[cfg(feature = "age")]
+            age,
+        );
+    }
+
+    /// Sort the sites by descending size, then by descending count and finally by location.
+    fn sort(&mut self) {
+        let key = |site: &SiteStats| {
+            (
+                Reverse(site.bytes),
+                Reverse(site.count),
+                site.location
+                    .map(|location| (location.file(), location.line())),
+            )
+        };
+        self.sites
+            .sort_unstable_by(|site1, site2| match (site1, site2) {
+                (Some(s1), Some(s2)) => key(s1).cmp(&key(s2)),
+                (site1, site2) => site2.is_some().cmp(&site1.is_some()),
+            });
+    }
+
+    /// Iterate over the sites, including the `<other>` entry if it's not empty.
+    fn iter(&self) -> impl Iterator<Item = &SiteStats> {
+        self.sites
+            .iter()
+            .flatten()
+            .chain(Some(&self.other).filter(|other| other.count > 0))
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Group the live allocations by call site, sorted by descending size.
+    fn site_table(&self) -> Result<SiteTable, fmt::Error> {
+        #[cfg(feature = "age")]
+        let now = self.now();
+        let index = self.index.try_borrow().map_err(|_| fmt::Error)?;
+
+        let mut table = SiteTable {
+            sites: [None; PROFILE_SITES],
+            other: SiteStats::empty(None),
+        };
+        for region in index.regions() {
+            if let Some(location) = region.location {
+                table.record(
+                    location,
+                    region.size,
+                    #[cfg(feature = "age")]
+                    crate::age::age(now, region.allocated_at),
+                );
+            }
+        }
+        table.sort();
+
+        Ok(table)
+    }
+
+    /// Write a human readable report of the live allocations grouped by call site,
+    /// one line per site sorted by descending size.
+    ///
+    /// At most [`PROFILE_SITES`] call sites are reported individually, see [`PROFILE_SITES`].
+    ///
+    /// With the `age` feature and a clock set, each line ends with the age of the oldest allocation of the site.
+    ///
+    /// With the `stats` feature, the report ends with the non-empty buckets of
+    /// [`IndexAllocator::size_histogram`], one line per bucket.
+    ///
+    /// # Example
+    ///
+    /// ```text
+    /// src/main.rs:12: 2 allocations, 64 bytes
+    /// src/main.rs:8: 1 allocations, 16 bytes
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`fmt::Error`] if writing failed or if the index is currently in use.
+    pub fn profile_report(&self, w: &mut impl Write) -> fmt::Result {
+        for site in self.site_table()?.iter() {
+            match site.location {
+                Some(location) => write!(w, "{}:{}", location.file(), location.line())?,
+                None => write!(w, "<other>")?,
+            }
+            write!(w, ": {} allocations, {} bytes", site.count, site.bytes)?;
+            #[cfg(feature = "age")]
+            if let Some(oldest) = site.oldest {
+                write!(w, ", oldest {oldest} ticks")?;
+            }
+            writeln!(w)?;
+        }
+
+        #[cfg(feature = "stats")]
+        for bucket in self.size_histogram() {
+            if bucket.requests == 0 {
+                continue;
+            }
+            match bucket.max_size {
+                Some(max_size) => write!(w, "<= {max_size} bytes")?,
+                None => write!(w, "> {} bytes", crate::stats::SIZE_BUCKETS_MAX)?,
+            }
+            writeln!(w, ": {} requests, {} live", bucket.requests, bucket.live)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the same report as [`IndexAllocator::profile_report`] as CSV,
+    /// one `file,line,count,bytes` line per site.
+    ///
+    /// The `<other>` entry has an empty line column.
+    ///
+    /// # Errors
+    ///
+    /// The method return a [`fmt::Error`] if writing failed or if the index is currently in use.
+    pub fn profile_report_csv(&self, w: &mut impl Write) -> fmt::Result {
+        for site in self.site_table()?.iter() {
+            match site.location {
+                Some(location) => write!(w, "{},{}", location.file(), location.line())?,
+                None => write!(w, "<other>,")?,
+            }
+            writeln!(w, ",{},{}", site.count, site.bytes)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::format;
+    use std::string::String;
+
+    use super::*;
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
+    fn test_profile_report() {
+        let allocator: IndexAllocator<256, 16> = IndexAllocator::empty();
+
+        let mut a = [None, None];
+        let line_a = line!() + 2;
+        for slot in &mut a {
+            *slot = Some(allocator.try_boxed([0u8; 8]).unwrap());
+        }
+        let (_b, line_b) = (allocator.try_boxed([0u8; 32]).unwrap(), line!());
+        let mut c = [None, None, None];
+        let line_c = line!() + 2;
+        for slot in &mut c {
+            *slot = Some(allocator.try_boxed([0u8; 4]).unwrap());
+        }
+
+        let mut report = String::new();
+        allocator.profile_report(&mut report).unwrap();
+        let histogram = if cfg!(feature = "stats") {
+            "<= 16 bytes: 5 requests, 5 live\n\
+             <= 32 bytes: 1 requests, 1 live\n"
+        } else {
+            ""
+        };
+        assert_eq!(
+            report,
+            format!(
+                "src/profile.rs:{line_b}: 1 allocations, 32 bytes\n\
+                 src/profile.rs:{line_a}: 2 allocations, 16 bytes\n\
+                 src/profile.rs:{line_c}: 3 allocations, 12 bytes\n\
+                 {histogram}"
+            )
+        );
+
+        let mut csv = String::new();
+        allocator.profile_report_csv(&mut csv).unwrap();
+        assert_eq!(
+            csv,
+            format!(
+                "src/profile.rs,{line_b},1,32\n\
+                 src/profile.rs,{line_a},2,16\n\
+                 src/profile.rs,{line_c},3,12\n"
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "age")]
+    fn test_profile_report_age() {
+        static TIME: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+        let allocator: IndexAllocator<256, 16> = IndexAllocator::empty();
+        allocator.set_clock(|| TIME.load(core::sync::atomic::Ordering::Relaxed));
+
+        let (_a, line) = (allocator.try_boxed([0u8; 8]).unwrap(), line!());
+        TIME.store(42, core::sync::atomic::Ordering::Relaxed);
+
+        let mut report = String::new();
+        allocator.profile_report(&mut report).unwrap();
+        assert!(report.starts_with(&format!("src/profile.rs:{line}: 1 allocations, ")));
+        assert!(report
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with(", oldest 42 ticks"));
+    }
+}
@@ -1,61 +1,99 @@
 //! This module contains the [`Rc`] smart point capable of shared ownership of memory in a [`IndexAllocator`]
 
+use core::alloc::Layout;
+use core::cell::{Cell, RefCell};
 use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::mem;
 use core::ops::Deref;
-use core::{cell::Cell, marker::PhantomData};
+use core::ptr::{self, NonNull};
 
-use crate::{IndexAllocator, IndexError};
+#[cfg(feature = "generations")]
+use crate::generation::Generation;
+use crate::{IndexAllocator, IndexError, UnwindGuard};
 
 /// A smart pointer holding it's value in a [`IndexAllocator`] and managing its memory.
 /// It also keep track of the number of strong and weak references to the inner value.
+///
+/// The inner value is alive as long as the strong count is not 0.
 struct RcBox<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
 where
     T: ?Sized,
 {
-    pub val: Cell<Option<&'a T>>,
+    pub val: NonNull<T>,
     pub strong: Cell<usize>,
     pub weak: Cell<usize>,
     allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    phantom_val: PhantomData<&'a T>,
+}
+
+/// The allocation holding the value of an [`Rc`], followed by a pointer back to its [`RcBox`]
+/// so that [`Rc::from_raw`] finds the counts of a value.
+///
+/// The value comes first, so that the pointer to the value frees the whole allocation, even if it takes no space.
+#[repr(C)]
+struct RcValue<U> {
+    val: U,
+    rc_box: *const (),
 }
 
 impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> RcBox<'a, T, MEMORY_SIZE, INDEX_SIZE>
 where
     T: ?Sized,
 {
-    /// Allocate the inner type and set the strong and weak count to 0.
+    /// Allocate the inner type and set the strong count to 1 and the weak count to 0.
+    /// Return the pointer back to the [`RcBox`] to set once it is allocated as well.
+    #[cfg_attr(feature = "call-site", track_caller)]
     fn try_new<U>(
         val: U,
         allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
-    ) -> Result<Self, IndexError>
+    ) -> Result<(Self, NonNull<*const ()>), IndexError>
     where
         U: 'a,
         &'a T: From<&'a U>,
     {
-        let val_ref = unsafe { allocator.try_alloc_value(val)? };
+        let value_ptr = unsafe {
+            allocator.try_alloc_value(RcValue {
+                val,
+                rc_box: ptr::null(),
+            })?
+        }
+        .as_ptr();
+        // The conversion is user code, which may panic.
+        let guard = UnwindGuard::initialized(allocator, value_ptr);
+        let val = NonNull::from(<&'a T>::from(unsafe { &(*value_ptr).val }));
+        guard.disarm();
+
+        let back = unsafe { NonNull::new_unchecked(ptr::addr_of_mut!((*value_ptr).rc_box)) };
+        Ok((Self::from_value(val, allocator), back))
+    }
 
-        Ok(Self {
-            val: Cell::new(Some(<&'a T>::from(&*val_ref))),
-            strong: Cell::new(0),
+    /// Hold the value at `val`, allocated in `allocator`, with a strong count of 1 and a weak count of 0.
+    fn from_value(val: NonNull<T>, allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        Self {
+            val,
+            strong: Cell::new(1),
             weak: Cell::new(0),
             allocator,
-        })
-    }
-
-    /// Try to free the inner value and set it to None.
-    /// Panic if the inner value is already freed. (Which shouldn't happen).
-    fn try_free_inner(&self) -> Result<(), IndexError> {
-        match self.val.get() {
-            Some(v) => {
-                unsafe {
-                    self.allocator.try_free_value(v)?;
-                }
-                self.val.set(None);
-                Ok(())
-            }
-            None => unreachable!(),
+            phantom_val: PhantomData,
         }
     }
 
+    /// Free the allocation of the inner value, reporting a failure as the `Drop` implementations do.
+    /// This must only be called once, when the strong count gets to 0, and after the value was dropped or moved out.
+    fn drop_free_inner(&self) {
+        free_value(self.allocator, self.val);
+    }
+
+    /// Free the [`RcBox`] itself, reporting a failure as the `Drop` implementations do.
+    /// This must only be called once, when both the strong and weak counts get to 0.
+    fn drop_free_self(&self) {
+        unsafe {
+            self.allocator
+                .drop_free("an Rc box", ptr::from_ref(self).cast_mut())
+        };
+    }
+
     fn increment_strong(&self) {
         self.strong.set(self.strong.get() + 1);
     }
@@ -78,20 +116,16 @@ where
     }
 }
 
-impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Drop
-    for RcBox<'a, T, MEMORY_SIZE, INDEX_SIZE>
-where
+/// Free the allocation holding the value at `val`, reporting a failure as the `Drop` implementations do.
+///
+/// The value is at the start of its [`RcValue`], so its first byte frees the whole allocation, even if it takes no space.
+fn free_value<T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>(
+    allocator: &IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    val: NonNull<T>,
+) where
     T: ?Sized,
 {
-    /// Drop the inner value if not already done.
-    fn drop(&mut self) {
-        if let Some(v) = self.val.get() {
-            unsafe {
-                self.allocator.try_free_value(v).unwrap();
-                self.val.set(None);
-            }
-        }
-    }
+    unsafe { allocator.drop_free("an Rc value", val.as_ptr().cast::<u8>()) };
 }
 
 /// A smart pointer holding its value in an [`IndexAllocator`] and allowing shared ownership between multiple [`Rc`].
@@ -104,16 +138,35 @@ where
 /// use index_alloc::IndexAllocator;
 /// use index_alloc::rc::Rc;
 ///
-/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+/// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
 ///
 /// let test_rc = Rc::try_new([1, 2, 3, 4], &allocator).unwrap();
 /// assert_eq!(*test_rc, [1, 2, 3, 4]);
 /// ```
+///
+/// # Thread safety
+///
+/// The strong and weak counts are stored in non-atomic [`Cell`]s,
+/// so neither [`Rc`] nor [`Weak`] are [`Send`] or [`Sync`], whatever `T` is:
+///
+/// ```compile_fail
+/// use index_alloc::rc::Rc;
+/// use index_alloc::sync::SingleThreaded;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+///
+/// static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+///
+/// let test_rc = Rc::try_new(1u8, &ALLOCATOR).unwrap();
+/// assert_send(&test_rc);
+/// ```
 pub struct Rc<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
 where
     T: ?Sized,
 {
     rc_box: &'a RcBox<'a, T, MEMORY_SIZE, INDEX_SIZE>,
+    #[cfg(feature = "generations")]
+    generation: Generation<'a, MEMORY_SIZE, INDEX_SIZE>,
     phantom_unsync_unsend: PhantomData<*const ()>,
 }
 
@@ -126,6 +179,7 @@ where
     ///
     /// # Errors
     /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
     pub fn try_new<U>(
         val: U,
         allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
@@ -134,15 +188,126 @@ where
         U: 'a,
         &'a T: From<&'a U>,
     {
-        let rc_box = RcBox::try_new(val, allocator)?;
-        rc_box.increment_strong();
+        let (rc_box, back) = RcBox::try_new(val, allocator)?;
+        Self::try_alloc_rc_box(rc_box, back)
+    }
 
-        let rc_box_ref = unsafe { allocator.try_alloc_value(rc_box)? };
+    /// Allocate `rc_box` and point the allocation of its value back to it, freeing the value if it fails.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    fn try_alloc_rc_box(
+        rc_box: RcBox<'a, T, MEMORY_SIZE, INDEX_SIZE>,
+        back: NonNull<*const ()>,
+    ) -> Result<Self, IndexError> {
+        let allocator = rc_box.allocator;
+        let val_ptr = rc_box.val;
+
+        match unsafe { allocator.try_alloc_value(rc_box) } {
+            Ok(rc_box_ptr) => {
+                unsafe { back.as_ptr().write(rc_box_ptr.as_ptr().cast_const().cast()) };
+                Ok(Self {
+                    rc_box: unsafe { &*rc_box_ptr.as_ptr() },
+                    #[cfg(feature = "generations")]
+                    generation: Generation::new(allocator),
+                    phantom_unsync_unsend: Default::default(),
+                })
+            }
+            Err(err) => {
+                // Don't leak the inner value if the box couldn't be allocated:
+                // the `RcBox` has no `Drop` implementation freeing it.
+                free_value(allocator, val_ptr);
+                Err(err)
+            }
+        }
+    }
+
+    /// Return the inner value if the [`Rc`] is its only strong reference, or the [`Rc`] itself otherwise.
+    ///
+    /// The [`Weak`] references left can no longer be upgraded.
+    ///
+    /// # Errors
+    /// The method return the [`Rc`] back if other strong references to its value exist.
+    pub fn try_unwrap(this: Self) -> Result<T, Self>
+    where
+        T: Sized,
+    {
+        if this.rc_box.strong.get() != 1 {
+            return Err(this);
+        }
+
+        let rc_box = this.rc_box;
+        mem::forget(this);
+
+        // The value is read out before its memory is freed, the same way `Drop` releases it.
+        let val = unsafe { ptr::read(rc_box.val.as_ptr()) };
+        rc_box.decrement_strong();
+        rc_box.drop_free_inner();
+
+        if rc_box.weak.get() == 0 {
+            rc_box.drop_free_self();
+        }
+
+        Ok(val)
+    }
+
+    /// Consume the [`Rc`] without decrementing the strong count, returning a pointer to its value,
+    /// such as to pass it through an FFI boundary.
+    ///
+    /// The strong reference is given back by rebuilding the [`Rc`] with [`Rc::from_raw`],
+    /// otherwise the value is never freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    /// use index_alloc::rc::Rc;
+    ///
+    /// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+    ///
+    /// let test_rc = Rc::try_new(42u32, &allocator).unwrap();
+    /// let ptr = Rc::into_raw(test_rc);
+    /// assert_eq!(unsafe { *ptr }, 42);
+    ///
+    /// let test_rc = unsafe { Rc::from_raw(ptr, &allocator) };
+    /// assert_eq!(test_rc.strong_count(), 1);
+    /// ```
+    #[must_use = "the value can only be freed through the returned pointer"]
+    pub fn into_raw(this: Self) -> *const T
+    where
+        T: Sized,
+    {
+        let val = this.rc_box.val.as_ptr().cast_const();
+        mem::forget(this);
+        val
+    }
 
-        Ok(Self {
-            rc_box: rc_box_ref,
+    /// Rebuild an [`Rc`] from a pointer returned by [`Rc::into_raw`], taking back the strong reference it kept.
+    ///
+    /// With the `generations` feature, the [`Rc`] is stamped with the current epoch of `allocator`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`Rc::into_raw`] on an [`Rc`] of `allocator` created from a value of type `T`,
+    /// and every call must match a single call to [`Rc::into_raw`].
+    pub unsafe fn from_raw(
+        ptr: *const T,
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Self
+    where
+        T: Sized,
+    {
+        // The value is the first field of its `RcValue`, which leads to the `RcBox`.
+        let back = ptr
+            .byte_add(mem::offset_of!(RcValue<T>, rc_box))
+            .cast::<*const RcBox<'a, T, MEMORY_SIZE, INDEX_SIZE>>();
+        let rc_box = &*back.read();
+        debug_assert!(ptr::eq(rc_box.allocator(), allocator));
+
+        Self {
+            rc_box,
+            #[cfg(feature = "generations")]
+            generation: Generation::new(allocator),
             phantom_unsync_unsend: Default::default(),
-        })
+        }
     }
 
     /// Create a [`Weak`] reference to the value owned by the [`Rc`].
@@ -150,6 +315,8 @@ where
         self.rc_box.increment_weak();
         Weak {
             rc_box: self.rc_box,
+            #[cfg(feature = "generations")]
+            generation: self.generation,
             phantom_unsync_unsend: Default::default(),
         }
     }
@@ -166,6 +333,15 @@ where
         self.rc_box.weak.get()
     }
 
+    /// Test if the allocator wasn't reset since the [`Rc`] was created, see [`generation`](crate::generation).
+    ///
+    /// An [`Rc`] which isn't valid must not be accessed anymore.
+    #[cfg(feature = "generations")]
+    #[must_use]
+    pub fn is_valid(this: &Self) -> bool {
+        this.generation.is_current()
+    }
+
     /// Get a reference to the [`IndexAllocator`] used by the [`Rc`].
     #[must_use]
     pub fn allocator(&self) -> &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
@@ -173,6 +349,91 @@ where
     }
 }
 
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    Rc<'a, [T], MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Try to create a new [`Rc`] sharing a copy of `slice`, whose length is only known at runtime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    /// use index_alloc::rc::Rc;
+    ///
+    /// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+    ///
+    /// let test_rc = Rc::try_new_slice_copy(&[1u8, 2, 3, 4], &allocator).unwrap();
+    /// assert_eq!(*test_rc, [1, 2, 3, 4]);
+    /// ```
+    ///
+    /// # Errors
+    /// The method return an [`IndexError::LayoutOverflow`] if the slice is too long to be allocated,
+    /// or an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_new_slice_copy(
+        slice: &[T],
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError>
+    where
+        T: Copy,
+    {
+        // The same layout as an `RcValue`: the values, then the pointer back to the `RcBox`.
+        let (layout, back_offset) = Layout::array::<T>(slice.len())
+            .and_then(|values| values.extend(Layout::new::<*const ()>()))
+            .map_err(|_| IndexError::LayoutOverflow)?;
+        let value_ptr = unsafe { allocator.try_alloc(layout)? };
+        let val = NonNull::new(ptr::slice_from_raw_parts_mut(
+            value_ptr.cast::<T>(),
+            slice.len(),
+        ))
+        .ok_or(IndexError::EmptyPtr)?;
+        unsafe {
+            ptr::copy_nonoverlapping(slice.as_ptr(), value_ptr.cast::<T>(), slice.len());
+            let back = NonNull::new_unchecked(value_ptr.add(back_offset).cast::<*const ()>());
+            Self::try_alloc_rc_box(RcBox::from_value(val, allocator), back)
+        }
+    }
+}
+
+/// An [`Rc`] around a [`RefCell`], sharing a mutable value between its clones, such as the nodes of a graph.
+///
+/// The [`RefCell`] methods, such as [`RefCell::borrow`] and [`RefCell::borrow_mut`], are reached through [`Deref`].
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::IndexAllocator;
+/// use index_alloc::rc::{Rc, RcCell};
+///
+/// let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+///
+/// let counter: RcCell<u32, 256, 8> = Rc::try_new_cell(0, &allocator).unwrap();
+/// let shared = counter.clone();
+///
+/// *shared.borrow_mut() += 1;
+/// assert_eq!(*counter.borrow(), 1);
+/// ```
+pub type RcCell<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> =
+    Rc<'a, RefCell<T>, MEMORY_SIZE, INDEX_SIZE>;
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    RcCell<'a, T, MEMORY_SIZE, INDEX_SIZE>
+where
+    T: 'a,
+{
+    /// Try to create a new [`RcCell`], wrapping `val` in a [`RefCell`] shared by the clones of the [`Rc`].
+    ///
+    /// # Errors
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_new_cell(
+        val: T,
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError> {
+        Self::try_new(RefCell::new(val), allocator)
+    }
+}
+
 impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Clone
     for Rc<'a, T, MEMORY_SIZE, INDEX_SIZE>
 where
@@ -186,7 +447,7 @@ where
     /// use index_alloc::IndexAllocator;
     /// use index_alloc::rc::Rc;
     ///
-    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
     ///
     /// let test_rc = Rc::try_new("Hello World", &allocator).unwrap();
     ///
@@ -195,7 +456,6 @@ where
     ///     assert_eq!(*test_ref, "Hello World");
     /// }
     /// ```
-    #[must_use]
     fn clone(&self) -> Self {
         self.rc_box.increment_strong();
         Self { ..*self }
@@ -210,10 +470,8 @@ where
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        match self.rc_box.val.get() {
-            Some(v) => v,
-            None => unreachable!(),
-        }
+        // The value is alive as long as there is a strong reference to it.
+        unsafe { self.rc_box.val.as_ref() }
     }
 }
 
@@ -223,7 +481,34 @@ where
     T: ?Sized + Debug,
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        self.rc_box.val.get().unwrap().fmt(f)
+        (**self).fmt(f)
+    }
+}
+
+/// Format the address of the allocation shared by the [`Rc`], its clones and its [`Weak`] references,
+/// to correlate the log lines about the same allocation.
+///
+/// ```
+/// use index_alloc::IndexAllocator;
+/// use index_alloc::rc::Rc;
+///
+/// let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+///
+/// let test_rc = Rc::try_new(42u32, &allocator).unwrap();
+/// let clone = test_rc.clone();
+/// let other = Rc::try_new(42u32, &allocator).unwrap();
+///
+/// assert_eq!(format!("{test_rc:p}"), format!("{clone:p}"));
+/// assert_eq!(format!("{test_rc:p}"), format!("{:p}", test_rc.downgrade()));
+/// assert_ne!(format!("{test_rc:p}"), format!("{other:p}"));
+/// ```
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> core::fmt::Pointer
+    for Rc<'a, T, MEMORY_SIZE, INDEX_SIZE>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Pointer::fmt(&self.rc_box, f)
     }
 }
 
@@ -233,16 +518,21 @@ where
     T: ?Sized,
 {
     fn drop(&mut self) {
+        #[cfg(feature = "generations")]
+        if !self.generation.check_drop("an Rc") {
+            return;
+        }
+
         self.rc_box.decrement_strong();
         // If the strong count get to 0, drop the inner value.
         if self.rc_box.strong.get() == 0 {
-            self.rc_box.try_free_inner().unwrap();
+            // As for `Box`, a trait object runs the destructor of its concrete type through its vtable.
+            unsafe { ptr::drop_in_place(self.rc_box.val.as_ptr()) };
+            self.rc_box.drop_free_inner();
 
             // If morover the weak count gets to 0, drop the inner box.
             if self.rc_box.weak.get() == 0 {
-                unsafe {
-                    self.allocator().try_free_value(self.rc_box).unwrap();
-                }
+                self.rc_box.drop_free_self();
             }
         }
     }
@@ -260,17 +550,35 @@ where
 /// use index_alloc::IndexAllocator;
 /// use index_alloc::rc::Rc;
 ///
-/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+/// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
 ///
 /// let test_rc = Rc::try_new([1, 2, 3, 4], &allocator).unwrap();
 /// let test_ref = test_rc.downgrade();
 /// assert_eq!(test_ref.strong_count(), 1);
 /// ```
+///
+/// # Thread safety
+///
+/// Like [`Rc`], [`Weak`] is neither [`Send`] nor [`Sync`]:
+///
+/// ```compile_fail
+/// use index_alloc::rc::Rc;
+/// use index_alloc::sync::SingleThreaded;
+///
+/// fn assert_send<T: Send>(_: &T) {}
+///
+/// static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+///
+/// let test_rc = Rc::try_new(1u8, &ALLOCATOR).unwrap();
+/// assert_send(&test_rc.downgrade());
+/// ```
 pub struct Weak<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
 where
     T: ?Sized,
 {
     rc_box: &'a RcBox<'a, T, MEMORY_SIZE, INDEX_SIZE>,
+    #[cfg(feature = "generations")]
+    generation: Generation<'a, MEMORY_SIZE, INDEX_SIZE>,
     phantom_unsync_unsend: PhantomData<*const ()>,
 }
 
@@ -281,10 +589,17 @@ where
     /// Try to upgrade the [`Weak`] reference to a strong reference ([`Rc`]) return `None` if the inner_value was already dropped.
     #[must_use]
     pub fn upgrade(&self) -> Option<Rc<'a, T, MEMORY_SIZE, INDEX_SIZE>> {
+        #[cfg(feature = "generations")]
+        if !self.is_valid() {
+            return None;
+        }
+
         if self.strong_count() > 0 {
             self.rc_box.increment_strong();
             Some(Rc {
                 rc_box: self.rc_box,
+                #[cfg(feature = "generations")]
+                generation: self.generation,
                 phantom_unsync_unsend: Default::default(),
             })
         } else {
@@ -304,6 +619,15 @@ where
         self.rc_box.weak.get()
     }
 
+    /// Test if the allocator wasn't reset since the [`Weak`] reference was created, see [`generation`](crate::generation).
+    ///
+    /// A [`Weak`] reference which isn't valid can't be upgraded anymore.
+    #[cfg(feature = "generations")]
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.generation.is_current()
+    }
+
     /// Get a reference to the [`IndexAllocator`] used by the [`Weak`] reference.
     #[must_use]
     pub fn allocator(&self) -> &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
@@ -330,19 +654,33 @@ where
         write!(f, "(Weak)")
     }
 }
+
+/// Format the address of the allocation shared with the [`Rc`] the [`Weak`] was obtained from.
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> core::fmt::Pointer
+    for Weak<'a, T, MEMORY_SIZE, INDEX_SIZE>
+where
+    T: ?Sized,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Pointer::fmt(&self.rc_box, f)
+    }
+}
 impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Drop
     for Weak<'a, T, MEMORY_SIZE, INDEX_SIZE>
 where
     T: ?Sized,
 {
     fn drop(&mut self) {
+        #[cfg(feature = "generations")]
+        if !self.generation.check_drop("a Weak") {
+            return;
+        }
+
         self.rc_box.decrement_weak();
 
         // If no more reference (strong or weak), drop the inner box.
         if self.rc_box.strong.get() == 0 && self.rc_box.weak.get() == 0 {
-            unsafe {
-                self.allocator().try_free_value(self.rc_box).unwrap();
-            }
+            self.rc_box.drop_free_self();
         }
     }
 }
@@ -354,6 +692,10 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations don't fit in the memory pool"
+    )]
     fn test_rc_allocation() {
         let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
 
@@ -365,11 +707,15 @@ mod tests {
 
         assert_eq!(
             allocator.index.borrow().get_region(0),
-            Ok(&MemoryRegion::new(0, 64, false))
+            Ok(MemoryRegion::new(0, 64, false))
         );
     }
 
     #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations don't fit in the memory pool"
+    )]
     fn test_rc_counting() {
         let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
 
@@ -388,6 +734,10 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations don't fit in the memory pool"
+    )]
     fn test_weak_counting() {
         let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
 
@@ -409,6 +759,10 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations don't fit in the memory pool"
+    )]
     fn test_weak_on_dropped_value() {
         let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
 
@@ -418,13 +772,210 @@ mod tests {
         drop(test_rc);
 
         assert_eq!(test_weak.strong_count(), 0);
-        assert!(matches!(test_weak.upgrade(), None));
+        assert!(test_weak.upgrade().is_none());
 
         drop(test_weak);
 
         assert_eq!(
             allocator.index.borrow().get_region(0),
-            Ok(&MemoryRegion::new(0, 64, false))
+            Ok(MemoryRegion::new(0, 64, false))
+        );
+    }
+
+    #[test]
+    fn test_rc_box_allocation_failure() {
+        // The value fits in the memory pool, but the `RcBox` doesn't fit after it.
+        const MEMORY_SIZE: usize = 16 + 2 * crate::REDZONE_SIZE + 24;
+        let allocator: IndexAllocator<MEMORY_SIZE, 8> = IndexAllocator::empty();
+
+        assert_eq!(
+            Rc::<[u8; 16], MEMORY_SIZE, 8>::try_new([1; 16], &allocator).err(),
+            Some(IndexError::NoFittingRegion)
+        );
+
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.heap_stats().free_bytes, MEMORY_SIZE);
+        assert_eq!(
+            allocator.index.borrow().get_region(0),
+            Ok(MemoryRegion::new(0, MEMORY_SIZE, false))
         );
+        assert!(!allocator.has_failed_drop());
+    }
+
+    /// A value whose conversion to [`Converted`] panics, holding a reference count to check it is dropped.
+    struct Unconvertible {
+        id: u32,
+        _owner: std::rc::Rc<()>,
+    }
+    struct Converted;
+
+    impl<'a> From<&'a Unconvertible> for &'a Converted {
+        fn from(value: &'a Unconvertible) -> Self {
+            panic!("can't convert {}", value.id)
+        }
+    }
+
+    #[test]
+    fn test_rc_conversion_panic() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let owned = std::rc::Rc::new(());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            Rc::<Converted, 64, 8>::try_new(
+                Unconvertible {
+                    id: 42,
+                    _owner: owned.clone(),
+                },
+                &allocator,
+            )
+            .map(|_| ())
+        }));
+        assert!(result.is_err());
+        // The value was moved in before the conversion, so it is dropped rather than leaked.
+        assert_eq!(std::rc::Rc::strong_count(&owned), 1);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(
+            allocator.index.borrow().get_region(0),
+            Ok(MemoryRegion::new(0, 64, false))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations don't fit in the memory pool"
+    )]
+    fn test_rc_try_unwrap() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let test_rc = Rc::try_new([1u8, 2, 3, 4], &allocator).unwrap();
+        let test_clone = Rc::clone(&test_rc);
+        let test_weak = test_rc.downgrade();
+
+        let test_rc = Rc::try_unwrap(test_rc).unwrap_err();
+        drop(test_clone);
+        assert_eq!(Rc::try_unwrap(test_rc).ok(), Some([1, 2, 3, 4]));
+        assert!(test_weak.upgrade().is_none());
+
+        drop(test_weak);
+        assert_eq!(
+            allocator.index.borrow().get_region(0),
+            Ok(MemoryRegion::new(0, 64, false))
+        );
+    }
+
+    /// A value counting its drops.
+    struct Noisy<'a>(&'a Cell<usize>);
+
+    impl Drop for Noisy<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_rc_drops_value_once() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        let drops = Cell::new(0);
+
+        let test_rc = Rc::try_new(Noisy(&drops), &allocator).unwrap();
+        let test_clone = Rc::clone(&test_rc);
+        let test_weak = test_rc.downgrade();
+
+        drop(test_rc);
+        assert_eq!(drops.get(), 0);
+        drop(test_clone);
+        assert_eq!(drops.get(), 1);
+        assert!(test_weak.upgrade().is_none());
+        drop(test_weak);
+        assert_eq!(drops.get(), 1);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+
+        // A value moved out of its Rc is dropped by its new owner only.
+        let test_rc = Rc::try_new(Noisy(&drops), &allocator).unwrap();
+        let val = Rc::try_unwrap(test_rc).ok().unwrap();
+        assert_eq!(drops.get(), 1);
+        drop(val);
+        assert_eq!(drops.get(), 2);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_rc_raw_round_trip() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let test_rc = Rc::try_new([1u64, 2, 3], &allocator).unwrap();
+        let clone = Rc::clone(&test_rc);
+        let test_weak = test_rc.downgrade();
+
+        let ptr = Rc::into_raw(test_rc);
+        assert_eq!(clone.strong_count(), 2);
+        assert_eq!(unsafe { *ptr }, [1, 2, 3]);
+
+        let test_rc = unsafe { Rc::from_raw(ptr, &allocator) };
+        assert_eq!(test_rc.strong_count(), 2);
+        assert_eq!(test_rc.weak_count(), 1);
+        assert_eq!(*test_rc, [1, 2, 3]);
+
+        drop(clone);
+        assert_eq!(allocator.heap_stats().allocations, 2);
+        drop(test_rc);
+        assert!(test_weak.upgrade().is_none());
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        drop(test_weak);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert!(!allocator.has_failed_drop());
+
+        // A value taking no space has an allocation of its own as well, freed once.
+        let unit = Rc::try_new((), &allocator).unwrap();
+        let unit = unsafe { Rc::from_raw(Rc::into_raw(unit), &allocator) };
+        drop(unit);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.largest_free_block(), Ok(256));
+    }
+
+    #[test]
+    fn test_rc_slice_copy() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        let len = core::hint::black_box(6);
+        let source: std::vec::Vec<u8> = (0..len).collect();
+
+        let test_rc: Rc<[u8], 256, 8> = Rc::try_new_slice_copy(&source, &allocator).unwrap();
+        let clone = Rc::clone(&test_rc);
+        assert_eq!(*clone, [0, 1, 2, 3, 4, 5]);
+        assert!(ptr::eq(&*test_rc, &*clone));
+        assert_eq!(test_rc.strong_count(), 2);
+
+        drop(test_rc);
+        assert_eq!(clone.len(), 6);
+        drop(clone);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+
+        let empty = Rc::<[u32], 256, 8>::try_new_slice_copy(&[], &allocator).unwrap();
+        assert!(empty.is_empty());
+        drop(empty);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert_eq!(allocator.largest_free_block(), Ok(256));
+        assert!(!allocator.has_failed_drop());
+    }
+
+    #[test]
+    fn test_rc_cell_shared_mutation() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let first: RcCell<[u32; 4], 256, 8> = Rc::try_new_cell([0; 4], &allocator).unwrap();
+        let second = Rc::clone(&first);
+
+        first.borrow_mut()[0] = 1;
+        second.borrow_mut()[1] = 2;
+        assert_eq!(*first.borrow(), [1, 2, 0, 0]);
+        assert_eq!(*second.borrow(), [1, 2, 0, 0]);
+
+        let reading = first.borrow();
+        assert!(second.try_borrow_mut().is_err());
+        drop(reading);
+
+        drop(first);
+        assert_eq!(Rc::try_unwrap(second).unwrap().into_inner(), [1, 2, 0, 0]);
     }
 }
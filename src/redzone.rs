@@ -0,0 +1,167 @@
+//! This module contains the red-zone checks, catching writes just before or just after an allocation.
+//!
+//! It is only available with the `redzone` feature, which surrounds every allocation with a gap of [`REDZONE_SIZE`] bytes
+//! filled with [`REDZONE_BYTE`]. The gaps are part of the used region, so they are never handed out,
+//! even when the neighbour region is free, and [`HeapStats::used_bytes`](crate::stats::HeapStats::used_bytes) counts them.
+//!
+//! The gaps are verified when an allocation is freed (reporting corruption through `log` records with the `log` feature)
+//! and on demand with [`IndexAllocator::check_integrity`].
+
+use core::fmt::{self, Display};
+
+use crate::index::MemoryRegion;
+use crate::{IndexAllocator, IndexError};
+
+/// The size of the gaps before and after every allocation.
+pub const REDZONE_SIZE: usize = 8;
+
+/// The byte the gaps are filled with.
+pub const REDZONE_BYTE: u8 = 0xFD;
+
+/// The error raised by [`IndexAllocator::check_integrity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityError {
+    /// The gap before an allocation was overwritten, the offset (in the memory pool) is the first corrupted byte.
+    Underrun { offset: usize },
+    /// The gap after an allocation was overwritten, the offset (in the memory pool) is the first corrupted byte.
+    Overrun { offset: usize },
+    /// The index couldn't be read.
+    Index(IndexError),
+}
+
+impl Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Underrun { offset } => write!(f, "buffer underrun at offset {offset}"),
+            Self::Overrun { offset } => write!(f, "buffer overrun at offset {offset}"),
+            Self::Index(err) => err.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for IntegrityError {}
+
+impl From<IndexError> for IntegrityError {
+    fn from(err: IndexError) -> Self {
+        Self::Index(err)
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Check the gaps around every live allocation are intact.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::redzone::IntegrityError;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let mut test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    /// assert_eq!(allocator.check_integrity(), Ok(()));
+    ///
+    /// // Write one byte past the end of the array.
+    /// unsafe { test_box.as_mut_ptr().add(4).write(5) };
+    /// assert!(matches!(
+    ///     allocator.check_integrity(),
+    ///     Err(IntegrityError::Overrun { .. })
+    /// ));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return the first [`IntegrityError`] found, going through the index in order,
+    /// or an [`IntegrityError::Index`] if the index is currently in use.
+    pub fn check_integrity(&self) -> Result<(), IntegrityError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        let result = index
+            .regions()
+            .filter(|region| region.used && !region.arena)
+            .try_for_each(|region| self.check_redzones(&region));
+        result
+    }
+
+    /// Check the gaps around the allocation at `addr` (relative to the memory pool).
+    pub(crate) fn check_allocation(&self, addr: usize) -> Result<(), IntegrityError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+        let region = index.get_region(index.find_region(addr)?)?;
+
+        // A free region has no gaps, freeing it again is reported as a double free.
+        // The allocations in a bump arena have no gaps either.
+        if !region.used || region.arena {
+            return Ok(());
+        }
+
+        self.check_redzones(&region)
+    }
+
+    fn check_redzones(&self, region: &MemoryRegion) -> Result<(), IntegrityError> {
+        let before = (region.from + region.data_offset).saturating_sub(REDZONE_SIZE);
+        if let Some(offset) = self.find_corrupted(before) {
+            return Err(IntegrityError::Underrun { offset });
+        }
+
+        let after = region.end().saturating_sub(REDZONE_SIZE);
+        if let Some(offset) = self.find_corrupted(after) {
+            return Err(IntegrityError::Overrun { offset });
+        }
+
+        Ok(())
+    }
+
+    /// Find the first byte of the gap starting at `from` which isn't [`REDZONE_BYTE`].
+    fn find_corrupted(&self, from: usize) -> Option<usize> {
+        (from..from.saturating_add(REDZONE_SIZE))
+            .filter(|offset| *offset < MEMORY_SIZE || self.in_extra_pool(*offset))
+            .find(|offset| unsafe { self.ptr_at(*offset).read() } != REDZONE_BYTE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redzone_underrun() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        let _before = allocator.try_boxed([0u8; 4]).unwrap();
+        let mut buffer = allocator.try_boxed([0u8; 16]).unwrap();
+        let _after = allocator.try_boxed([0u8; 4]).unwrap();
+        assert_eq!(allocator.check_integrity(), Ok(()));
+
+        let start = buffer.as_mut_ptr();
+        let offset = start as usize - allocator.memory.get() as usize;
+        unsafe { start.sub(1).write(0) };
+
+        assert_eq!(
+            allocator.check_integrity(),
+            Err(IntegrityError::Underrun { offset: offset - 1 })
+        );
+    }
+
+    #[test]
+    fn test_redzone_overrun_next_to_free_region() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        let mut buffer = allocator.try_boxed([0u8; 16]).unwrap();
+        let end = unsafe { buffer.as_mut_ptr().add(16) };
+        let offset = end as usize - allocator.memory.get() as usize;
+        unsafe { end.write(0) };
+
+        assert_eq!(
+            allocator.check_integrity(),
+            Err(IntegrityError::Overrun { offset })
+        );
+        assert_eq!(allocator.heap_stats().used_bytes, 16 + 2 * REDZONE_SIZE);
+        assert_eq!(buffer.slack(), Ok(0));
+    }
+}
@@ -0,0 +1,155 @@
+//! This module contains the [`Reservation`] handle, reserving room for data whose final size isn't known yet.
+
+use core::alloc::Layout;
+use core::fmt::Debug;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::boxed::Box;
+use crate::{IndexAllocator, IndexError};
+
+/// A buffer reserved at its maximum size in an [`IndexAllocator`], obtained with [`IndexAllocator::reserve_max`].
+///
+/// The data is written in the buffer as it comes, and once its size is known,
+/// [`Reservation::commit`] gives the unused tail back to the [`IndexAllocator`] without moving the data.
+/// A [`Reservation`] dropped without being committed frees the whole buffer.
+///
+/// # Example
+///
+/// ```
+/// use core::alloc::Layout;
+///
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+///
+/// let mut reservation = allocator
+///     .reserve_max(Layout::array::<u8>(64).unwrap())
+///     .unwrap();
+/// let message = b"streamed";
+/// reservation[..message.len()].copy_from_slice(message);
+///
+/// let buffer = reservation.commit(message.len()).unwrap();
+/// assert_eq!(&*buffer, message);
+/// ```
+pub struct Reservation<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    buffer: Box<'a, [u8], MEMORY_SIZE, INDEX_SIZE>,
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    Reservation<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Keep the first `len` bytes of the buffer and give the others back to the [`IndexAllocator`],
+    /// returning the committed bytes in a [`Box`]. A `len` greater than the buffer keeps it whole.
+    ///
+    /// # Errors
+    ///
+    /// The method return the [`Reservation`] unchanged if the tail couldn't be given back,
+    /// for instance when the index is full and the tail needs a new region.
+    pub fn commit(mut self, len: usize) -> Result<Box<'a, [u8], MEMORY_SIZE, INDEX_SIZE>, Self> {
+        match self.buffer.try_shrink_to(len) {
+            Ok(()) => Ok(self.buffer),
+            Err(_) => Err(self),
+        }
+    }
+
+    /// Get a reference to the [`IndexAllocator`] used by the [`Reservation`].
+    #[must_use]
+    pub fn allocator(&self) -> &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+        self.buffer.allocator()
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Try to reserve a buffer of the size of `max_layout`, to be shrunk to the actual size of its data
+    /// with [`Reservation::commit`], see [`Reservation`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn reserve_max(
+        &self,
+        max_layout: Layout,
+    ) -> Result<Reservation<'_, MEMORY_SIZE, INDEX_SIZE>, IndexError> {
+        let inner_ptr = if max_layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(unsafe { self.try_alloc(max_layout)? }).ok_or(IndexError::EmptyPtr)?
+        };
+
+        let buffer = unsafe { slice::from_raw_parts_mut(inner_ptr.as_ptr(), max_layout.size()) };
+        Ok(Reservation {
+            buffer: unsafe { Box::from_raw_ref(buffer, self) },
+        })
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Deref
+    for Reservation<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> DerefMut
+    for Reservation<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Debug
+    for Reservation<'a, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Reservation")
+            .field("len", &self.buffer.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservation_commit() {
+        let allocator: IndexAllocator<512, 8> = IndexAllocator::empty();
+
+        let reservation = allocator
+            .reserve_max(Layout::array::<u8>(256).unwrap())
+            .unwrap();
+        assert_eq!(reservation.len(), 256);
+        let free_bytes = allocator.heap_stats().free_bytes;
+
+        let buffer = reservation.commit(40).unwrap();
+        assert_eq!(buffer.len(), 40);
+        assert_eq!(allocator.heap_stats().free_bytes, free_bytes + 216);
+
+        drop(buffer);
+        assert_eq!(allocator.heap_stats().free_bytes, 512);
+    }
+
+    #[test]
+    fn test_reservation_commit_index_full() {
+        let allocator: IndexAllocator<512, 3> = IndexAllocator::empty();
+
+        let reservation = allocator
+            .reserve_max(Layout::array::<u8>(256).unwrap())
+            .unwrap();
+        let _next = allocator.try_boxed(0u8).unwrap();
+
+        // The tail can't be merged in a free region and there is no index left to hold it.
+        let reservation = reservation.commit(40).unwrap_err();
+        assert_eq!(reservation.len(), 256);
+
+        drop(reservation);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+    }
+}
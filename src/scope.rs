@@ -0,0 +1,312 @@
+//! This module contains the [`Scope`] guard, freeing every allocation made through it when it goes out of scope.
+
+use core::cell::Cell;
+use core::fmt::Debug;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+use crate::{IndexAllocator, IndexError};
+
+/// The default number of allocations a [`Scope`] can track at the same time.
+pub const SCOPE_CAPACITY: usize = 16;
+
+/// An allocation tracked by a [`Scope`], with the function dropping its value.
+#[derive(Clone, Copy)]
+struct ScopeEntry {
+    ptr: NonNull<u8>,
+    /// The size of the value, as the type-erased pointer no longer tells values taking no space apart.
+    size: usize,
+    drop_value: unsafe fn(*mut u8),
+}
+
+/// Drop the value of type `T` at `ptr`, used to type-erase the values of a [`Scope`].
+unsafe fn drop_value<T>(ptr: *mut u8) {
+    ptr::drop_in_place(ptr.cast::<T>());
+}
+
+/// A guard tracking the allocations made through it, which are all dropped and freed with the [`Scope`].
+///
+/// A [`Scope`] is obtained with [`IndexAllocator::scope`] and allocates [`ScopedBox`] with [`Scope::try_boxed`].
+/// A [`ScopedBox`] dropped before the [`Scope`] frees its memory as usual, the others
+/// (including the ones passed to [`core::mem::forget`]) are dropped and freed when the [`Scope`] is,
+/// which guarantees the cleanup even on early returns.
+/// Unlike resetting the whole allocator, allocations made outside the [`Scope`] are left alone.
+///
+/// The [`Scope`] can track up to `CAPACITY` live allocations.
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::{IndexAllocator, IndexError};
+///
+/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+///
+/// fn work(allocator: &IndexAllocator<64, 8>) -> Result<u8, IndexError> {
+///     let scope = allocator.scope();
+///     let a = scope.try_boxed(1u8)?;
+///     let b = scope.try_boxed([0u8; 128])?;
+///     Ok(*a + b[0])
+/// }
+///
+/// assert_eq!(work(&allocator), Err(IndexError::NoFittingRegion));
+/// assert_eq!(allocator.heap_stats().allocations, 0);
+/// ```
+///
+/// A [`ScopedBox`] can't outlive its [`Scope`]:
+///
+/// ```compile_fail
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+///
+/// let escaped = {
+///     let scope = allocator.scope();
+///     scope.try_boxed(1u8).unwrap()
+/// };
+/// ```
+pub struct Scope<
+    'a,
+    const MEMORY_SIZE: usize,
+    const INDEX_SIZE: usize,
+    const CAPACITY: usize = SCOPE_CAPACITY,
+> {
+    entries: [Cell<Option<ScopeEntry>>; CAPACITY],
+    allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize, const CAPACITY: usize>
+    Scope<'a, MEMORY_SIZE, INDEX_SIZE, CAPACITY>
+{
+    /// Create an empty [`Scope`] allocating in `allocator`.
+    #[must_use]
+    pub fn new(allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        Self {
+            entries: [const { Cell::new(None) }; CAPACITY],
+            allocator,
+        }
+    }
+
+    /// Try to allocate the value in the memory pool and return a [`ScopedBox`] tracked by the [`Scope`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::NoIndexAvailable`] if the [`Scope`] already tracks `CAPACITY` allocations
+    /// or an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_boxed<'s, T>(
+        &'s self,
+        val: T,
+    ) -> Result<ScopedBox<'s, 'a, T, MEMORY_SIZE, INDEX_SIZE, CAPACITY>, IndexError>
+    where
+        // The value may only be dropped with the scope, so it must outlive it.
+        T: 'a,
+    {
+        let slot = self
+            .entries
+            .iter()
+            .position(|entry| entry.get().is_none())
+            .ok_or(IndexError::NoIndexAvailable)?;
+
+        let inner_ptr = unsafe { self.allocator.try_alloc_value(val)? };
+        if let Some(entry) = self.entries.get(slot) {
+            entry.set(Some(ScopeEntry {
+                ptr: inner_ptr.cast(),
+                size: mem::size_of::<T>(),
+                drop_value: drop_value::<T>,
+            }));
+        }
+
+        Ok(ScopedBox {
+            val: unsafe { &mut *inner_ptr.as_ptr() },
+            scope: self,
+            slot,
+        })
+    }
+
+    /// Return the number of allocations currently tracked by the [`Scope`].
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.get().is_some())
+            .count()
+    }
+
+    /// Test if the [`Scope`] doesn't track any allocation.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get a reference to the [`IndexAllocator`] used by the [`Scope`].
+    #[must_use]
+    pub fn allocator(&self) -> &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+        self.allocator
+    }
+
+    /// Drop and free a tracked value. Values taking no space were never reserved and are only dropped.
+    unsafe fn release(&self, entry: ScopeEntry) {
+        (entry.drop_value)(entry.ptr.as_ptr());
+        if entry.size != 0 {
+            self.allocator.drop_free("a scoped Box", entry.ptr.as_ptr());
+        }
+    }
+}
+
+impl<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize, const CAPACITY: usize> Drop
+    for Scope<'a, MEMORY_SIZE, INDEX_SIZE, CAPACITY>
+{
+    fn drop(&mut self) {
+        for entry in &self.entries {
+            if let Some(entry) = entry.take() {
+                unsafe { self.release(entry) };
+            }
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Create a [`Scope`] tracking up to [`SCOPE_CAPACITY`] allocations, see [`Scope`].
+    #[must_use]
+    pub fn scope(&self) -> Scope<'_, MEMORY_SIZE, INDEX_SIZE> {
+        Scope::new(self)
+    }
+}
+
+/// A smart pointer holding its value in an [`IndexAllocator`], tracked by the [`Scope`] which created it.
+///
+/// It is obtained with [`Scope::try_boxed`].
+pub struct ScopedBox<
+    's,
+    'a,
+    T,
+    const MEMORY_SIZE: usize,
+    const INDEX_SIZE: usize,
+    const CAPACITY: usize = SCOPE_CAPACITY,
+> {
+    val: &'s mut T,
+    scope: &'s Scope<'a, MEMORY_SIZE, INDEX_SIZE, CAPACITY>,
+    slot: usize,
+}
+
+impl<'s, 'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize, const CAPACITY: usize> Drop
+    for ScopedBox<'s, 'a, T, MEMORY_SIZE, INDEX_SIZE, CAPACITY>
+{
+    fn drop(&mut self) {
+        if let Some(entry) = self.scope.entries.get(self.slot).and_then(Cell::take) {
+            unsafe { self.scope.release(entry) };
+        }
+    }
+}
+
+impl<'s, 'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize, const CAPACITY: usize> Deref
+    for ScopedBox<'s, 'a, T, MEMORY_SIZE, INDEX_SIZE, CAPACITY>
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.val
+    }
+}
+
+impl<'s, 'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize, const CAPACITY: usize> DerefMut
+    for ScopedBox<'s, 'a, T, MEMORY_SIZE, INDEX_SIZE, CAPACITY>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.val
+    }
+}
+
+impl<'s, 'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize, const CAPACITY: usize> Debug
+    for ScopedBox<'s, 'a, T, MEMORY_SIZE, INDEX_SIZE, CAPACITY>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.val.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Count its drops in a shared counter.
+    struct DropCounter<'c>(&'c Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn scoped_work(
+        allocator: &IndexAllocator<128, 8>,
+        drops: &Cell<usize>,
+    ) -> Result<(), IndexError> {
+        let scope = allocator.scope();
+
+        let kept = scope.try_boxed(DropCounter(drops))?;
+        let dropped = scope.try_boxed(DropCounter(drops))?;
+        mem::forget(scope.try_boxed(DropCounter(drops))?);
+        drop(dropped);
+        assert_eq!(drops.get(), 1);
+        assert_eq!(scope.len(), 2);
+
+        let _too_large = scope.try_boxed([0u8; 256])?;
+        drop(kept);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scope_early_return() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+        let drops = Cell::new(0);
+
+        let outside = allocator.try_boxed(1u8).unwrap();
+        assert_eq!(
+            scoped_work(&allocator, &drops),
+            Err(IndexError::NoFittingRegion)
+        );
+
+        assert_eq!(drops.get(), 3);
+        assert_eq!(allocator.heap_stats().allocations, 1);
+        assert_eq!(*outside, 1);
+    }
+
+    #[test]
+    fn test_scope_capacity() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+        let scope: Scope<128, 8, 2> = Scope::new(&allocator);
+
+        let _a = scope.try_boxed(1u8).unwrap();
+        let b = scope.try_boxed(2u8).unwrap();
+        assert_eq!(
+            scope.try_boxed(3u8).map(|_| ()),
+            Err(IndexError::NoIndexAvailable)
+        );
+
+        drop(b);
+        assert_eq!(*scope.try_boxed(4u8).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_scope_zero_sized() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+        let drops = Cell::new(0);
+
+        {
+            let scope = allocator.scope();
+            drop(scope.try_boxed(()).unwrap());
+            mem::forget(scope.try_boxed(()).unwrap());
+            let _counted = scope.try_boxed(DropCounter(&drops)).unwrap();
+            assert_eq!(allocator.heap_stats().allocations, 1);
+        }
+
+        // The values taking no space are dropped without being freed, which would fail.
+        assert_eq!(drops.get(), 1);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+        assert!(!allocator.has_failed_drop());
+    }
+}
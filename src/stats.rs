@@ -0,0 +1,731 @@
+//! This module contains the statistics an [`IndexAllocator`] keeps about its memory pool.
+//!
+//! With the `stats` feature, the allocator also keeps a histogram of the requested sizes, see `IndexAllocator::size_histogram`.
+//!
+//! A hook can also be called when the memory usage reaches a watermark, see [`IndexAllocator::set_watermark`].
+
+use core::alloc::Layout;
+#[cfg(feature = "stats")]
+use core::cell::Cell;
+
+use crate::{IndexAllocator, IndexError, REDZONE_SIZE};
+
+/// The number of buckets of the size histogram.
+#[cfg(feature = "stats")]
+pub const SIZE_BUCKETS: usize = 10;
+
+/// The largest size of each bucket of the size histogram but the last one, which holds the larger requests.
+#[cfg(feature = "stats")]
+const BUCKET_MAX_SIZES: [usize; SIZE_BUCKETS - 1] =
+    [16, 32, 64, 128, 256, 512, 1024, 2048, SIZE_BUCKETS_MAX];
+
+/// The largest size of the bounded buckets of the size histogram, the last bucket holds the requests above it.
+#[cfg(feature = "stats")]
+pub const SIZE_BUCKETS_MAX: usize = 4096;
+
+/// The allocations of a range of requested sizes, see [`IndexAllocator::size_histogram`].
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BucketStats {
+    /// The largest size (in bytes) of the bucket, or `None` for the last bucket, which has no upper bound.
+    pub max_size: Option<usize>,
+    /// The number of allocations requested since the allocator was created.
+    pub requests: usize,
+    /// The number of live allocations.
+    pub live: usize,
+}
+
+/// The counters of the size histogram, updated on each allocation and deallocation.
+#[cfg(feature = "stats")]
+pub(crate) struct SizeHistogram {
+    requests: [Cell<usize>; SIZE_BUCKETS],
+    live: [Cell<usize>; SIZE_BUCKETS],
+}
+
+#[cfg(feature = "stats")]
+impl SizeHistogram {
+    pub const fn new() -> Self {
+        Self {
+            requests: [const { Cell::new(0) }; SIZE_BUCKETS],
+            live: [const { Cell::new(0) }; SIZE_BUCKETS],
+        }
+    }
+
+    fn bucket(size: usize) -> usize {
+        BUCKET_MAX_SIZES
+            .iter()
+            .position(|max_size| size <= *max_size)
+            .unwrap_or(SIZE_BUCKETS - 1)
+    }
+
+    /// Record an allocation of `size` requested bytes.
+    pub fn record_alloc(&self, size: usize) {
+        let bucket = Self::bucket(size);
+        if let (Some(requests), Some(live)) = (self.requests.get(bucket), self.live.get(bucket)) {
+            requests.set(requests.get() + 1);
+            live.set(live.get() + 1);
+        }
+    }
+
+    /// Forget every live allocation, keeping the number of requests.
+    pub fn clear_live(&self) {
+        for live in &self.live {
+            live.set(0);
+        }
+    }
+
+    /// Record the deallocation of an allocation of `size` requested bytes.
+    pub fn record_free(&self, size: usize) {
+        if let Some(live) = self.live.get(Self::bucket(size)) {
+            live.set(live.get().saturating_sub(1));
+        }
+    }
+}
+
+/// The threshold set with [`IndexAllocator::set_watermark`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Watermark {
+    bytes: usize,
+    hook: fn(HeapStats),
+    /// Whether the hook fires when the usage reaches the threshold, cleared until the usage drops under the margin.
+    armed: bool,
+}
+
+/// A snapshot of the memory usage of an [`IndexAllocator`].
+///
+/// It is obtained with [`IndexAllocator::heap_stats`], which only reads counters kept up to date
+/// on each allocation and deallocation, so it is cheap and can't fail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// The number of bytes held by used regions, including alignment padding.
+    pub used_bytes: usize,
+    /// The number of bytes not held by any used region.
+    pub free_bytes: usize,
+    /// The number of live allocations.
+    pub allocations: usize,
+}
+
+/// The layout of the memory index of an [`IndexAllocator`], to see how close it is to running out of memory or index slots.
+///
+/// It is obtained with [`IndexAllocator::stats`], which walks the whole index,
+/// unlike [`IndexAllocator::heap_stats`] which only reads counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The number of bytes held by used regions, including alignment padding.
+    pub used_bytes: usize,
+    /// The number of bytes held by free regions.
+    pub free_bytes: usize,
+    /// The size of the largest free region, see [`IndexAllocator::largest_free_block`].
+    pub largest_free_block: usize,
+    /// The number of used regions, a bump arena counting as one.
+    pub used_regions: usize,
+    /// The number of free regions, which may be more than needed in lazy merge mode.
+    /// The empty regions left by allocations filling a region exactly aren't counted, but still take a slot.
+    pub free_regions: usize,
+    /// The number of slots of the index holding no region.
+    pub index_slots_free: usize,
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Get the current [`HeapStats`] of the allocator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    /// let stats = allocator.heap_stats();
+    /// assert_eq!(stats.allocations, 1);
+    /// assert!(stats.used_bytes >= 4);
+    /// assert_eq!(stats.used_bytes + stats.free_bytes, 64);
+    ///
+    /// drop(test_box);
+    /// assert_eq!(allocator.heap_stats().free_bytes, 64);
+    /// ```
+    #[must_use]
+    pub fn heap_stats(&self) -> HeapStats {
+        let used_bytes = self.used_bytes.get();
+
+        HeapStats {
+            used_bytes,
+            free_bytes: (MEMORY_SIZE + self.extra_bytes()).saturating_sub(used_bytes),
+            allocations: self.allocations.get(),
+        }
+    }
+
+    /// Get the number of free bytes the allocator was built with, which [`HeapStats::free_bytes`] starts from.
+    ///
+    /// It is the whole memory pool for an empty allocator, but the blobs placed by a
+    /// [`ConstPoolBuilder`](crate::const_pool::ConstPoolBuilder) are held from the start.
+    /// Unlike [`HeapStats::free_bytes`], it doesn't change with allocations nor with the pools added later.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// assert_eq!(allocator.initial_free_bytes(), 64);
+    ///
+    /// let _test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    /// assert_eq!(allocator.initial_free_bytes(), 64);
+    /// ```
+    #[must_use]
+    pub fn initial_free_bytes(&self) -> usize {
+        self.initial_free_bytes
+    }
+
+    /// Compute the [`MemoryStats`] of the allocator by walking its index.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    ///
+    /// let _test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    /// let stats = allocator.stats().unwrap();
+    /// assert_eq!(stats.used_regions, 1);
+    /// assert_eq!(stats.free_regions, 1);
+    /// assert_eq!(stats.index_slots_free, 6);
+    /// assert_eq!(stats.used_bytes + stats.free_bytes, 64);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn stats(&self) -> Result<MemoryStats, IndexError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        let mut stats = MemoryStats {
+            index_slots_free: INDEX_SIZE,
+            ..MemoryStats::default()
+        };
+        for region in index.regions() {
+            stats.index_slots_free -= 1;
+            if region.used {
+                stats.used_bytes += region.size;
+                stats.used_regions += 1;
+            } else if region.size > 0 {
+                stats.free_bytes += region.size;
+                stats.free_regions += 1;
+                stats.largest_free_block = stats.largest_free_block.max(region.size);
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Get the size of the largest free region, which is the largest allocation that can succeed
+    /// (minus the alignment offset).
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn largest_free_block(&self) -> Result<usize, IndexError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        Ok(index.largest_free_block())
+    }
+
+    /// Compute how many bytes the largest free region would grow by if every free region were merged into one,
+    /// that is the total of the free regions minus the largest one.
+    ///
+    /// A gain of 0 means the free memory is already contiguous, so moving the allocations wouldn't help.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+    ///
+    /// let first = allocator.try_boxed([0u8; 16]).unwrap();
+    /// let _second = allocator.try_boxed([0u8; 16]).unwrap();
+    /// assert_eq!(allocator.compaction_gain(), Ok(0));
+    ///
+    /// drop(first);
+    /// assert!(allocator.compaction_gain().unwrap() > 0);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn compaction_gain(&self) -> Result<usize, IndexError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        Ok(index.free_bytes() - index.largest_free_block())
+    }
+
+    /// Test if `layout` would fit once every free region is merged into one, see [`IndexAllocator::compaction_gain`].
+    ///
+    /// The address of the merged region isn't known, so the worst alignment offset is assumed:
+    /// a layout reported to fit always fits, but a layout aligned further than a byte may fit while being reported not to.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn would_fit_after_compaction(&self, layout: Layout) -> Result<bool, IndexError> {
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        Ok(layout
+            .size()
+            .checked_add(2 * REDZONE_SIZE + (layout.align() - 1))
+            .is_some_and(|size| size <= index.free_bytes()))
+    }
+
+    /// Set a bit in `out` for every `granularity` bytes chunk of the memory pool overlapping a used region,
+    /// to find the chunks to save in an incremental snapshot. Return the number of chunks.
+    ///
+    /// The chunk `i` is the bit `i % 8` of the byte `i / 8`, the bits of the free chunks being cleared.
+    /// The buffers attached with [`IndexAllocator::add_region`] aren't covered.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed([0u8; 4]).unwrap();
+    /// let mut bitmap = [0xFF; 2];
+    /// assert_eq!(allocator.used_bitmap(32, &mut bitmap), Ok(8));
+    /// assert_eq!(bitmap, [0b0000_0001, 0xFF]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::RegionTooThin`] if `granularity` is 0 or `out` has less than a bit per chunk,
+    /// or an [`IndexError::IndexAlreadyBorrowed`] if the index is currently in use.
+    pub fn used_bitmap(&self, granularity: usize, out: &mut [u8]) -> Result<usize, IndexError> {
+        if granularity == 0 {
+            return Err(IndexError::RegionTooThin);
+        }
+        let chunks = MEMORY_SIZE.div_ceil(granularity);
+        let bitmap = out
+            .get_mut(..chunks.div_ceil(8))
+            .ok_or(IndexError::RegionTooThin)?;
+        let index = self
+            .index
+            .try_borrow()
+            .map_err(|_| IndexError::IndexAlreadyBorrowed)?;
+
+        bitmap.fill(0);
+        for region in index
+            .regions()
+            .filter(|region| region.used && region.from < MEMORY_SIZE)
+        {
+            let last = region.end().min(MEMORY_SIZE) - 1;
+            for chunk in region.from / granularity..=last / granularity {
+                if let Some(byte) = bitmap.get_mut(chunk / 8) {
+                    *byte |= 1 << (chunk % 8);
+                }
+            }
+        }
+
+        Ok(chunks)
+    }
+
+    /// Get the histogram of the requested allocation sizes, by power of two buckets from 16 to 4096 bytes,
+    /// the last bucket holding the larger requests.
+    ///
+    /// The size is the one requested, so a request whose region was padded for alignment
+    /// stays in the bucket it was requested in.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+    ///
+    /// let test_box = allocator.try_boxed([0u8; 20]).unwrap();
+    /// let histogram = allocator.size_histogram();
+    /// assert_eq!(histogram[1].max_size, Some(32));
+    /// assert_eq!(histogram[1].live, 1);
+    /// ```
+    #[cfg(feature = "stats")]
+    #[must_use]
+    pub fn size_histogram(&self) -> [BucketStats; SIZE_BUCKETS] {
+        let mut histogram = [BucketStats::default(); SIZE_BUCKETS];
+        for (i, bucket) in histogram.iter_mut().enumerate() {
+            *bucket = BucketStats {
+                max_size: BUCKET_MAX_SIZES.get(i).copied(),
+                requests: self.size_histogram.requests.get(i).map_or(0, Cell::get),
+                live: self.size_histogram.live.get(i).map_or(0, Cell::get),
+            };
+        }
+        histogram
+    }
+
+    /// Call `hook` when the used bytes reach `bytes`, see [`HeapStats::used_bytes`].
+    ///
+    /// The hook fires once when an allocation makes the usage reach the threshold,
+    /// and fires again only after the usage dropped under the threshold minus a margin of 10 %,
+    /// so an usage hovering around the threshold doesn't call it on every allocation.
+    /// It runs once the index is released, so it may read the statistics, log or even allocate.
+    /// Setting a new watermark replaces the previous one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// use index_alloc::stats::HeapStats;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// fn shed_load(_stats: HeapStats) {
+    ///     WARNINGS.fetch_add(1, Ordering::Relaxed);
+    /// }
+    ///
+    /// let allocator: IndexAllocator<1024, 16> = IndexAllocator::empty();
+    /// allocator.set_watermark(800, shed_load);
+    ///
+    /// let _large = allocator.try_boxed([0u8; 900]).unwrap();
+    /// assert_eq!(WARNINGS.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn set_watermark(&self, bytes: usize, hook: fn(HeapStats)) {
+        self.watermark.set(Some(Watermark {
+            bytes,
+            hook,
+            armed: true,
+        }));
+    }
+
+    /// Remove the watermark set with [`IndexAllocator::set_watermark`].
+    pub fn clear_watermark(&self) {
+        self.watermark.set(None);
+    }
+
+    /// Fire or re-arm the watermark after the used bytes changed.
+    ///
+    /// It must be called once the index is released, as the hook may allocate.
+    pub(crate) fn check_watermark(&self) {
+        let Some(mut watermark) = self.watermark.get() else {
+            return;
+        };
+        let used_bytes = self.used_bytes.get();
+
+        if watermark.armed && used_bytes >= watermark.bytes {
+            watermark.armed = false;
+            self.watermark.set(Some(watermark));
+            (watermark.hook)(self.heap_stats());
+        } else if !watermark.armed && used_bytes < watermark.bytes - watermark.bytes / 10 {
+            watermark.armed = true;
+            self.watermark.set(Some(watermark));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{MemoryIndex, MemoryRegion};
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
+    fn test_heap_stats() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let first_box = allocator.try_boxed([0u8; 8]).unwrap();
+        let second_box = allocator.try_boxed([0u8; 4]).unwrap();
+        assert_eq!(
+            allocator.heap_stats(),
+            HeapStats {
+                used_bytes: 12,
+                free_bytes: 52,
+                allocations: 2
+            }
+        );
+
+        drop(first_box);
+        assert_eq!(
+            allocator.heap_stats(),
+            HeapStats {
+                used_bytes: 4,
+                free_bytes: 60,
+                allocations: 1
+            }
+        );
+
+        drop(second_box);
+        assert_eq!(allocator.heap_stats().used_bytes, 0);
+    }
+
+    #[test]
+    fn test_memory_stats() {
+        const GAPS: usize = 2 * REDZONE_SIZE;
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        allocator.set_lazy_merge(true);
+
+        // Byte arrays take no alignment padding, so the used bytes are their sizes and gaps.
+        let first = allocator.try_boxed([0u8; 32]).unwrap();
+        let second = allocator.try_boxed([0u8; 32]).unwrap();
+        let _third = allocator.try_boxed([0u8; 16]).unwrap();
+        let rest = Layout::array::<u8>(256 - 80 - 4 * GAPS).unwrap();
+        let _fill = allocator.try_allocate(rest).unwrap();
+        let slots_free = || 8 - allocator.index.borrow().len();
+        assert_eq!(
+            allocator.stats(),
+            Ok(MemoryStats {
+                used_bytes: 256,
+                free_bytes: 0,
+                largest_free_block: 0,
+                used_regions: 4,
+                free_regions: 0,
+                index_slots_free: slots_free(),
+            })
+        );
+
+        // Lazily freed, the two regions side by side are only merged on demand.
+        drop((first, second));
+        let stats = allocator.stats().unwrap();
+        assert_eq!(stats.used_bytes, 16 + rest.size() + 2 * GAPS);
+        assert_eq!(stats.free_regions, 2);
+        assert_eq!(stats.largest_free_block, 32 + GAPS);
+
+        allocator.merge_now().unwrap();
+        let stats = allocator.stats().unwrap();
+        assert_eq!(stats.free_bytes, 64 + 2 * GAPS);
+        assert_eq!(stats.free_regions, 1);
+        assert_eq!(stats.largest_free_block, 64 + 2 * GAPS);
+        assert_eq!(stats.index_slots_free, slots_free());
+
+        let _borrow = allocator.index.borrow_mut();
+        assert_eq!(allocator.stats(), Err(IndexError::IndexAlreadyBorrowed));
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the memory layout"
+    )]
+    fn test_used_bitmap() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let _first = allocator.try_boxed([0u8; 20]).unwrap();
+        let gap = allocator.try_boxed([0u8; 44]).unwrap();
+        let _second = allocator.try_boxed([0u8; 8]).unwrap();
+        drop(gap);
+
+        // The first allocation spans the chunks 0 and 1, the second one the chunk 4.
+        let mut bitmap = [0xFF; 3];
+        assert_eq!(allocator.used_bitmap(16, &mut bitmap), Ok(16));
+        assert_eq!(bitmap, [0b0001_0011, 0, 0xFF]);
+
+        assert_eq!(allocator.used_bitmap(100, &mut bitmap), Ok(3));
+        assert_eq!(bitmap[0], 0b001);
+
+        assert_eq!(
+            allocator.used_bitmap(1, &mut bitmap),
+            Err(IndexError::RegionTooThin)
+        );
+        assert_eq!(
+            allocator.used_bitmap(0, &mut bitmap),
+            Err(IndexError::RegionTooThin)
+        );
+    }
+
+    /// A fragmented index of a 256 bytes pool, with 216 free bytes in three regions, the largest holding 120.
+    fn fragmented_index() -> MemoryIndex<8> {
+        MemoryIndex::new([
+            Some(MemoryRegion::new(0, 32, false)),
+            Some(MemoryRegion::new(32, 32, true)),
+            Some(MemoryRegion::new(64, 64, false)),
+            Some(MemoryRegion::new(128, 8, true)),
+            Some(MemoryRegion::new(136, 120, false)),
+            None,
+            None,
+            None,
+        ])
+    }
+
+    #[test]
+    fn test_compaction_gain() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+        assert_eq!(allocator.compaction_gain(), Ok(0));
+
+        *allocator.index.borrow_mut() = fragmented_index();
+        assert_eq!(allocator.compaction_gain(), Ok(96));
+        assert_eq!(allocator.largest_free_block(), Ok(120));
+
+        // The same allocations, moved to the start of the memory pool.
+        let merged: MemoryIndex<8> = MemoryIndex::new([
+            Some(MemoryRegion::new(0, 32, true)),
+            Some(MemoryRegion::new(32, 8, true)),
+            Some(MemoryRegion::new(40, 216, false)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        ]);
+        // A layout larger than the largest free region only fits after compaction.
+        let large = Layout::new::<[u8; 150]>();
+        assert!(allocator.try_boxed([0u8; 150]).is_err());
+        assert_eq!(allocator.would_fit_after_compaction(large), Ok(true));
+
+        let memory_start = allocator.memory.get() as usize;
+        for (size, align) in [(100, 1), (216, 1), (217, 1), (150, 8), (200, 16), (210, 16)] {
+            let size = size - 2 * REDZONE_SIZE;
+            let layout = Layout::from_size_align(size, align).unwrap();
+            let reserved = Layout::from_size_align(size + 2 * REDZONE_SIZE, align).unwrap();
+            let fits_merged = merged
+                .size_region_available(memory_start + REDZONE_SIZE, reserved)
+                .is_ok();
+            let predicted = allocator.would_fit_after_compaction(layout).unwrap();
+
+            assert!(!predicted || fits_merged, "{layout:?} predicted to fit");
+            if align == 1 {
+                assert_eq!(predicted, fits_merged, "{layout:?}");
+            }
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_size_histogram() {
+        let allocator: IndexAllocator<8192, 16> = IndexAllocator::empty();
+
+        let small = allocator.try_boxed([0u8; 3]).unwrap();
+        let _small = allocator.try_boxed(0u64).unwrap();
+        let _medium = allocator.try_boxed([0u8; 100]).unwrap();
+        let large = allocator.try_boxed([0u8; 5000]).unwrap();
+        // Requested as 17 bytes, but granted more room to align the value on 16 bytes.
+        let over_granted = unsafe {
+            allocator
+                .try_alloc(core::alloc::Layout::from_size_align(17, 16).unwrap())
+                .unwrap()
+        };
+        drop(small);
+        drop(large);
+        unsafe { allocator.try_free(over_granted).unwrap() };
+
+        let histogram = allocator.size_histogram();
+        let counts: [(usize, usize); SIZE_BUCKETS] =
+            core::array::from_fn(|i| (histogram[i].requests, histogram[i].live));
+        assert_eq!(
+            counts,
+            [
+                (2, 1),
+                (1, 0),
+                (0, 0),
+                (1, 1),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (1, 0)
+            ]
+        );
+        assert_eq!(histogram[SIZE_BUCKETS - 1].max_size, None);
+    }
+
+    #[test]
+    fn test_watermark() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        static USED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+        fn hook(stats: HeapStats) {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            USED_BYTES.store(stats.used_bytes, Ordering::SeqCst);
+        }
+
+        let allocator: IndexAllocator<2048, 64> = IndexAllocator::empty();
+        allocator.set_watermark(800, hook);
+        let used_bytes = || allocator.heap_stats().used_bytes;
+        let mut boxes = std::vec::Vec::new();
+
+        while used_bytes() < 800 {
+            assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+            boxes.push(allocator.try_boxed([0u8; 20]).unwrap());
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(USED_BYTES.load(Ordering::SeqCst), used_bytes());
+
+        // Further allocations above the threshold don't fire it again.
+        boxes.push(allocator.try_boxed([0u8; 20]).unwrap());
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        // Dropping under the threshold but not under the margin doesn't re-arm it.
+        while used_bytes() >= 800 {
+            boxes.pop();
+        }
+        assert!(used_bytes() >= 720);
+        while used_bytes() < 800 {
+            boxes.push(allocator.try_boxed([0u8; 20]).unwrap());
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        while used_bytes() >= 720 {
+            boxes.pop();
+        }
+        while used_bytes() < 800 {
+            boxes.push(allocator.try_boxed([0u8; 20]).unwrap());
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_hooks_introspect() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::sync::SingleThreaded;
+
+        static ALLOCATOR: SingleThreaded<256, 8> = unsafe { SingleThreaded::empty() };
+        static INTROSPECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+        // The hooks run once the index is released, so they can query it.
+        fn introspect() {
+            assert!(ALLOCATOR.largest_free_block().is_ok());
+            assert!(ALLOCATOR.compaction_gain().is_ok());
+            INTROSPECTIONS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn hook(_stats: HeapStats) {
+            introspect();
+        }
+
+        ALLOCATOR.set_watermark(64, hook);
+        #[cfg(feature = "age")]
+        ALLOCATOR.set_clock(|| {
+            introspect();
+            0
+        });
+
+        let _large = ALLOCATOR.try_boxed([0u8; 100]).unwrap();
+        assert!(INTROSPECTIONS.load(Ordering::SeqCst) >= 1);
+
+        // In bump mode as well.
+        ALLOCATOR.clear_watermark();
+        ALLOCATOR.enter_bump_mode().unwrap();
+        ALLOCATOR.set_watermark(128, hook);
+        let before = INTROSPECTIONS.load(Ordering::SeqCst);
+        let _small = ALLOCATOR.try_boxed([0u8; 32]).unwrap();
+        assert!(INTROSPECTIONS.load(Ordering::SeqCst) > before);
+    }
+}
@@ -0,0 +1,366 @@
+//! This module contains the wrappers making an [`IndexAllocator`] usable in a `static`, such as the global allocator.
+//!
+//! An [`IndexAllocator`] keeps its state in cells which aren't thread-safe, so it isn't [`Sync`]
+//! and can't be shared between threads or placed in a `static` on its own.
+//! Two wrappers make it [`Sync`]:
+//!
+//! - [`SingleThreaded`], which doesn't lock anything and is only sound when the allocator is used by a single thread.
+//!   It still gives access to the whole [`IndexAllocator`] API, including `'static` boxes.
+#![cfg_attr(
+    feature = "critical-section",
+    doc = "- [`Locked`], which runs every operation in a critical section, see the [`critical_section`] crate."
+)]
+#![cfg_attr(
+    not(feature = "critical-section"),
+    doc = "- `Locked`, which runs every operation in a critical section, with the `critical-section` feature."
+)]
+//!
+//! [`HeaderSingleThreaded`] and `HeaderLocked` do the same for a [`HeaderIndexAllocator`].
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ops::Deref;
+#[cfg(feature = "critical-section")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "critical-section")]
+use critical_section::Mutex;
+
+use crate::header::HeaderIndexAllocator;
+#[cfg(feature = "critical-section")]
+use crate::stats::HeapStats;
+use crate::IndexAllocator;
+
+/// An [`IndexAllocator`] declared to be only used by a single thread, which makes it [`Sync`] without locking.
+///
+/// It dereferences to its [`IndexAllocator`] and implements [`GlobalAlloc`].
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::sync::SingleThreaded;
+///
+/// // Safety: the allocator is only used by the main thread.
+/// static ALLOCATOR: SingleThreaded<64, 8> = unsafe { SingleThreaded::empty() };
+///
+/// let test_box = ALLOCATOR.try_boxed([1u8, 2, 3, 4]).unwrap();
+/// assert_eq!(*test_box, [1, 2, 3, 4]);
+/// ```
+pub struct SingleThreaded<const MEMORY_SIZE: usize, const INDEX_SIZE: usize>(
+    IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+);
+
+// Safety: the caller of `SingleThreaded::new` guarantees the allocator is never used by two threads.
+unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Sync
+    for SingleThreaded<MEMORY_SIZE, INDEX_SIZE>
+{
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> SingleThreaded<MEMORY_SIZE, INDEX_SIZE> {
+    /// Wrap an [`IndexAllocator`] only used by a single thread.
+    ///
+    /// # Safety
+    ///
+    /// The allocator must never be used by two threads, nor by an interrupt handler preempting its thread.
+    /// As the global allocator, this means the program must not spawn threads.
+    #[must_use]
+    pub const unsafe fn new(allocator: IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        Self(allocator)
+    }
+
+    /// Create an empty [`IndexAllocator`] only used by a single thread, see [`IndexAllocator::empty`].
+    ///
+    /// # Safety
+    ///
+    /// See [`SingleThreaded::new`].
+    #[must_use]
+    pub const unsafe fn empty() -> Self {
+        Self(IndexAllocator::empty())
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Deref
+    for SingleThreaded<MEMORY_SIZE, INDEX_SIZE>
+{
+    type Target = IndexAllocator<MEMORY_SIZE, INDEX_SIZE>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> GlobalAlloc
+    for SingleThreaded<MEMORY_SIZE, INDEX_SIZE>
+{
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0.alloc(layout)
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.0.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.0.realloc(ptr, layout, new_size)
+    }
+}
+
+/// An [`IndexAllocator`] running every operation in a critical section, which makes it [`Sync`].
+///
+/// It is only available with the `critical-section` feature, the critical section being provided
+/// by an implementation of the [`critical_section`] crate for the target.
+///
+/// The [`IndexAllocator`] is only reachable inside a critical section, through [`Locked::with`],
+/// so smart pointers can't be kept outside of it: [`Locked`] is meant to be the global allocator.
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::sync::Locked;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: Locked<16384, 128> = Locked::empty();
+///
+/// fn main() {
+///     let handle = std::thread::spawn(|| String::from("Hello World"));
+///     assert_eq!(handle.join().unwrap(), "Hello World");
+///     assert!(ALLOCATOR.heap_stats().allocations > 0);
+/// }
+/// ```
+#[cfg(feature = "critical-section")]
+pub struct Locked<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    allocator: Mutex<IndexAllocator<MEMORY_SIZE, INDEX_SIZE>>,
+    stats: AtomicStats,
+}
+
+/// A copy of the [`HeapStats`] of a [`Locked`] allocator, published after every operation
+/// so that it can be read without entering a critical section.
+///
+/// Only loads and stores are used, which even the targets without atomic read-modify-write operations have.
+#[cfg(feature = "critical-section")]
+struct AtomicStats {
+    used_bytes: AtomicUsize,
+    free_bytes: AtomicUsize,
+    allocations: AtomicUsize,
+}
+
+#[cfg(feature = "critical-section")]
+impl AtomicStats {
+    const fn new(free_bytes: usize) -> Self {
+        Self {
+            used_bytes: AtomicUsize::new(0),
+            free_bytes: AtomicUsize::new(free_bytes),
+            allocations: AtomicUsize::new(0),
+        }
+    }
+
+    fn store(&self, stats: HeapStats) {
+        self.used_bytes.store(stats.used_bytes, Ordering::Relaxed);
+        self.free_bytes.store(stats.free_bytes, Ordering::Relaxed);
+        self.allocations.store(stats.allocations, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> HeapStats {
+        HeapStats {
+            used_bytes: self.used_bytes.load(Ordering::Relaxed),
+            free_bytes: self.free_bytes.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(feature = "critical-section")]
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Locked<MEMORY_SIZE, INDEX_SIZE> {
+    /// Create an empty [`IndexAllocator`] locked by a critical section, see [`IndexAllocator::empty`].
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            allocator: Mutex::new(IndexAllocator::empty()),
+            stats: AtomicStats::new(MEMORY_SIZE),
+        }
+    }
+
+    /// Run `f` with the [`IndexAllocator`], in a critical section.
+    pub fn with<R>(&self, f: impl FnOnce(&IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> R) -> R {
+        critical_section::with(|cs| {
+            let allocator = self.allocator.borrow(cs);
+            let result = f(allocator);
+            self.stats.store(allocator.heap_stats());
+            result
+        })
+    }
+
+    /// Get the [`HeapStats`] of the allocator as of the last operation, see [`IndexAllocator::heap_stats`].
+    ///
+    /// The statistics are read without entering a critical section, so reading them never holds up the allocations.
+    /// Each counter is up to date, but while other threads allocate, they may come from consecutive operations.
+    #[must_use]
+    pub fn heap_stats(&self) -> HeapStats {
+        self.stats.load()
+    }
+}
+
+#[cfg(feature = "critical-section")]
+unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> GlobalAlloc
+    for Locked<MEMORY_SIZE, INDEX_SIZE>
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.with(|allocator| allocator.alloc(layout))
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.with(|allocator| allocator.alloc_zeroed(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.with(|allocator| allocator.dealloc(ptr, layout));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.with(|allocator| allocator.realloc(ptr, layout, new_size))
+    }
+}
+
+/// A [`HeaderIndexAllocator`] declared to be only used by a single thread, see [`SingleThreaded`].
+///
+/// It dereferences to its [`HeaderIndexAllocator`] and implements [`GlobalAlloc`].
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::sync::HeaderSingleThreaded;
+///
+/// // Safety: the program never allocates from two threads.
+/// #[global_allocator]
+/// static ALLOCATOR: HeaderSingleThreaded<65536> = unsafe { HeaderSingleThreaded::empty() };
+///
+/// fn main() {
+///     // As many live allocations as the memory holds, without an index to size.
+///     let values: Vec<Box<u32>> = (0..500).map(Box::new).collect();
+///     assert!(ALLOCATOR.heap_stats().allocations >= 500);
+///     assert_eq!(*values[499], 499);
+/// }
+/// ```
+pub struct HeaderSingleThreaded<const MEMORY_SIZE: usize>(HeaderIndexAllocator<MEMORY_SIZE>);
+
+// Safety: the caller of `HeaderSingleThreaded::new` guarantees the allocator is never used by two threads.
+unsafe impl<const MEMORY_SIZE: usize> Sync for HeaderSingleThreaded<MEMORY_SIZE> {}
+
+impl<const MEMORY_SIZE: usize> HeaderSingleThreaded<MEMORY_SIZE> {
+    /// Wrap a [`HeaderIndexAllocator`] only used by a single thread.
+    ///
+    /// # Safety
+    ///
+    /// See [`SingleThreaded::new`].
+    #[must_use]
+    pub const unsafe fn new(allocator: HeaderIndexAllocator<MEMORY_SIZE>) -> Self {
+        Self(allocator)
+    }
+
+    /// Create an empty [`HeaderIndexAllocator`] only used by a single thread, see [`HeaderIndexAllocator::empty`].
+    ///
+    /// # Safety
+    ///
+    /// See [`SingleThreaded::new`].
+    #[must_use]
+    pub const unsafe fn empty() -> Self {
+        Self(HeaderIndexAllocator::empty())
+    }
+}
+
+impl<const MEMORY_SIZE: usize> Deref for HeaderSingleThreaded<MEMORY_SIZE> {
+    type Target = HeaderIndexAllocator<MEMORY_SIZE>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+unsafe impl<const MEMORY_SIZE: usize> GlobalAlloc for HeaderSingleThreaded<MEMORY_SIZE> {
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.0.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.0.realloc(ptr, layout, new_size)
+    }
+}
+
+/// A [`HeaderIndexAllocator`] running every operation in a critical section, see [`Locked`].
+///
+/// It is only available with the `critical-section` feature.
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::sync::HeaderLocked;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: HeaderLocked<65536> = HeaderLocked::empty();
+///
+/// fn main() {
+///     let handle = std::thread::spawn(|| String::from("Hello World"));
+///     assert_eq!(handle.join().unwrap(), "Hello World");
+///     assert!(ALLOCATOR.heap_stats().allocations > 0);
+/// }
+/// ```
+#[cfg(feature = "critical-section")]
+pub struct HeaderLocked<const MEMORY_SIZE: usize> {
+    allocator: Mutex<HeaderIndexAllocator<MEMORY_SIZE>>,
+    stats: AtomicStats,
+}
+
+#[cfg(feature = "critical-section")]
+impl<const MEMORY_SIZE: usize> HeaderLocked<MEMORY_SIZE> {
+    /// Create an empty [`HeaderIndexAllocator`] locked by a critical section, see [`HeaderIndexAllocator::empty`].
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            allocator: Mutex::new(HeaderIndexAllocator::empty()),
+            stats: AtomicStats::new(MEMORY_SIZE),
+        }
+    }
+
+    /// Run `f` with the [`HeaderIndexAllocator`], in a critical section.
+    pub fn with<R>(&self, f: impl FnOnce(&HeaderIndexAllocator<MEMORY_SIZE>) -> R) -> R {
+        critical_section::with(|cs| {
+            let allocator = self.allocator.borrow(cs);
+            let result = f(allocator);
+            self.stats.store(allocator.heap_stats());
+            result
+        })
+    }
+
+    /// Get the [`HeapStats`] of the allocator as of the last operation, see [`Locked::heap_stats`].
+    #[must_use]
+    pub fn heap_stats(&self) -> HeapStats {
+        self.stats.load()
+    }
+}
+
+#[cfg(feature = "critical-section")]
+unsafe impl<const MEMORY_SIZE: usize> GlobalAlloc for HeaderLocked<MEMORY_SIZE> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.with(|allocator| allocator.alloc(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.with(|allocator| allocator.dealloc(ptr, layout));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.with(|allocator| allocator.realloc(ptr, layout, new_size))
+    }
+}
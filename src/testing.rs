@@ -0,0 +1,362 @@
+//! This module contains the [`TrackingAllocator`], checking the tests of a crate give back everything they allocate.
+//!
+//! It is only available with the `testing` feature, meant to be enabled in the `[dev-dependencies]` of the crate:
+//! the failure injection it adds to the allocation path, see [`IndexAllocator::set_fail_after`], has no place in a release build.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::Cell;
+use core::fmt::{self, Display};
+use core::ops::Deref;
+
+use crate::{IndexAllocator, IndexError};
+
+/// The balance of a [`TrackingAllocator`] since a reference point, see [`TrackingAllocator::outstanding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outstanding {
+    /// The number of allocations not freed, negative if more allocations were freed than made.
+    pub allocations: isize,
+    /// The number of bytes held by the allocations not freed, negative if more were freed than reserved.
+    pub bytes: isize,
+    /// The number of double frees through the [`GlobalAlloc`] implementation of the [`TrackingAllocator`].
+    pub double_frees: usize,
+}
+
+impl Outstanding {
+    /// Test if everything allocated was freed, once.
+    #[must_use]
+    pub fn is_balanced(&self) -> bool {
+        self.allocations == 0 && self.bytes == 0 && self.double_frees == 0
+    }
+}
+
+/// The state of a [`TrackingAllocator`] at a reference point.
+#[derive(Clone, Copy)]
+struct Snapshot<const INDEX_SIZE: usize> {
+    allocations: usize,
+    used_bytes: usize,
+    double_frees: usize,
+    /// The offsets of the live allocations.
+    live: [Option<usize>; INDEX_SIZE],
+}
+
+/// An [`IndexAllocator`] counting what is allocated and freed, to check a test gives everything back.
+///
+/// The balance is read from the allocator itself, so the allocations made through its smart pointers
+/// are counted as well as the ones of its [`GlobalAlloc`] implementation. The double frees are only counted
+/// through the [`GlobalAlloc`] implementation, the other ways to free memory returning an [`IndexError::DoubleFree`].
+///
+/// It dereferences to its [`IndexAllocator`], failures being injected with [`IndexAllocator::set_fail_after`].
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::testing::TrackingAllocator;
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: TrackingAllocator<256, 16> = TrackingAllocator::new(IndexAllocator::empty());
+///
+/// let sum = allocator.assert_balanced(|| {
+///     let values = allocator.try_boxed([1u32, 2, 3]).unwrap();
+///     values.iter().sum::<u32>()
+/// });
+/// assert_eq!(sum, 6);
+/// allocator.check();
+/// ```
+pub struct TrackingAllocator<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    allocator: IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    baseline: Snapshot<INDEX_SIZE>,
+    double_frees: Cell<usize>,
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> TrackingAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Wrap an [`IndexAllocator`], the allocations it already holds being the reference point of [`TrackingAllocator::check`].
+    #[must_use]
+    pub fn new(allocator: IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        let double_frees = Cell::new(0);
+        let baseline = snapshot(&allocator, &double_frees);
+        Self {
+            allocator,
+            baseline,
+            double_frees,
+        }
+    }
+
+    /// Unwrap the [`IndexAllocator`].
+    #[must_use]
+    pub fn into_inner(self) -> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+        self.allocator
+    }
+
+    /// Get the balance of the allocator since it was wrapped.
+    #[must_use]
+    pub fn outstanding(&self) -> Outstanding {
+        self.outstanding_since(&self.baseline)
+    }
+
+    /// Check everything allocated since the allocator was wrapped was freed, once.
+    ///
+    /// # Panics
+    ///
+    /// The method panics with the balance and the allocations not freed, with their call site
+    /// if the `call-site` feature is enabled, if the allocator isn't balanced.
+    #[track_caller]
+    pub fn check(&self) {
+        self.check_since(&self.baseline);
+    }
+
+    /// Run `f`, checking everything it allocated was freed, once, see [`TrackingAllocator::check`].
+    ///
+    /// # Panics
+    ///
+    /// The method panics if the allocator isn't balanced once `f` returns, see [`TrackingAllocator::check`].
+    #[track_caller]
+    pub fn assert_balanced<R>(&self, f: impl FnOnce() -> R) -> R {
+        let before = snapshot(&self.allocator, &self.double_frees);
+        let result = f();
+        self.check_since(&before);
+        result
+    }
+
+    fn outstanding_since(&self, before: &Snapshot<INDEX_SIZE>) -> Outstanding {
+        let stats = self.allocator.heap_stats();
+        Outstanding {
+            allocations: (stats.allocations as isize).wrapping_sub(before.allocations as isize),
+            bytes: (stats.used_bytes as isize).wrapping_sub(before.used_bytes as isize),
+            double_frees: self.double_frees.get() - before.double_frees,
+        }
+    }
+
+    #[track_caller]
+    fn check_since(&self, before: &Snapshot<INDEX_SIZE>) {
+        let outstanding = self.outstanding_since(before);
+        if !outstanding.is_balanced() {
+            panic!(
+                "{}",
+                Imbalance {
+                    allocator: &self.allocator,
+                    before,
+                    outstanding,
+                }
+            );
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Deref
+    for TrackingAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+    type Target = IndexAllocator<MEMORY_SIZE, INDEX_SIZE>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.allocator
+    }
+}
+
+unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> GlobalAlloc
+    for TrackingAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocator.alloc(layout)
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.allocator.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        if ptr.is_null() {
+            return;
+        }
+        if self.allocator.try_free(ptr) == Err(IndexError::DoubleFree) {
+            self.double_frees.set(self.double_frees.get() + 1);
+        }
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        self.allocator.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Take a [`Snapshot`] of `allocator`, which recorded `double_frees`.
+fn snapshot<const MEMORY_SIZE: usize, const INDEX_SIZE: usize>(
+    allocator: &IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    double_frees: &Cell<usize>,
+) -> Snapshot<INDEX_SIZE> {
+    let stats = allocator.heap_stats();
+    let mut live = [None; INDEX_SIZE];
+    if let Ok(index) = allocator.index.try_borrow() {
+        for (slot, region) in live
+            .iter_mut()
+            .zip(index.regions().filter(|region| region.used))
+        {
+            *slot = Some(region.from);
+        }
+    }
+
+    Snapshot {
+        allocations: stats.allocations,
+        used_bytes: stats.used_bytes,
+        double_frees: double_frees.get(),
+        live,
+    }
+}
+
+/// The report of an unbalanced [`TrackingAllocator`], listing the allocations made since the reference point
+/// and not freed.
+struct Imbalance<'a, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    before: &'a Snapshot<INDEX_SIZE>,
+    outstanding: Outstanding,
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Display
+    for Imbalance<'_, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Outstanding {
+            allocations,
+            bytes,
+            double_frees,
+        } = self.outstanding;
+        write!(
+            f,
+            "unbalanced allocator: {allocations} allocations and {bytes} bytes outstanding, {double_frees} double frees"
+        )?;
+
+        let Ok(index) = self.allocator.index.try_borrow() else {
+            return Ok(());
+        };
+        for region in index
+            .regions()
+            .filter(|region| region.used && !self.before.live.contains(&Some(region.from)))
+        {
+            write!(
+                f,
+                "\n  not freed: {} bytes at offset {}",
+                region.size, region.from
+            )?;
+            #[cfg(feature = "call-site")]
+            if let Some(location) = region.location {
+                write!(f, ", allocated at {location}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Make the allocations fail with an [`IndexError::NoFittingRegion`] once `allocations` more were attempted,
+    /// to exercise the failure paths in tests, replacing the previous setting.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::{IndexAllocator, IndexError};
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// allocator.set_fail_after(1);
+    ///
+    /// let first = allocator.try_boxed(1u8).unwrap();
+    /// assert_eq!(allocator.try_boxed(2u8).err(), Some(IndexError::NoFittingRegion));
+    ///
+    /// allocator.clear_fail_after();
+    /// let second = allocator.try_boxed(2u8).unwrap();
+    /// ```
+    pub fn set_fail_after(&self, allocations: usize) {
+        self.fail_after.set(Some(allocations));
+    }
+
+    /// Stop injecting failures, see [`IndexAllocator::set_fail_after`].
+    pub fn clear_fail_after(&self) {
+        self.fail_after.set(None);
+    }
+
+    /// Fail an allocation if the failures injected with [`IndexAllocator::set_fail_after`] are due.
+    pub(crate) fn inject_failure(&self) -> Result<(), IndexError> {
+        match self.fail_after.get() {
+            Some(0) => Err(IndexError::NoFittingRegion),
+            Some(allocations) => {
+                self.fail_after.set(Some(allocations - 1));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracking_balanced() {
+        let allocator: TrackingAllocator<256, 16> = TrackingAllocator::new(IndexAllocator::empty());
+        let kept = allocator.try_boxed([0u8; 8]).unwrap();
+
+        allocator.assert_balanced(|| {
+            let layout = Layout::new::<u64>();
+            let ptr = unsafe { allocator.alloc(layout) };
+            let value = allocator.try_boxed([1u8; 16]).unwrap();
+            unsafe { allocator.dealloc(ptr, layout) };
+            drop(value);
+        });
+        assert_eq!(allocator.outstanding().allocations, 1);
+
+        drop(kept);
+        allocator.check();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "unbalanced allocator: 1 allocations and 16 bytes outstanding, 0 double frees\n  not freed: 16 bytes at offset 8"
+    )]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the offsets and sizes"
+    )]
+    fn test_tracking_leak() {
+        let allocator: TrackingAllocator<256, 16> = TrackingAllocator::new(IndexAllocator::empty());
+        let _kept = allocator.try_boxed([0u8; 8]).unwrap();
+
+        allocator.assert_balanced(|| {
+            core::mem::forget(allocator.try_boxed([1u8; 16]).unwrap());
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "0 allocations and 0 bytes outstanding, 1 double frees")]
+    fn test_tracking_double_free() {
+        let allocator: TrackingAllocator<256, 16> = TrackingAllocator::new(IndexAllocator::empty());
+        let layout = Layout::new::<u32>();
+
+        let ptr = unsafe { allocator.alloc(layout) };
+        unsafe {
+            allocator.dealloc(ptr, layout);
+            allocator.dealloc(ptr, layout);
+        }
+        assert_eq!(allocator.outstanding().double_frees, 1);
+        allocator.check();
+    }
+
+    #[test]
+    fn test_tracking_fail_after() {
+        let allocator: TrackingAllocator<256, 16> = TrackingAllocator::new(IndexAllocator::empty());
+        allocator.set_fail_after(2);
+
+        let layout = Layout::new::<u32>();
+        allocator.assert_balanced(|| {
+            let first = allocator.try_boxed(1u32).unwrap();
+            let second = unsafe { allocator.alloc(layout) };
+            assert!(unsafe { allocator.alloc(layout) }.is_null());
+            assert_eq!(
+                allocator.try_boxed(3u32).err(),
+                Some(IndexError::NoFittingRegion)
+            );
+            unsafe { allocator.dealloc(second, layout) };
+            drop(first);
+        });
+    }
+}
@@ -0,0 +1,188 @@
+//! This module contains the [`UninitIndexAllocator`], an [`IndexAllocator`] initialized at runtime,
+//! whose memory pool can live in RAM which isn't usable at startup, such as an external SDRAM.
+//!
+//! A `static` [`IndexAllocator`] has its memory pool zeroed by the startup code, before `main` runs.
+//! An [`UninitIndexAllocator`] isn't initialized at all: it can be placed in a linker section the startup code
+//! leaves alone, such as a `NOLOAD` section mapped to the external RAM, and initialized once the RAM is usable.
+//!
+//! Until then, every allocation fails with an [`IndexError::NotInitialized`] instead of reading uninitialized memory.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr;
+
+use crate::{IndexAllocator, IndexError};
+
+/// The value of the marker once the allocator is initialized, unlikely to be found in RAM which was never written.
+const INIT_MARKER: usize = 0x1DA1_10C8;
+
+/// An [`IndexAllocator`] left uninitialized until [`UninitIndexAllocator::init`] is called, see [`uninit`](crate::uninit).
+///
+/// Like [`SingleThreaded`](crate::sync::SingleThreaded), it is [`Sync`] without locking,
+/// so it must only be used by a single thread. It implements [`GlobalAlloc`],
+/// the allocations failing until it is initialized.
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::uninit::UninitIndexAllocator;
+/// use index_alloc::IndexError;
+///
+/// // On the target, the static would carry `#[link_section = ".sdram"]`.
+/// // Safety: the allocator is only used by the main thread.
+/// static SDRAM: UninitIndexAllocator<1024, 16> = unsafe { UninitIndexAllocator::uninit() };
+///
+/// assert_eq!(SDRAM.get().err(), Some(IndexError::NotInitialized));
+///
+/// // Once the memory controller is configured.
+/// let allocator = unsafe { SDRAM.init(false) };
+/// let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+/// assert_eq!(*test_box, [1, 2, 3, 4]);
+/// ```
+pub struct UninitIndexAllocator<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    /// Set to [`INIT_MARKER`] once the allocator is initialized, and holds whatever the RAM held before.
+    marker: UnsafeCell<MaybeUninit<usize>>,
+    allocator: UnsafeCell<MaybeUninit<IndexAllocator<MEMORY_SIZE, INDEX_SIZE>>>,
+}
+
+// Safety: the caller of `UninitIndexAllocator::uninit` guarantees the allocator is never used by two threads.
+unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Sync
+    for UninitIndexAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    UninitIndexAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Create an [`UninitIndexAllocator`], which is only usable once [`UninitIndexAllocator::init`] is called.
+    ///
+    /// # Safety
+    ///
+    /// The allocator must never be used by two threads, nor by an interrupt handler preempting its thread,
+    /// see [`SingleThreaded::new`](crate::sync::SingleThreaded::new).
+    #[must_use]
+    pub const unsafe fn uninit() -> Self {
+        Self {
+            marker: UnsafeCell::new(MaybeUninit::uninit()),
+            allocator: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Initialize the allocator with an empty index, zeroing its memory pool if `zeroed` is set,
+    /// and return it. The memory pool is written in place, never copied through the stack.
+    ///
+    /// Without zeroing, the memory pool keeps whatever the RAM held, so allocations must be written before being read,
+    /// as with [`GlobalAlloc::alloc`]. Once initialized, the allocator is returned as is by the following calls.
+    ///
+    /// # Safety
+    ///
+    /// The memory of the allocator must be usable, for instance once the external memory controller is configured.
+    pub unsafe fn init(&self, zeroed: bool) -> &IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+        if !self.is_initialized() {
+            IndexAllocator::init_in_place((*self.allocator.get()).as_mut_ptr(), zeroed);
+            ptr::write_volatile(self.marker.get().cast::<usize>(), INIT_MARKER);
+        }
+
+        (*self.allocator.get()).assume_init_ref()
+    }
+
+    /// Test if the allocator was initialized with [`UninitIndexAllocator::init`].
+    #[must_use]
+    pub fn is_initialized(&self) -> bool {
+        // The marker may never have been written, so it is read as is from memory rather than assumed to hold a value.
+        unsafe { ptr::read_volatile(self.marker.get().cast::<usize>()) == INIT_MARKER }
+    }
+
+    /// Get the allocator once it is initialized.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError::NotInitialized`] if [`UninitIndexAllocator::init`] wasn't called yet.
+    pub fn get(&self) -> Result<&IndexAllocator<MEMORY_SIZE, INDEX_SIZE>, IndexError> {
+        if !self.is_initialized() {
+            return Err(IndexError::NotInitialized);
+        }
+
+        Ok(unsafe { (*self.allocator.get()).assume_init_ref() })
+    }
+}
+
+unsafe impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> GlobalAlloc
+    for UninitIndexAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.get() {
+            Ok(allocator) => allocator.alloc(layout),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match self.get() {
+            Ok(allocator) => allocator.alloc_zeroed(layout),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Ok(allocator) = self.get() {
+            allocator.dealloc(ptr, layout);
+        }
+    }
+
+    #[cfg_attr(feature = "call-site", track_caller)]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        match self.get() {
+            Ok(allocator) => allocator.realloc(ptr, layout, new_size),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_phase_init() {
+        static SDRAM: UninitIndexAllocator<256, 8> = unsafe { UninitIndexAllocator::uninit() };
+        let layout = Layout::new::<[u8; 16]>();
+
+        assert!(!SDRAM.is_initialized());
+        assert_eq!(SDRAM.get().err(), Some(IndexError::NotInitialized));
+        assert!(unsafe { SDRAM.alloc(layout) }.is_null());
+
+        let allocator = unsafe { SDRAM.init(true) };
+        assert!(SDRAM.is_initialized());
+        assert_eq!(allocator.heap_stats().free_bytes, 256);
+        assert!(crate::tests::memory(allocator)
+            .iter()
+            .all(|&byte| byte == 0));
+
+        let ptr = unsafe { SDRAM.alloc(layout) };
+        assert!(allocator.owns(ptr));
+        assert_eq!(allocator.heap_stats().allocations, 1);
+
+        // Initializing again keeps the allocations.
+        assert!(core::ptr::eq(unsafe { SDRAM.init(true) }, allocator));
+        assert_eq!(allocator.heap_stats().allocations, 1);
+
+        unsafe { SDRAM.dealloc(ptr, layout) };
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_alloc_zeroed_without_zeroing() {
+        static SDRAM: UninitIndexAllocator<256, 8> = unsafe { UninitIndexAllocator::uninit() };
+
+        // Without zeroing, no byte of the memory pool is known to be zero, so every zeroed allocation is filled.
+        let allocator = unsafe { SDRAM.init(false) };
+        assert_eq!(allocator.untouched.get(), 256);
+
+        let array = allocator.try_alloc_array_zeroed::<u32>(8).unwrap();
+        assert!(unsafe { array.as_ref() }.iter().all(|&value| value == 0));
+    }
+}
@@ -0,0 +1,323 @@
+//! This module contains the [`IndexVec`] growable array, holding its values in an [`IndexAllocator`].
+
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::{mem, slice};
+
+use crate::{IndexAllocator, IndexError};
+
+/// The capacity of the first allocation of an [`IndexVec`].
+const MIN_CAPACITY: usize = 4;
+
+/// A growable array holding its values in an [`IndexAllocator`].
+///
+/// The capacity doubles when the [`IndexVec`] is full, moving the values to a new allocation,
+/// and the excess capacity can be given back to the [`IndexAllocator`] with [`IndexVec::shrink_to_fit`].
+///
+/// # Example
+///
+/// ```
+/// use index_alloc::vec::IndexVec;
+/// use index_alloc::IndexAllocator;
+///
+/// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+///
+/// let mut test_vec = IndexVec::new(&allocator);
+/// test_vec.try_push(1).unwrap();
+/// test_vec.try_push(2).unwrap();
+/// assert_eq!(*test_vec, [1, 2]);
+/// ```
+pub struct IndexVec<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> {
+    ptr: NonNull<T>,
+    len: usize,
+    capacity: usize,
+    allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    phantom_val: PhantomData<T>,
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize>
+    IndexVec<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    /// Create an empty [`IndexVec`], which doesn't allocate until a value is pushed.
+    #[must_use]
+    pub fn new(allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>) -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+            // Zero sized values never need any memory.
+            capacity: if mem::size_of::<T>() == 0 {
+                usize::MAX
+            } else {
+                0
+            },
+            allocator,
+            phantom_val: PhantomData,
+        }
+    }
+
+    /// Try to create an empty [`IndexVec`] able to hold `capacity` values without moving.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_with_capacity(
+        capacity: usize,
+        allocator: &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE>,
+    ) -> Result<Self, IndexError> {
+        let mut vec = Self::new(allocator);
+        vec.try_reserve(capacity)?;
+        Ok(vec)
+    }
+
+    /// Try to make room for at least `additional` more values, moving the values to a larger allocation if needed.
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the allocation failed, in which case the [`IndexVec`] is left unchanged.
+    /// Once the values moved, freeing the previous allocation can't fail the method anymore:
+    /// a failure leaks it and is flagged as a failed drop, see [`IndexAllocator::has_failed_drop`].
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), IndexError> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(IndexError::LayoutOverflow)?;
+        if required <= self.capacity {
+            return Ok(());
+        }
+
+        let capacity = required.max(self.capacity * 2).max(MIN_CAPACITY);
+        let new_ptr = self.allocator.try_alloc_array::<T>(capacity)?.cast::<T>();
+        unsafe { ptr::copy_nonoverlapping(self.ptr.as_ptr(), new_ptr.as_ptr(), self.len) };
+
+        let old_ptr = mem::replace(&mut self.ptr, new_ptr);
+        let old_capacity = mem::replace(&mut self.capacity, capacity);
+        if old_capacity > 0 {
+            let result = unsafe { self.allocator.try_free_array(old_ptr, old_capacity) };
+            self.allocator
+                .flag_drop_error("the previous allocation of an IndexVec", result);
+        }
+
+        Ok(())
+    }
+
+    /// Try to add a value at the end of the [`IndexVec`].
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the [`IndexVec`] was full and growing it failed.
+    #[cfg_attr(feature = "call-site", track_caller)]
+    pub fn try_push(&mut self, val: T) -> Result<(), IndexError> {
+        self.try_reserve(1)?;
+        unsafe { ptr::write(self.ptr.as_ptr().add(self.len), val) };
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Remove the last value and return it, or `None` if the [`IndexVec`] is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        Some(unsafe { ptr::read(self.ptr.as_ptr().add(self.len)) })
+    }
+
+    /// Try to give the excess capacity back to the [`IndexAllocator`], without moving the values.
+    /// An empty [`IndexVec`] frees its allocation entirely.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::vec::IndexVec;
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+    ///
+    /// let mut test_vec = IndexVec::try_with_capacity(16, &allocator).unwrap();
+    /// test_vec.try_push(1u8).unwrap();
+    /// let used_bytes = allocator.heap_stats().used_bytes;
+    ///
+    /// test_vec.shrink_to_fit().unwrap();
+    /// assert_eq!(test_vec.capacity(), 1);
+    /// assert_eq!(allocator.heap_stats().used_bytes, used_bytes - 15);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The method return an [`IndexError`] if the memory couldn't be given back,
+    /// in which case the [`IndexVec`] is left unchanged.
+    pub fn shrink_to_fit(&mut self) -> Result<(), IndexError> {
+        if mem::size_of::<T>() == 0 || self.capacity == self.len {
+            return Ok(());
+        }
+
+        if self.len == 0 {
+            unsafe { self.allocator.try_free_array(self.ptr, self.capacity)? };
+            self.ptr = NonNull::dangling();
+        } else {
+            unsafe {
+                self.allocator.try_shrink(
+                    self.ptr.as_ptr().cast::<u8>(),
+                    self.len * mem::size_of::<T>(),
+                )?;
+            }
+        }
+        self.capacity = self.len;
+
+        Ok(())
+    }
+
+    /// Return the number of values in the [`IndexVec`].
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Test if the [`IndexVec`] is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of values the [`IndexVec`] can hold without moving.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get a reference to the [`IndexAllocator`] used by the [`IndexVec`].
+    #[must_use]
+    pub fn allocator(&self) -> &'a IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+        self.allocator
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Drop
+    for IndexVec<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place::<[T]>(&mut **self) };
+
+        if mem::size_of::<T>() != 0 && self.capacity > 0 {
+            unsafe { self.allocator.drop_free("an IndexVec", self.ptr.as_ptr()) };
+        }
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Deref
+    for IndexVec<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> DerefMut
+    for IndexVec<'a, T, MEMORY_SIZE, INDEX_SIZE>
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<'a, T, const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Debug
+    for IndexVec<'a, T, MEMORY_SIZE, INDEX_SIZE>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::MemoryRegion;
+
+    use super::*;
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations don't fit in the memory pool"
+    )]
+    fn test_vec_push_pop() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        let mut test_vec = IndexVec::new(&allocator);
+        for i in 0..10u32 {
+            test_vec.try_push(i).unwrap();
+        }
+        assert_eq!(test_vec.len(), 10);
+        assert_eq!(test_vec.capacity(), 16);
+        assert_eq!(test_vec.pop(), Some(9));
+        assert_eq!(*test_vec, [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        drop(test_vec);
+        assert_eq!(
+            allocator.index.borrow().get_region(0),
+            Ok(MemoryRegion::new(0, 128, false))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations don't fit in the memory pool"
+    )]
+    fn test_vec_shrink_to_fit() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        let mut test_vec = IndexVec::new(&allocator);
+        for i in 0..16u32 {
+            test_vec.try_push(i).unwrap();
+        }
+        while test_vec.len() > 3 {
+            test_vec.pop();
+        }
+        let free_bytes = allocator.heap_stats().free_bytes;
+
+        test_vec.shrink_to_fit().unwrap();
+        assert_eq!(test_vec.capacity(), 3);
+        assert_eq!(*test_vec, [0, 1, 2]);
+        assert_eq!(allocator.heap_stats().free_bytes, free_bytes + 13 * 4);
+
+        while test_vec.pop().is_some() {}
+        test_vec.shrink_to_fit().unwrap();
+        assert_eq!(test_vec.capacity(), 0);
+        assert_eq!(allocator.heap_stats().free_bytes, 128);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+
+    #[test]
+    fn test_vec_reserve_keeps_moved_values() {
+        let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+
+        let mut test_vec = IndexVec::try_with_capacity(8, &allocator).unwrap();
+        test_vec.try_push(1u8).unwrap();
+        let _blocker = allocator.try_boxed(0u8).unwrap();
+        // Freed behind the back of the vec, so that freeing it again once the values moved fails.
+        unsafe { allocator.try_free(test_vec.ptr.as_ptr()).unwrap() };
+
+        assert_eq!(test_vec.try_reserve(64), Ok(()));
+        assert!(test_vec.capacity() >= 65);
+        assert_eq!(test_vec.len(), 1);
+        assert!(allocator.has_failed_drop());
+    }
+
+    #[test]
+    fn test_vec_zero_sized() {
+        let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+        let mut test_vec = IndexVec::new(&allocator);
+        for _ in 0..100 {
+            test_vec.try_push(()).unwrap();
+        }
+        assert_eq!(test_vec.len(), 100);
+        assert_eq!(allocator.heap_stats().allocations, 0);
+    }
+}
@@ -0,0 +1,200 @@
+//! This module contains the watchpoints of the allocator, a debugging aid reporting every allocation covering a given byte.
+//!
+//! It is only available with the `watch` feature. When a byte of the memory pool keeps getting corrupted,
+//! [`IndexAllocator::watch_offset`] tells which allocations ever covered it: the hook set with
+//! [`IndexAllocator::set_watch_hook`] is called whenever a region covering a watched offset is reserved or freed,
+//! once the index is released.
+//!
+//! The regions reported include the alignment padding and, with the `redzone` feature, the gaps around the values.
+//! The allocations made in bump mode and [`IndexAllocator::reset`] aren't reported.
+
+use core::cell::Cell;
+#[cfg(feature = "call-site")]
+use core::panic::Location;
+
+use crate::index::MemoryRegion;
+use crate::IndexAllocator;
+
+/// The number of offsets an allocator can watch at once.
+pub const WATCHPOINTS: usize = 4;
+
+/// What happened to a region covering a watched offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// The region was reserved by an allocation.
+    Alloc,
+    /// The region was freed.
+    Free,
+}
+
+/// The report of a region covering a watched offset, passed to the hook set with [`IndexAllocator::set_watch_hook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub kind: WatchKind,
+    /// The watched offset, from the start of the memory pool.
+    pub offset: usize,
+    /// The offset of the region covering it.
+    pub from: usize,
+    /// The size of the region covering it.
+    pub size: usize,
+    /// The call site which allocated the region, for a free as well.
+    #[cfg(feature = "call-site")]
+    pub location: Option<&'static Location<'static>>,
+}
+
+/// The offsets watched by an allocator, and the hook reporting them.
+pub(crate) struct Watchpoints {
+    offsets: Cell<[Option<usize>; WATCHPOINTS]>,
+    hook: Cell<Option<fn(WatchEvent)>>,
+}
+
+impl Watchpoints {
+    pub const fn new() -> Self {
+        Self {
+            offsets: Cell::new([None; WATCHPOINTS]),
+            hook: Cell::new(None),
+        }
+    }
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Watch the byte at `offset` from the start of the memory pool, reporting the allocations covering it
+    /// to the hook set with [`IndexAllocator::set_watch_hook`].
+    ///
+    /// Return `false` if the [`WATCHPOINTS`] are all taken by other offsets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use index_alloc::watch::{WatchEvent, WatchKind};
+    /// use index_alloc::IndexAllocator;
+    ///
+    /// fn report(event: WatchEvent) {
+    ///     assert_eq!(event.offset, 0);
+    ///     assert_eq!(event.from, 0);
+    /// }
+    ///
+    /// let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+    /// allocator.set_watch_hook(report);
+    /// assert!(allocator.watch_offset(0));
+    ///
+    /// let corrupted = allocator.try_boxed([0u8; 8]).unwrap();
+    /// ```
+    #[must_use = "the offset isn't watched if the watchpoints are all taken"]
+    pub fn watch_offset(&self, offset: usize) -> bool {
+        let mut offsets = self.watchpoints.offsets.get();
+        if offsets.contains(&Some(offset)) {
+            return true;
+        }
+        let Some(slot) = offsets.iter_mut().find(|slot| slot.is_none()) else {
+            return false;
+        };
+        *slot = Some(offset);
+        self.watchpoints.offsets.set(offsets);
+        true
+    }
+
+    /// Stop watching the byte at `offset`, see [`IndexAllocator::watch_offset`].
+    pub fn unwatch_offset(&self, offset: usize) {
+        let mut offsets = self.watchpoints.offsets.get();
+        for slot in &mut offsets {
+            if *slot == Some(offset) {
+                *slot = None;
+            }
+        }
+        self.watchpoints.offsets.set(offsets);
+    }
+
+    /// Set the hook called with the regions covering a watched offset, replacing the previous one.
+    ///
+    /// The hook is called in the allocation and free paths, so it must not allocate through the allocator it watches.
+    pub fn set_watch_hook(&self, hook: fn(WatchEvent)) {
+        self.watchpoints.hook.set(Some(hook));
+    }
+
+    /// Remove the hook set with [`IndexAllocator::set_watch_hook`].
+    pub fn clear_watch_hook(&self) {
+        self.watchpoints.hook.set(None);
+    }
+
+    /// Report `region` to the hook once for every watched offset it covers.
+    pub(crate) fn report_watch(&self, kind: WatchKind, region: &MemoryRegion) {
+        let Some(hook) = self.watchpoints.hook.get() else {
+            return;
+        };
+        for offset in self.watchpoints.offsets.get().into_iter().flatten() {
+            if offset >= region.from && offset - region.from < region.size {
+                hook(WatchEvent {
+                    kind,
+                    offset,
+                    from: region.from,
+                    size: region.size,
+                    #[cfg(feature = "call-site")]
+                    location: region.location,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use super::*;
+
+    static EVENTS: Mutex<Vec<WatchEvent>> = Mutex::new(Vec::new());
+
+    fn record(event: WatchEvent) {
+        EVENTS.lock().unwrap().push(event);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "redzone",
+        ignore = "the gaps around allocations change the offsets and sizes"
+    )]
+    fn test_watch_offset() {
+        let allocator: IndexAllocator<128, 16> = IndexAllocator::empty();
+        allocator.set_watch_hook(record);
+        assert!(allocator.watch_offset(20));
+        assert!(allocator.watch_offset(20));
+        assert!(allocator.watch_offset(100));
+        assert!(allocator.watch_offset(101));
+        assert!(allocator.watch_offset(102));
+        assert!(!allocator.watch_offset(103));
+        allocator.unwatch_offset(101);
+        allocator.unwatch_offset(102);
+
+        let first = allocator.try_boxed([0u8; 16]).unwrap();
+        let second = allocator.try_boxed([0u8; 16]).unwrap();
+        let third = allocator.try_boxed([0u8; 16]).unwrap();
+        drop(second);
+        let fourth = allocator.try_boxed([0u8; 8]).unwrap();
+        let fifth = allocator.try_boxed([0u8; 8]).unwrap();
+        drop(first);
+        drop(fifth);
+        drop(third);
+        drop(fourth);
+
+        let alloc = |from, size| (WatchKind::Alloc, 20, from, size);
+        let free = |from, size| (WatchKind::Free, 20, from, size);
+        let events: Vec<_> = EVENTS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| (event.kind, event.offset, event.from, event.size))
+            .collect();
+        assert_eq!(
+            events,
+            [alloc(16, 16), free(16, 16), alloc(16, 8), free(16, 8),]
+        );
+        #[cfg(feature = "call-site")]
+        assert!(EVENTS
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|event| event.location.is_some()));
+    }
+}
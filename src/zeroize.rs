@@ -0,0 +1,135 @@
+//! This module contains the secure wipe of the `zeroize` feature, so that secrets such as cryptographic keys
+//! don't linger in the memory pool once freed.
+//!
+//! With the feature, the bytes given back by a free or a shrink are overwritten with zeros, and so is every pool
+//! on [`IndexAllocator::reset`]. The whole region is wiped, including its alignment padding and redzones.
+//! The writes are volatile, so the compiler can't elide them even if the memory is never read again.
+//!
+//! The allocations made in bump mode are only wiped by [`IndexAllocator::reset`], as they are never freed individually.
+//! Once wiped, the end of the memory pool is known to be zero again, which [`GlobalAlloc::alloc_zeroed`](core::alloc::GlobalAlloc::alloc_zeroed)
+//! doesn't fill a second time.
+//! A scrub byte set with [`IndexAllocator::set_free_scrub`] is written over the zeros.
+
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::IndexAllocator;
+
+/// Overwrite the `len` bytes at `start` with zeros, with writes the compiler can't elide nor move after the following code.
+///
+/// # Safety
+///
+/// `start` must be valid for writes of `len` bytes.
+pub(crate) unsafe fn wipe_bytes(start: *mut u8, len: usize) {
+    for i in 0..len {
+        ptr::write_volatile(start.add(i), 0);
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> IndexAllocator<MEMORY_SIZE, INDEX_SIZE> {
+    /// Overwrite `size` bytes of the memory pool with zeros, starting at `from` (relative to the memory pool).
+    /// The bytes must be in a single pool, as the bytes of a region are.
+    ///
+    /// Bytes wiped up to the ones never handed out join them, so that [`GlobalAlloc::alloc_zeroed`](core::alloc::GlobalAlloc::alloc_zeroed)
+    /// doesn't zero them again, unless a scrub byte is about to be written over the zeros.
+    pub(crate) unsafe fn wipe(&self, from: usize, size: usize) {
+        wipe_bytes(self.ptr_at(from), size);
+        let untouched = self.untouched.get();
+        if self.free_scrub.get().is_none()
+            && from < untouched
+            && from.saturating_add(size) >= untouched
+        {
+            self.untouched.set(from);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boxed::Box;
+    use crate::tests::memory;
+    use crate::IndexAllocator;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_zeroize_on_free() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        // The key is aligned after a byte, so its region starts with padding.
+        let byte = allocator.try_boxed(0x5Au8).unwrap();
+        let key = allocator.try_boxed(0xA5A5_A5A5_A5A5_A5A5u64).unwrap();
+        assert!(memory(&allocator).contains(&0xA5));
+
+        drop(key);
+        drop(byte);
+        assert!(memory(&allocator).iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_zeroize_on_shrink() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        let mut key: Box<[u8], 128, 8> = allocator.try_boxed([0xA5u8; 32]).unwrap().into();
+        key.try_shrink_to(8).unwrap();
+        assert_eq!(
+            memory(&allocator)
+                .iter()
+                .filter(|byte| **byte == 0xA5)
+                .count(),
+            8
+        );
+
+        drop(key);
+        assert!(memory(&allocator).iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_zeroize_on_reset() {
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+
+        allocator.enter_bump_mode().unwrap();
+        allocator.try_leak([0xA5u8; 16]).unwrap();
+        allocator.exit_bump_mode().unwrap();
+        allocator.try_leak([0xA5u8; 16]).unwrap();
+
+        unsafe { allocator.reset() }.unwrap();
+        assert!(memory(&allocator).iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_zeroize_alloc_zeroed_after_free() {
+        use core::alloc::{GlobalAlloc, Layout};
+
+        let allocator: IndexAllocator<128, 8> = IndexAllocator::empty();
+        let layout = Layout::array::<u8>(32).unwrap();
+
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            ptr.write_bytes(0xA5, 32);
+            assert!(allocator.untouched.get() > 0);
+
+            // The wiped allocation was the last one handed out, so its bytes are known to be zero again.
+            allocator.dealloc(ptr, layout);
+            assert_eq!(allocator.untouched.get(), 0);
+            let zeroed = allocator.alloc_zeroed(layout);
+            assert_eq!(zeroed, ptr);
+            assert!(core::slice::from_raw_parts(zeroed, 32)
+                .iter()
+                .all(|&byte| byte == 0));
+
+            // A scrub byte written over the zeros keeps the bytes touched.
+            allocator.set_free_scrub(Some(0xCD));
+            allocator.dealloc(zeroed, layout);
+            assert!(allocator.untouched.get() > 0);
+            let scrubbed = allocator.alloc_zeroed(layout);
+            assert!(core::slice::from_raw_parts(scrubbed, 32)
+                .iter()
+                .all(|&byte| byte == 0));
+            allocator.dealloc(scrubbed, layout);
+        }
+    }
+}
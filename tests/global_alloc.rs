@@ -0,0 +1,156 @@
+//! The allocation suite shared by every allocator of the crate, run through their [`GlobalAlloc`] implementation.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::vec::Vec;
+
+use index_alloc::header::HeaderIndexAllocator;
+use index_alloc::IndexAllocator;
+
+/// An allocator the suite runs against, reporting its live allocations.
+trait Allocations: GlobalAlloc {
+    fn allocations(&self) -> usize;
+}
+
+impl<const MEMORY_SIZE: usize, const INDEX_SIZE: usize> Allocations
+    for IndexAllocator<MEMORY_SIZE, INDEX_SIZE>
+{
+    fn allocations(&self) -> usize {
+        self.heap_stats().allocations
+    }
+}
+
+impl<const MEMORY_SIZE: usize> Allocations for HeaderIndexAllocator<MEMORY_SIZE> {
+    fn allocations(&self) -> usize {
+        self.heap_stats().allocations
+    }
+}
+
+fn check_alloc_free(allocator: &impl Allocations) {
+    let layouts = [
+        Layout::new::<u8>(),
+        Layout::new::<u64>(),
+        Layout::from_size_align(24, 8).unwrap(),
+        Layout::from_size_align(7, 1).unwrap(),
+        Layout::from_size_align(16, 16).unwrap(),
+    ];
+
+    unsafe {
+        let ptrs: Vec<*mut u8> = layouts
+            .iter()
+            .enumerate()
+            .map(|(i, layout)| {
+                let ptr = allocator.alloc(*layout);
+                assert!(!ptr.is_null());
+                assert_eq!(ptr as usize % layout.align(), 0);
+                ptr.write_bytes(i as u8, layout.size());
+                ptr
+            })
+            .collect();
+        assert_eq!(allocator.allocations(), layouts.len());
+
+        // No allocation overwrote another one.
+        for (i, (ptr, layout)) in ptrs.iter().zip(layouts).enumerate() {
+            let bytes = core::slice::from_raw_parts(*ptr, layout.size());
+            assert!(bytes.iter().all(|&byte| byte == i as u8));
+        }
+
+        for (ptr, layout) in ptrs.into_iter().zip(layouts) {
+            allocator.dealloc(ptr, layout);
+        }
+    }
+    assert_eq!(allocator.allocations(), 0);
+}
+
+fn check_exhaustion(allocator: &impl Allocations) {
+    let layout = Layout::new::<[u64; 4]>();
+
+    unsafe {
+        let mut ptrs = Vec::new();
+        loop {
+            let ptr = allocator.alloc(layout);
+            if ptr.is_null() {
+                break;
+            }
+            ptrs.push(ptr);
+        }
+        assert!(!ptrs.is_empty());
+
+        // Everything freed, the memory serves allocations again.
+        for ptr in ptrs {
+            allocator.dealloc(ptr, layout);
+        }
+        let ptr = allocator.alloc(layout);
+        assert!(!ptr.is_null());
+        allocator.dealloc(ptr, layout);
+    }
+    assert_eq!(allocator.allocations(), 0);
+}
+
+fn check_alloc_zeroed(allocator: &impl Allocations) {
+    let layout = Layout::array::<u8>(64).unwrap();
+
+    unsafe {
+        let dirty = allocator.alloc(layout);
+        assert!(!dirty.is_null());
+        dirty.write_bytes(0xAA, 64);
+        allocator.dealloc(dirty, layout);
+
+        let zeroed = allocator.alloc_zeroed(layout);
+        assert!(!zeroed.is_null());
+        assert!(core::slice::from_raw_parts(zeroed, 64)
+            .iter()
+            .all(|&byte| byte == 0));
+        allocator.dealloc(zeroed, layout);
+    }
+    assert_eq!(allocator.allocations(), 0);
+}
+
+fn check_realloc(allocator: &impl Allocations) {
+    let layout = Layout::array::<u8>(16).unwrap();
+
+    unsafe {
+        let mut ptr = allocator.alloc(layout);
+        assert!(!ptr.is_null());
+        for i in 0..16 {
+            ptr.add(i).write(i as u8);
+        }
+        let blocker = allocator.alloc(layout);
+        assert!(!blocker.is_null());
+
+        // Grown, shrunk and grown again, past the allocation right after it, the data is kept.
+        let mut size = 16;
+        for new_size in [64, 8, 96] {
+            ptr = allocator.realloc(ptr, Layout::array::<u8>(size).unwrap(), new_size);
+            assert!(!ptr.is_null());
+            size = new_size;
+            let kept = core::slice::from_raw_parts(ptr, size.min(8));
+            assert!(kept.iter().enumerate().all(|(i, &byte)| byte == i as u8));
+        }
+        assert_eq!(allocator.allocations(), 2);
+
+        // A growth no free memory can hold fails, and leaves the allocation alone.
+        let old_layout = Layout::array::<u8>(size).unwrap();
+        assert!(allocator.realloc(ptr, old_layout, 4096).is_null());
+        assert_eq!(*ptr.add(7), 7);
+
+        allocator.dealloc(blocker, layout);
+        allocator.dealloc(ptr, old_layout);
+    }
+    assert_eq!(allocator.allocations(), 0);
+}
+
+#[test]
+fn test_index_allocator() {
+    check_alloc_free(&IndexAllocator::<512, 16>::empty());
+    check_exhaustion(&IndexAllocator::<512, 16>::empty());
+    check_alloc_zeroed(&IndexAllocator::<512, 16>::empty());
+    check_realloc(&IndexAllocator::<512, 16>::empty());
+}
+
+#[test]
+fn test_header_index_allocator() {
+    check_alloc_free(&HeaderIndexAllocator::<512>::empty());
+    check_exhaustion(&HeaderIndexAllocator::<512>::empty());
+    check_alloc_zeroed(&HeaderIndexAllocator::<512>::empty());
+    check_realloc(&HeaderIndexAllocator::<512>::empty());
+}
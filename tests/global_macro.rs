@@ -0,0 +1,47 @@
+//! The global allocator replaces the allocator of the whole test binary,
+//! so this test runs without the test harness to control every allocation.
+
+use std::hint::black_box;
+
+/// The size of the memory pool, bounded by the `compact-index` feature.
+#[cfg(feature = "compact-index")]
+const HEAP_SIZE: usize = index_alloc::COMPACT_MEMORY_SIZE;
+#[cfg(not(feature = "compact-index"))]
+const HEAP_SIZE: usize = 65536;
+
+// Safety: the test runs without the harness, on the main thread only.
+index_alloc::index_global_alloc!(unsafe static HEAP: HEAP_SIZE, 256);
+
+/// The bytes reserved around each allocation on top of its size.
+#[cfg(feature = "redzone")]
+const GAPS: usize = 2 * index_alloc::redzone::REDZONE_SIZE;
+#[cfg(not(feature = "redzone"))]
+const GAPS: usize = 0;
+
+fn main() {
+    let before = heap_stats();
+    assert_eq!(before.used_bytes + before.free_bytes, HEAP_SIZE);
+
+    let test_vec: Vec<u8> = black_box(Vec::with_capacity(100));
+    let with_vec = heap_stats();
+    assert_eq!(with_vec.used_bytes, before.used_bytes + 100 + GAPS);
+    assert_eq!(with_vec.allocations, before.allocations + 1);
+
+    drop(test_vec);
+    assert_eq!(heap_stats(), before);
+
+    // A vector growing into the free memory after it keeps its buffer in place.
+    let mut growing: Vec<u8> = Vec::with_capacity(16);
+    growing.extend_from_slice(&[7; 16]);
+    let buffer = growing.as_ptr();
+    growing.reserve_exact(48);
+    assert_eq!(growing.as_ptr(), buffer);
+    assert_eq!(growing, [7; 16]);
+    assert_eq!(heap_stats().allocations, before.allocations + 1);
+    drop(growing);
+
+    // An exhausted memory pool is reported as an error instead of aborting.
+    let mut huge_vec: Vec<u8> = Vec::new();
+    assert!(huge_vec.try_reserve(HEAP_SIZE + 1).is_err());
+    assert_eq!(heap_stats(), before);
+}
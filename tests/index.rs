@@ -0,0 +1,43 @@
+use index_alloc::index::{MemoryIndex, MemoryRegion};
+
+#[test]
+fn test_sort_merge_fixture() {
+    let mut index: MemoryIndex<8> = MemoryIndex::from_regions([
+        Some(MemoryRegion::new(48, 16, false)),
+        None,
+        Some(MemoryRegion::new(16, 16, true)),
+        Some(MemoryRegion::new(32, 16, false)),
+        Some(MemoryRegion::new(0, 16, false)),
+        None,
+        None,
+        None,
+    ])
+    .unwrap();
+    assert!(!index.is_sorted());
+
+    index.sort_merge();
+
+    assert!(index.is_sorted());
+    assert!(index.regions().eq([
+        MemoryRegion::new(0, 16, false),
+        MemoryRegion::new(16, 16, true),
+        MemoryRegion::new(32, 32, false),
+    ]));
+}
+
+#[test]
+fn test_invalid_fixtures() {
+    let gap = MemoryIndex::<2>::from_regions([
+        Some(MemoryRegion::new(0, 16, false)),
+        Some(MemoryRegion::new(32, 16, false)),
+    ]);
+    let overlap = MemoryIndex::<2>::from_regions([
+        Some(MemoryRegion::new(0, 32, false)),
+        Some(MemoryRegion::new(16, 16, true)),
+    ]);
+    let empty = MemoryIndex::<2>::from_regions([None, None]);
+
+    assert!(gap.is_none());
+    assert!(overlap.is_none());
+    assert!(empty.is_none());
+}
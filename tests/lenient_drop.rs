@@ -0,0 +1,39 @@
+//! The leak counter is shared by every allocator, so this test lives in its own binary to count only its leaks.
+
+use std::cell::Cell;
+
+use index_alloc::rc::Rc;
+use index_alloc::{leak, IndexAllocator, IndexError};
+
+thread_local! {
+    static HOOKED: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count_leak(err: IndexError) {
+    assert_eq!(err, IndexError::IndexAlreadyBorrowed);
+    HOOKED.with(|hooked| hooked.set(hooked.get() + 1));
+}
+
+#[test]
+fn test_lenient_drop_leaks() {
+    let allocator: IndexAllocator<256, 8> = IndexAllocator::empty();
+    allocator.set_leak_hook(count_leak);
+
+    let test_box = allocator.try_boxed([1u8, 2, 3, 4]).unwrap();
+    let test_rc = Rc::try_new([5u8, 6, 7, 8], &allocator).unwrap();
+    let test_weak = test_rc.downgrade();
+    let allocations = allocator.heap_stats().allocations;
+
+    {
+        // Hold the index so that every free fails.
+        let _index = allocator.borrow_index();
+        drop(test_box);
+        drop(test_rc);
+        drop(test_weak);
+    }
+
+    assert_eq!(leak::leaked_drops(), 3);
+    assert_eq!(HOOKED.with(Cell::get), 3);
+    assert!(allocator.has_failed_drop());
+    assert_eq!(allocator.heap_stats().allocations, allocations);
+}
@@ -0,0 +1,96 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::vec::Vec;
+
+use index_alloc::sync::Locked;
+
+static ALLOCATOR: Locked<16384, 128> = Locked::empty();
+static STATS_ALLOCATOR: Locked<16384, 128> = Locked::empty();
+static SEND_ALLOCATOR: Locked<1024, 16> = Locked::empty();
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_locked_threads() {
+    thread::scope(|scope| {
+        for thread in 0..4u8 {
+            scope.spawn(move || {
+                let layout = Layout::array::<u8>(16).unwrap();
+                for _ in 0..500 {
+                    let ptrs: Vec<*mut u8> =
+                        (0..8).map(|_| unsafe { ALLOCATOR.alloc(layout) }).collect();
+                    for &ptr in &ptrs {
+                        assert!(!ptr.is_null());
+                        unsafe { ptr.write_bytes(thread, layout.size()) };
+                    }
+                    for ptr in ptrs {
+                        let bytes = unsafe { std::slice::from_raw_parts(ptr, layout.size()) };
+                        assert!(bytes.iter().all(|&byte| byte == thread));
+                        unsafe { ALLOCATOR.dealloc(ptr, layout) };
+                    }
+                }
+            });
+        }
+    });
+
+    let stats = ALLOCATOR.heap_stats();
+    assert_eq!(stats.allocations, 0);
+    assert_eq!(stats.free_bytes, 16384);
+    assert!(ALLOCATOR.with(|allocator| !allocator.has_failed_drop()));
+}
+
+#[test]
+fn test_locked_stats_while_allocating() {
+    let done = AtomicBool::new(false);
+    let layout = Layout::array::<u8>(64).unwrap();
+
+    thread::scope(|scope| {
+        let writers: Vec<_> = (0..2)
+            .map(|_| {
+                scope.spawn(|| {
+                    for _ in 0..500 {
+                        let ptrs: Vec<*mut u8> = (0..8)
+                            .map(|_| unsafe { STATS_ALLOCATOR.alloc(layout) })
+                            .collect();
+                        for ptr in ptrs {
+                            assert!(!ptr.is_null());
+                            unsafe { STATS_ALLOCATOR.dealloc(ptr, layout) };
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        scope.spawn(|| {
+            let mut reads = 0;
+            while !done.load(Ordering::Relaxed) || reads == 0 {
+                let stats = STATS_ALLOCATOR.heap_stats();
+                assert!(stats.allocations <= 16);
+                assert!(stats.used_bytes <= 16384);
+                assert!(stats.free_bytes <= 16384);
+                reads += 1;
+            }
+        });
+
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+
+    let stats = STATS_ALLOCATOR.heap_stats();
+    assert_eq!(stats.allocations, 0);
+    assert_eq!(stats.free_bytes, 16384);
+}
+
+#[test]
+fn test_locked_send() {
+    // The allocator itself is shared between threads, the boxes stay in the critical section.
+    assert_send_sync::<Locked<1024, 16>>();
+
+    let value = SEND_ALLOCATOR.with(|allocator| *allocator.try_boxed(42u32).unwrap());
+    let handle = thread::spawn(move || value + 1);
+    assert_eq!(handle.join().unwrap(), 43);
+    assert_eq!(SEND_ALLOCATOR.heap_stats().allocations, 0);
+}
@@ -0,0 +1,98 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::string::{String, ToString};
+use std::sync::Mutex;
+use std::vec::Vec;
+
+use index_alloc::IndexAllocator;
+use log::{Level, Log, Metadata, Record};
+
+struct CapturingLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    records: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn test_log_records() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+    unsafe {
+        let ptr = allocator.alloc(Layout::from_size_align(128, 1).unwrap());
+        assert!(ptr.is_null());
+
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptr = allocator.alloc(layout);
+        allocator.dealloc(ptr, layout);
+        allocator.dealloc(ptr, layout);
+
+        let records = LOGGER.records.lock().unwrap();
+        assert_eq!(
+            *records,
+            [
+                (
+                    Level::Warn,
+                    "Failed to allocate Layout { size: 128, align: 1 (1 << 0) }: \
+                     no free region fits the allocation (largest free block: 64 bytes)"
+                        .to_string()
+                ),
+                (Level::Error, format!("Double free of {ptr:p}")),
+            ]
+        );
+    }
+
+    unsafe {
+        let other: IndexAllocator<64, 8> = IndexAllocator::empty();
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        let ptr = other.alloc(layout);
+        allocator.dealloc(ptr, layout);
+
+        let records = LOGGER.records.lock().unwrap();
+        let (level, message) = records.last().unwrap();
+        assert_eq!(*level, Level::Error);
+        assert!(message.starts_with(&format!(
+            "{ptr:p} is freed through an allocator which doesn't own it"
+        )));
+        drop(records);
+        other.dealloc(ptr, layout);
+    }
+
+    #[cfg(feature = "redzone")]
+    unsafe {
+        let layout = Layout::from_size_align(4, 1).unwrap();
+        let ptr = allocator.alloc(layout);
+        ptr.add(4).write(0);
+        allocator.dealloc(ptr, layout);
+
+        let records = LOGGER.records.lock().unwrap();
+        let corruption =
+            format!("Heap corruption detected while freeing {ptr:p}: buffer overrun at offset ");
+        let detected = records
+            .iter()
+            .position(|(level, message)| *level == Level::Error && message.starts_with(&corruption))
+            .unwrap();
+        // The corruption also poisons the allocator, which is logged right after.
+        assert!(records[detected + 1..]
+            .iter()
+            .any(|(_, message)| message.starts_with("Allocator poisoned")));
+    }
+}
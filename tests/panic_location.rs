@@ -0,0 +1,39 @@
+//! The panic hook is global, so this test lives in its own binary to capture the only panic.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use index_alloc::boxed::Box;
+use index_alloc::IndexAllocator;
+
+/// Run `f`, which must panic, and return the file and line of the panic.
+fn panic_location(f: impl FnOnce()) -> (String, u32) {
+    let location = Arc::new(Mutex::new(None));
+    let hook_location = location.clone();
+    panic::set_hook(std::boxed::Box::new(move |info| {
+        let panic_location = info.location().unwrap();
+        *hook_location.lock().unwrap() =
+            Some((panic_location.file().to_owned(), panic_location.line()));
+    }));
+
+    assert!(panic::catch_unwind(AssertUnwindSafe(f)).is_err());
+    let _ = panic::take_hook();
+
+    let location = location.lock().unwrap().take();
+    location.unwrap()
+}
+
+#[test]
+fn test_out_of_memory_panic_location() {
+    let allocator: IndexAllocator<64, 8> = IndexAllocator::empty();
+
+    let (file, line) = panic_location(|| {
+        let _ = allocator.boxed([0u8; 128]);
+    });
+    assert_eq!((file.as_str(), line), (file!(), line!() - 2));
+
+    let (file, line) = panic_location(|| {
+        let _: Box<[u8; 128], 64, 8> = Box::new([0; 128], &allocator);
+    });
+    assert_eq!((file.as_str(), line), (file!(), line!() - 2));
+}
@@ -0,0 +1,33 @@
+//! The prelude is meant for user code, so this test only uses the public API, through a single import.
+
+use index_alloc::prelude::*;
+
+static ALLOCATOR: SingleThreaded<256, 16> = unsafe { SingleThreaded::empty() };
+
+#[cfg(feature = "critical-section")]
+static LOCKED: Locked<256, 16> = Locked::empty();
+
+#[test]
+fn test_prelude() {
+    let allocator: &IndexAllocator<256, 16> = &ALLOCATOR;
+
+    let static_box: StaticBox<u32, 256, 16> = ALLOCATOR.try_boxed(1).unwrap();
+    let test_box: Box<[u8; 4], 256, 16> = Box::try_new([1, 2, 3, 4], allocator).unwrap();
+    let test_rc = Rc::try_new(*static_box, allocator).unwrap();
+    let test_weak: Weak<u32, 256, 16> = test_rc.downgrade();
+    let mut test_vec = IndexVec::new(allocator);
+    test_vec.try_push(test_box[3]).unwrap();
+
+    assert_eq!(test_weak.upgrade().as_deref(), Some(&1));
+    assert_eq!(*test_vec, [4]);
+
+    let stats: HeapStats = allocator.heap_stats();
+    assert_eq!(stats.allocations, 5);
+    assert_eq!(
+        allocator.try_boxed([0u8; 512]).err(),
+        Some(IndexError::NoFittingRegion)
+    );
+
+    #[cfg(feature = "critical-section")]
+    LOCKED.with(|allocator| assert!(allocator.try_boxed(1u8).is_ok()));
+}